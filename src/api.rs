@@ -0,0 +1,160 @@
+use crate::errors::Result;
+use crate::{
+    tasklists, Service, Task, TaskInsertOptions, TaskOptions, Tasklist, Tasklists,
+    TasklistsOptions, Tasks,
+};
+
+/// Operations of the Google Tasks API.
+///
+/// Implemented by [`Service`] and by the in-memory [`crate::FakeTasks`],
+/// so that code written against the trait can be tested without network.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait TasksApi: Send + Sync {
+    /// Returns all the authenticated user's task lists.
+    async fn list_tasklists(&self, opt: Option<TasklistsOptions>) -> Result<Tasklists>;
+
+    /// Returns all the authenticated user's task lists, following the page tokens.
+    async fn list_all_tasklists(&self) -> Result<Vec<Tasklist>> {
+        tasklists::list_all(self).await
+    }
+
+    /// Returns the authenticated user's specified task list.
+    async fn get_tasklist(&self, id: &str) -> Result<Tasklist>;
+
+    /// Creates a new task list and adds it to the authenticated user's task lists.
+    async fn insert_tasklist(&self, v: Tasklist) -> Result<Tasklist>;
+
+    /// Updates the authenticated user's specified task list.
+    async fn update_tasklist(&self, v: Tasklist) -> Result<Tasklist>;
+
+    /// Deletes the authenticated user's specified task list.
+    async fn delete_tasklist(&self, id: &str) -> Result<()>;
+
+    /// Updates the authenticated user's specified task list. This method supports patch semantics.
+    async fn patch_tasklist(&self, tasklist_id: &str, v: Tasklist) -> Result<Tasklist>;
+
+    /// Returns all tasks in the specified task list, `None` if the etag matches.
+    async fn list_tasks(
+        &self,
+        tasklist_id: &str,
+        opt: Option<TaskOptions>,
+        etag: Option<String>,
+    ) -> Result<Option<Tasks>>;
+
+    /// Returns the specified task, `None` if the etag matches.
+    async fn get_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        etag: Option<String>,
+    ) -> Result<Option<Task>>;
+
+    /// Creates a new task on the specified task list.
+    async fn insert_task(
+        &self,
+        tasklist_id: &str,
+        v: Task,
+        opts: Option<TaskInsertOptions>,
+    ) -> Result<Task>;
+
+    /// Updates the specified task.
+    async fn update_task(&self, tasklist_id: &str, v: Task) -> Result<Task>;
+
+    /// Deletes the specified task from the task list.
+    async fn delete_task(&self, tasklist_id: &str, task_id: &str) -> Result<()>;
+
+    /// Clears all completed tasks from the specified task list.
+    async fn clear_tasks(&self, tasklist_id: &str) -> Result<()>;
+
+    /// Moves the specified task to another position in the task list.
+    async fn move_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        opts: TaskInsertOptions,
+    ) -> Result<Task>;
+
+    /// Updates the specified task. This method supports patch semantics.
+    async fn patch_task(&self, tasklist_id: &str, task_id: &str, v: Task) -> Result<Task>;
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl TasksApi for Service {
+    async fn list_tasklists(&self, opt: Option<TasklistsOptions>) -> Result<Tasklists> {
+        Service::list_tasklists(self, opt).await
+    }
+
+    async fn get_tasklist(&self, id: &str) -> Result<Tasklist> {
+        Service::get_tasklist(self, id).await
+    }
+
+    async fn insert_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+        Service::insert_tasklist(self, v).await
+    }
+
+    async fn update_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+        Service::update_tasklist(self, v).await
+    }
+
+    async fn delete_tasklist(&self, id: &str) -> Result<()> {
+        Service::delete_tasklist(self, id).await
+    }
+
+    async fn patch_tasklist(&self, tasklist_id: &str, v: Tasklist) -> Result<Tasklist> {
+        Service::patch_tasklist(self, tasklist_id, v).await
+    }
+
+    async fn list_tasks(
+        &self,
+        tasklist_id: &str,
+        opt: Option<TaskOptions>,
+        etag: Option<String>,
+    ) -> Result<Option<Tasks>> {
+        Service::list_tasks(self, tasklist_id, opt, etag).await
+    }
+
+    async fn get_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        etag: Option<String>,
+    ) -> Result<Option<Task>> {
+        Service::get_task(self, tasklist_id, task_id, etag).await
+    }
+
+    async fn insert_task(
+        &self,
+        tasklist_id: &str,
+        v: Task,
+        opts: Option<TaskInsertOptions>,
+    ) -> Result<Task> {
+        Service::insert_task(self, tasklist_id, v, opts).await
+    }
+
+    async fn update_task(&self, tasklist_id: &str, v: Task) -> Result<Task> {
+        Service::update_task(self, tasklist_id, v).await
+    }
+
+    async fn delete_task(&self, tasklist_id: &str, task_id: &str) -> Result<()> {
+        Service::delete_task(self, tasklist_id, task_id).await
+    }
+
+    async fn clear_tasks(&self, tasklist_id: &str) -> Result<()> {
+        Service::clear_tasks(self, tasklist_id).await
+    }
+
+    async fn move_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        opts: TaskInsertOptions,
+    ) -> Result<Task> {
+        Service::move_task(self, tasklist_id, task_id, opts).await
+    }
+
+    async fn patch_task(&self, tasklist_id: &str, task_id: &str, v: Task) -> Result<Task> {
+        Service::patch_task(self, tasklist_id, task_id, v).await
+    }
+}
@@ -0,0 +1,447 @@
+//! Full account backup and restore.
+//!
+//! A backup holds every task list with all its tasks, including the completed,
+//! hidden and deleted ones. It is written either as a versioned JSON document
+//! or, with the `csv` feature, as a CSV file with one row per task.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::{
+    Result,
+    TasksError::{ParseError, ResponseError},
+};
+use crate::{tasklists, tasks, Task, TaskOptions, Tasklist, TasksApi};
+
+/// Version of the JSON backup document written by this crate.
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Snapshot of all the task lists and tasks of an account.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Backup {
+    /// Version of the backup document format.
+    pub version: u32,
+
+    /// Time the backup was started at.
+    pub created_at: DateTime<Utc>,
+
+    pub tasklists: Vec<TasklistBackup>,
+}
+
+/// Task list with all its tasks.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TasklistBackup {
+    pub tasklist: Tasklist,
+    pub tasks: Vec<Task>,
+}
+
+/// Mapping from the identifiers in a backup to the identifiers created by [`restore`].
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct IdMapping {
+    pub tasklists: HashMap<String, String>,
+    pub tasks: HashMap<String, String>,
+}
+
+#[cfg(feature = "csv")]
+#[derive(Deserialize, Serialize)]
+struct CsvRow {
+    tasklist_id: String,
+    tasklist_title: Option<String>,
+    tasklist_updated: Option<DateTime<Utc>>,
+    task_id: Option<String>,
+    parent: Option<String>,
+    position: Option<String>,
+    title: Option<String>,
+    notes: Option<String>,
+    status: Option<crate::TaskStatus>,
+    due: Option<DateTime<Utc>>,
+    completed: Option<DateTime<Utc>>,
+    updated: Option<DateTime<Utc>>,
+    deleted: Option<bool>,
+    hidden: Option<bool>,
+}
+
+/// Fetches all the task lists and their tasks.
+pub async fn backup<A: TasksApi + ?Sized>(service: &A) -> Result<Backup> {
+    let created_at = Utc::now();
+    let mut backups = Vec::new();
+
+    for tasklist in tasklists::list_all(service).await? {
+        let tasks = fetch_tasks(service, &tasklist).await?;
+        backups.push(TasklistBackup { tasklist, tasks });
+    }
+
+    Ok(Backup {
+        version: BACKUP_VERSION,
+        created_at,
+        tasklists: backups,
+    })
+}
+
+/// Writes the backup as a JSON document, one task list at a time.
+pub async fn write_json<A: TasksApi + ?Sized, W: Write>(service: &A, mut writer: W) -> Result<()> {
+    write!(
+        writer,
+        "{{\"version\":{},\"created_at\":{},\"tasklists\":[",
+        BACKUP_VERSION,
+        serde_json::to_string(&Utc::now())?
+    )?;
+
+    for (i, tasklist) in tasklists::list_all(service).await?.into_iter().enumerate() {
+        let tasks = fetch_tasks(service, &tasklist).await?;
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut writer, &TasklistBackup { tasklist, tasks })?;
+    }
+
+    writer.write_all(b"]}")?;
+    Ok(writer.flush()?)
+}
+
+/// Reads a JSON backup document.
+pub fn read_json<R: Read>(reader: R) -> Result<Backup> {
+    let backup: Backup = serde_json::from_reader(reader)?;
+    if backup.version > BACKUP_VERSION {
+        return Err(ParseError(format!(
+            "unsupported backup version {}",
+            backup.version
+        )));
+    }
+
+    Ok(backup)
+}
+
+/// Writes the backup as CSV, one row per task.
+/// A task list without tasks is written as a row without the task columns.
+#[cfg(feature = "csv")]
+pub async fn write_csv<A: TasksApi + ?Sized, W: Write>(service: &A, writer: W) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    for tasklist in tasklists::list_all(service).await? {
+        let tasks = fetch_tasks(service, &tasklist).await?;
+        let row = |task: Option<Task>| {
+            let task = task.unwrap_or_default();
+            CsvRow {
+                tasklist_id: tasklist.id.clone().unwrap_or_default(),
+                tasklist_title: tasklist.title.clone(),
+                tasklist_updated: tasklist.updated,
+                task_id: task.id,
+                parent: task.parent,
+                position: task.position,
+                title: task.title,
+                notes: task.notes,
+                status: task.status,
+                due: task.due,
+                completed: task.completed,
+                updated: task.updated,
+                deleted: task.deleted,
+                hidden: task.hidden,
+            }
+        };
+
+        if tasks.is_empty() {
+            writer.serialize(row(None))?;
+        }
+        for task in tasks {
+            writer.serialize(row(Some(task)))?;
+        }
+    }
+
+    Ok(writer.flush()?)
+}
+
+/// Reads a CSV backup written by [`write_csv`].
+#[cfg(feature = "csv")]
+pub fn read_csv<R: Read>(reader: R) -> Result<Backup> {
+    let mut tasklists: Vec<TasklistBackup> = Vec::new();
+
+    for row in csv::Reader::from_reader(reader).deserialize() {
+        let row: CsvRow = row?;
+
+        let is_same_list = |b: &TasklistBackup| b.tasklist.id.as_ref() == Some(&row.tasklist_id);
+        let backup = match tasklists.iter().position(is_same_list) {
+            Some(i) => &mut tasklists[i],
+            None => {
+                tasklists.push(TasklistBackup {
+                    tasklist: Tasklist {
+                        id: Some(row.tasklist_id.clone()),
+                        title: row.tasklist_title.clone(),
+                        updated: row.tasklist_updated,
+                        ..Default::default()
+                    },
+                    tasks: Vec::new(),
+                });
+                tasklists.last_mut().expect("task list was just added")
+            }
+        };
+
+        if row.task_id.is_some() {
+            backup.tasks.push(Task {
+                id: row.task_id,
+                parent: row.parent,
+                position: row.position,
+                title: row.title,
+                notes: row.notes,
+                status: row.status,
+                due: row.due,
+                completed: row.completed,
+                updated: row.updated,
+                deleted: row.deleted,
+                hidden: row.hidden,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(Backup {
+        version: BACKUP_VERSION,
+        created_at: Utc::now(),
+        tasklists,
+    })
+}
+
+/// Recreates all the task lists and tasks of the backup in the account of the service.
+/// Subtasks are created under their parents and the siblings keep their order.
+/// Deleted tasks are skipped, hidden tasks are restored as completed tasks.
+pub async fn restore<A: TasksApi + ?Sized>(service: &A, backup: &Backup) -> Result<IdMapping> {
+    let mut mapping = IdMapping::default();
+
+    for TasklistBackup { tasklist, tasks } in backup.tasklists.iter() {
+        let created = service
+            .insert_tasklist(Tasklist {
+                title: tasklist.title.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        let new_tasklist_id = created
+            .id
+            .ok_or_else(|| ResponseError("created task list has no id".to_owned()))?;
+        if let Some(id) = tasklist.id.clone() {
+            mapping.tasklists.insert(id, new_tasklist_id.clone());
+        }
+
+        let mut tasks: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| !task.deleted.unwrap_or(false))
+            .collect();
+        tasks.sort_by(|a, b| a.position.cmp(&b.position));
+
+        let index: HashMap<&str, usize> = tasks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, task)| task.id.as_deref().map(|id| (id, i)))
+            .collect();
+
+        let nodes = tasks
+            .iter()
+            .map(|task| {
+                let parent = task.parent.as_deref().and_then(|p| index.get(p).copied());
+                let copy = Task {
+                    title: task.title.clone(),
+                    notes: task.notes.clone(),
+                    status: task.status,
+                    due: task.due,
+                    completed: task.completed,
+                    links: task.links.clone(),
+                    ..Default::default()
+                };
+                (copy, parent)
+            })
+            .collect();
+
+        let created = tasks::insert_tree(service, &new_tasklist_id, nodes).await?;
+        for (old, new) in tasks.iter().zip(created.iter()) {
+            if let (Some(old), Some(new)) = (old.id.clone(), new.id.clone()) {
+                mapping.tasks.insert(old, new);
+            }
+        }
+    }
+
+    Ok(mapping)
+}
+
+async fn fetch_tasks<A: TasksApi + ?Sized>(service: &A, tasklist: &Tasklist) -> Result<Vec<Task>> {
+    let tasklist_id = match tasklist.id.as_deref() {
+        Some(id) => id,
+        None => return Ok(Vec::new()),
+    };
+
+    let opts = TaskOptions {
+        max_results: Some(100),
+        show_completed: Some(true),
+        show_deleted: Some(true),
+        show_hidden: Some(true),
+        ..Default::default()
+    };
+    tasks::list_all(service, tasklist_id, opts).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeTasks, TaskInsertOptions, TaskStatus};
+
+    fn task(title: &str) -> Task {
+        Task {
+            title: Some(title.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    async fn insert(fake: &FakeTasks, list: &str, title: &str, parent: Option<&Task>) -> Task {
+        let opts = TaskInsertOptions {
+            parent: parent.and_then(|p| p.id.clone()),
+            previous: None,
+        };
+        fake.insert_task(list, task(title), Some(opts))
+            .await
+            .unwrap()
+    }
+
+    // Account with a "Work" list holding a task with two subtasks, a completed and a deleted task.
+    async fn account() -> FakeTasks {
+        let fake = FakeTasks::new();
+        let work = Tasklist {
+            title: Some("Work".to_owned()),
+            ..Default::default()
+        };
+        let work = fake.insert_tasklist(work).await.unwrap().id.unwrap();
+
+        let deleted = insert(&fake, &work, "deleted", None).await;
+        let done = insert(&fake, &work, "done", None).await;
+        let parent = insert(&fake, &work, "parent", None).await;
+        insert(&fake, &work, "second", Some(&parent)).await;
+        insert(&fake, &work, "first", Some(&parent)).await;
+
+        let completed = Task {
+            status: Some(TaskStatus::Completed),
+            ..Default::default()
+        };
+        fake.patch_task(&work, done.id.as_deref().unwrap(), completed)
+            .await
+            .unwrap();
+        fake.delete_task(&work, deleted.id.as_deref().unwrap())
+            .await
+            .unwrap();
+        fake
+    }
+
+    // Lists every task list with its tasks as (title, parent title, completed), in order.
+    async fn outline(fake: &FakeTasks) -> Vec<(String, Vec<(String, Option<String>, bool)>)> {
+        let backup = backup(fake).await.unwrap();
+        let mut lists: Vec<_> = backup
+            .tasklists
+            .into_iter()
+            .map(|TasklistBackup { tasklist, tasks }| {
+                let titles: HashMap<String, String> = tasks
+                    .iter()
+                    .filter_map(|t| Some((t.id.clone()?, t.title.clone()?)))
+                    .collect();
+                let tasks = tasks
+                    .iter()
+                    .filter(|t| !t.deleted.unwrap_or(false))
+                    .map(|t| {
+                        (
+                            t.title.clone().unwrap_or_default(),
+                            t.parent.as_ref().and_then(|p| titles.get(p).cloned()),
+                            t.status == Some(TaskStatus::Completed),
+                        )
+                    })
+                    .collect();
+                (tasklist.title.unwrap_or_default(), tasks)
+            })
+            .collect();
+        lists.sort();
+        lists
+    }
+
+    #[tokio::test]
+    async fn json_round_trip() {
+        let source = account().await;
+        let mut json = Vec::new();
+        write_json(&source, &mut json).await.unwrap();
+
+        let backup = read_json(json.as_slice()).unwrap();
+        assert_eq!(backup.version, BACKUP_VERSION);
+        let work = &backup.tasklists[1];
+        assert_eq!(work.tasks.len(), 5);
+        assert!(work.tasks.iter().any(|t| t.deleted == Some(true)));
+
+        let target = FakeTasks::new();
+        let mapping = restore(&target, &backup).await.unwrap();
+        assert_eq!(mapping.tasklists.len(), 2);
+        assert_eq!(mapping.tasks.len(), 4);
+
+        let mut expected = outline(&source).await;
+        // the restored account also has its own default list
+        expected.push(("My Tasks".to_owned(), Vec::new()));
+        expected.sort();
+        assert_eq!(outline(&target).await, expected);
+
+        let restored = tasks::list_all(
+            &target,
+            &mapping.tasklists[work.tasklist.id.as_ref().unwrap()],
+            TaskOptions::default(),
+        )
+        .await
+        .unwrap();
+        for task in work.tasks.iter().filter(|t| t.parent.is_some()) {
+            let new_id = &mapping.tasks[task.id.as_ref().unwrap()];
+            let new_parent = &mapping.tasks[task.parent.as_ref().unwrap()];
+            let restored = restored.iter().find(|t| t.id.as_ref() == Some(new_id));
+            assert_eq!(restored.unwrap().parent.as_ref(), Some(new_parent));
+        }
+    }
+
+    #[test]
+    fn read_json_rejects_newer_versions() {
+        let json = format!(
+            r#"{{"version":{},"created_at":"2024-03-01T00:00:00Z","tasklists":[]}}"#,
+            BACKUP_VERSION + 1
+        );
+        assert!(matches!(read_json(json.as_bytes()), Err(ParseError(_))));
+    }
+
+    #[cfg(feature = "csv")]
+    #[tokio::test]
+    async fn csv_round_trip() {
+        let source = account().await;
+        let empty = Tasklist {
+            title: Some("Empty".to_owned()),
+            ..Default::default()
+        };
+        source.insert_tasklist(empty).await.unwrap();
+
+        let mut csv = Vec::new();
+        write_csv(&source, &mut csv).await.unwrap();
+        let backup = read_csv(csv.as_slice()).unwrap();
+        assert_eq!(backup.tasklists.len(), 3);
+
+        let target = FakeTasks::new();
+        restore(&target, &backup).await.unwrap();
+
+        let mut expected = outline(&source).await;
+        expected.push(("My Tasks".to_owned(), Vec::new()));
+        expected.sort();
+        assert_eq!(outline(&target).await, expected);
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn read_csv_rejects_malformed_rows() {
+        let header = "tasklist_id,tasklist_title,tasklist_updated,task_id,parent,position,\
+                      title,notes,status,due,completed,updated,deleted,hidden\n";
+
+        let bad_date = format!("{}l1,Work,,t1,,,Call,,needsAction,tomorrow,,,,\n", header);
+        let err = read_csv(bad_date.as_bytes());
+        assert!(matches!(err, Err(crate::TasksError::CSVError(_))));
+
+        let short_row = format!("{}l1,Work\n", header);
+        assert!(read_csv(short_row.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,798 @@
+//! Local CalDAV server which exposes Google Tasks lists as VTODO calendar collections.
+//!
+//! Usage: `GTASKS_ACCESS_TOKEN=<token> gtasks-caldav [address]`
+//!
+//! The server listens on `127.0.0.1:5232` by default. Every task list is served as
+//! the `/<tasklist id>/` collection and every task as the `/<tasklist id>/<task id>.ics` resource.
+//! Task etags are used as the CalDAV ETags.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use gtasks::{
+    ical, Service, Task, TaskInsertOptions, TaskOptions, TaskStatus, Tasklist, TasksApi, TasksError,
+};
+use hyper::header::{HeaderValue, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+
+const TOKEN_ENV: &str = "GTASKS_ACCESS_TOKEN";
+const DEFAULT_ADDR: &str = "127.0.0.1:5232";
+const PAGE_SIZE: u64 = 100;
+const ICS_EXT: &str = ".ics";
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8; component=vtodo";
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+struct Bridge {
+    service: Box<dyn TasksApi>,
+    // resource names chosen by the clients for the tasks they have created
+    aliases: Mutex<HashMap<String, String>>,
+}
+
+enum Target<'a> {
+    Root,
+    Collection(&'a str),
+    Resource(&'a str, String),
+}
+
+impl Bridge {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, BoxError> {
+        let path = req.uri().path().to_owned();
+        let target = match self.target(&path) {
+            Some(target) => target,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+
+        let depth = req
+            .headers()
+            .get("Depth")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("0")
+            .to_owned();
+
+        match (req.method().as_str(), target) {
+            ("OPTIONS", _) => Ok(options()),
+            ("PROPFIND", Target::Root) => self.propfind_root(&depth).await,
+            ("PROPFIND", Target::Collection(list)) => self.propfind_collection(list, &depth).await,
+            ("PROPFIND", Target::Resource(list, task)) => self.propfind_resource(list, &task).await,
+            ("REPORT", Target::Collection(list)) => {
+                let body = hyper::body::to_bytes(req.into_body()).await?;
+                self.report(list, &String::from_utf8_lossy(&body)).await
+            }
+            ("GET", Target::Resource(list, task)) => self.get(list, &task).await,
+            ("PUT", Target::Resource(list, task)) => self.put(list, &task, req).await,
+            ("DELETE", Target::Resource(list, task)) => self.delete(list, &task, &req).await,
+            _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+
+    fn target<'a>(&self, path: &'a str) -> Option<Target<'a>> {
+        let mut segments = path.split('/').filter(|s| !s.is_empty());
+
+        match (segments.next(), segments.next(), segments.next()) {
+            (None, _, _) => Some(Target::Root),
+            (Some(list), None, _) => Some(Target::Collection(list)),
+            (Some(list), Some(name), None) => {
+                let name = name.strip_suffix(ICS_EXT).unwrap_or(name);
+                Some(Target::Resource(list, self.resolve(name)))
+            }
+            _ => None,
+        }
+    }
+
+    fn resolve(&self, name: &str) -> String {
+        let aliases = self.aliases.lock().unwrap();
+        aliases
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_owned())
+    }
+
+    fn href(&self, tasklist_id: &str, task_id: &str) -> String {
+        let aliases = self.aliases.lock().unwrap();
+        let name = aliases
+            .iter()
+            .find(|(_, id)| id.as_str() == task_id)
+            .map(|(alias, _)| alias.as_str())
+            .unwrap_or(task_id);
+
+        format!("/{}/{}{}", tasklist_id, name, ICS_EXT)
+    }
+
+    async fn propfind_root(&self, depth: &str) -> Result<Response<Body>, BoxError> {
+        let mut responses = vec![response(
+            "/",
+            "<d:resourcetype><d:collection/></d:resourcetype>\
+             <d:displayname>Google Tasks</d:displayname>\
+             <d:current-user-principal><d:href>/</d:href></d:current-user-principal>\
+             <c:calendar-home-set><d:href>/</d:href></c:calendar-home-set>"
+                .to_owned(),
+        )];
+
+        if depth != "0" {
+            for tasklist in self.tasklists().await? {
+                responses.push(collection_response(&tasklist));
+            }
+        }
+
+        Ok(multistatus(responses))
+    }
+
+    async fn propfind_collection(
+        &self,
+        list: &str,
+        depth: &str,
+    ) -> Result<Response<Body>, BoxError> {
+        let tasklist = match self.find_tasklist(list).await? {
+            Some(tasklist) => tasklist,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+        let mut responses = vec![collection_response(&tasklist)];
+
+        if depth != "0" {
+            for task in self.tasks(list).await? {
+                responses.push(self.resource_response(list, &task, false));
+            }
+        }
+
+        Ok(multistatus(responses))
+    }
+
+    async fn propfind_resource(
+        &self,
+        list: &str,
+        task_id: &str,
+    ) -> Result<Response<Body>, BoxError> {
+        match self.find_task(list, task_id).await? {
+            Some(task) => Ok(multistatus(
+                vec![self.resource_response(list, &task, false)],
+            )),
+            None => Ok(status(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    // Handles both calendar-query and calendar-multiget reports.
+    async fn report(&self, list: &str, body: &str) -> Result<Response<Body>, BoxError> {
+        let tasks = self.tasks(list).await?;
+        let hrefs = element_texts(body, "href");
+
+        let responses = tasks
+            .iter()
+            .filter_map(|task| {
+                let id = task.id.as_deref()?;
+                let href = self.href(list, id);
+                let requested = hrefs.is_empty() || hrefs.iter().any(|h| h.ends_with(&href));
+                requested.then(|| self.resource_response(list, task, true))
+            })
+            .collect();
+
+        Ok(multistatus(responses))
+    }
+
+    async fn get(&self, list: &str, task_id: &str) -> Result<Response<Body>, BoxError> {
+        let task = match self.find_task(list, task_id).await? {
+            Some(task) => task,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+
+        let mut resp = Response::new(Body::from(ical::export_task(&task)));
+        set_header(&mut resp, CONTENT_TYPE, CALENDAR_CONTENT_TYPE);
+        if let Some(etag) = task.etag.as_deref() {
+            set_header(&mut resp, ETAG, etag);
+        }
+        Ok(resp)
+    }
+
+    async fn put(
+        &self,
+        list: &str,
+        task_id: &str,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, BoxError> {
+        let if_match = header(&req, IF_MATCH);
+        let if_none_match = header(&req, IF_NONE_MATCH);
+        let body = hyper::body::to_bytes(req.into_body()).await?;
+
+        // a malformed body is the fault of the client, not of the API
+        let todo = match ical::parse(&String::from_utf8_lossy(&body)) {
+            Ok(todos) => todos.into_iter().next(),
+            Err(_) => None,
+        };
+        let todo = match todo {
+            Some(todo) => todo,
+            None => return Ok(status(StatusCode::BAD_REQUEST)),
+        };
+
+        let existing = self.find_task(list, task_id).await?;
+        if !preconditions_hold(
+            existing.as_ref(),
+            if_match.as_deref(),
+            if_none_match.as_deref(),
+        ) {
+            return Ok(status(StatusCode::PRECONDITION_FAILED));
+        }
+
+        let parent = todo.parent_uid.as_deref().map(|uid| self.resolve(uid));
+        let (code, task) = match existing {
+            Some(existing) => {
+                let mut task = todo.task;
+                task.id = existing.id.clone();
+                if task.status != Some(TaskStatus::Completed) {
+                    task.completed = None;
+                }
+
+                let mut task = self.service.update_task(list, task).await?;
+                // a task whose RELATED-TO was removed moves back to the top level
+                if parent != existing.parent {
+                    let opts = TaskInsertOptions {
+                        parent,
+                        previous: None,
+                    };
+                    task = self.service.move_task(list, task_id, opts).await?;
+                }
+                (StatusCode::NO_CONTENT, task)
+            }
+            None => {
+                let opts = TaskInsertOptions {
+                    parent,
+                    previous: None,
+                };
+                let task = self
+                    .service
+                    .insert_task(list, todo.task, Some(opts))
+                    .await?;
+                if let Some(id) = task.id.clone() {
+                    self.aliases.lock().unwrap().insert(task_id.to_owned(), id);
+                }
+                (StatusCode::CREATED, task)
+            }
+        };
+
+        let mut resp = status(code);
+        if let Some(etag) = task.etag.as_deref() {
+            set_header(&mut resp, ETAG, etag);
+        }
+        Ok(resp)
+    }
+
+    async fn delete(
+        &self,
+        list: &str,
+        task_id: &str,
+        req: &Request<Body>,
+    ) -> Result<Response<Body>, BoxError> {
+        let existing = match self.find_task(list, task_id).await? {
+            Some(task) => task,
+            None => return Ok(status(StatusCode::NOT_FOUND)),
+        };
+
+        if !preconditions_hold(Some(&existing), header(req, IF_MATCH).as_deref(), None) {
+            return Ok(status(StatusCode::PRECONDITION_FAILED));
+        }
+
+        self.service.delete_task(list, task_id).await?;
+        self.aliases.lock().unwrap().retain(|_, id| id != task_id);
+        Ok(status(StatusCode::NO_CONTENT))
+    }
+
+    async fn find_tasklist(&self, list: &str) -> Result<Option<Tasklist>, BoxError> {
+        match self.service.get_tasklist(list).await {
+            Ok(tasklist) => Ok(Some(tasklist)),
+            Err(err) if api_code(&err) == Some(404) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn find_task(&self, list: &str, task_id: &str) -> Result<Option<Task>, BoxError> {
+        match self.service.get_task(list, task_id, None).await {
+            Ok(task) => Ok(task.filter(|task| !task.deleted.unwrap_or(false))),
+            Err(err) if api_code(&err) == Some(404) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn resource_response(&self, list: &str, task: &Task, with_data: bool) -> String {
+        let id = task.id.as_deref().unwrap_or_default();
+        let mut props = format!(
+            "<d:resourcetype/><d:getcontenttype>{}</d:getcontenttype>",
+            CALENDAR_CONTENT_TYPE
+        );
+
+        if let Some(etag) = task.etag.as_deref() {
+            props.push_str(&format!("<d:getetag>{}</d:getetag>", xml_escape(etag)));
+        }
+        if with_data {
+            props.push_str(&format!(
+                "<c:calendar-data>{}</c:calendar-data>",
+                xml_escape(&ical::export_task(task))
+            ));
+        }
+
+        response(&self.href(list, id), props)
+    }
+
+    async fn tasklists(&self) -> Result<Vec<Tasklist>, BoxError> {
+        let mut tasklists = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            let opts = gtasks::TasklistsOptions {
+                max_results: Some(PAGE_SIZE),
+                page_token,
+            };
+            let page = self.service.list_tasklists(Some(opts)).await?;
+            tasklists.extend(page.items);
+
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                return Ok(tasklists);
+            }
+        }
+    }
+
+    async fn tasks(&self, list: &str) -> Result<Vec<Task>, BoxError> {
+        let mut tasks = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            let opts = TaskOptions {
+                max_results: Some(PAGE_SIZE),
+                page_token,
+                show_completed: Some(true),
+                show_hidden: Some(true),
+                ..Default::default()
+            };
+
+            let page = match self.service.list_tasks(list, Some(opts), None).await? {
+                Some(page) => page,
+                None => return Ok(tasks),
+            };
+            tasks.extend(page.items.unwrap_or_default());
+
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                return Ok(tasks);
+            }
+        }
+    }
+}
+
+// Returns the HTTP status of an error response of the API, e.g. 404 for an unknown id.
+fn api_code(err: &TasksError) -> Option<u16> {
+    match err {
+        TasksError::ResponseError(body) => serde_json::from_str::<serde_json::Value>(body).ok()?
+            ["error"]["code"]
+            .as_u64()
+            .and_then(|code| u16::try_from(code).ok()),
+        _ => None,
+    }
+}
+
+// Returns the status answering a failed request: the API is unavailable when it is
+// overloaded or down, any other failure is a bad gateway.
+fn error_status(err: &BoxError) -> StatusCode {
+    match err.downcast_ref::<TasksError>().and_then(api_code) {
+        Some(429 | 503) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+fn preconditions_hold(
+    existing: Option<&Task>,
+    if_match: Option<&str>,
+    if_none_match: Option<&str>,
+) -> bool {
+    let etag = existing.and_then(|task| task.etag.as_deref());
+
+    let match_ok = match if_match {
+        Some("*") => existing.is_some(),
+        Some(expected) => etag == Some(expected),
+        None => true,
+    };
+
+    let none_match_ok = match if_none_match {
+        Some("*") => existing.is_none(),
+        Some(unexpected) => etag != Some(unexpected),
+        None => true,
+    };
+
+    match_ok && none_match_ok
+}
+
+fn collection_response(tasklist: &Tasklist) -> String {
+    let id = tasklist.id.as_deref().unwrap_or_default();
+    let title = tasklist.title.as_deref().unwrap_or(id);
+    let mut props = format!(
+        "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+         <d:displayname>{}</d:displayname>\
+         <c:supported-calendar-component-set><c:comp name=\"VTODO\"/></c:supported-calendar-component-set>",
+        xml_escape(title)
+    );
+
+    if let Some(etag) = tasklist.etag.as_deref() {
+        props.push_str(&format!("<cs:getctag>{}</cs:getctag>", xml_escape(etag)));
+    }
+
+    response(&format!("/{}/", id), props)
+}
+
+fn response(href: &str, props: String) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop>\
+         <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        xml_escape(href),
+        props
+    )
+}
+
+fn multistatus(responses: Vec<String>) -> Response<Body> {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" \
+         xmlns:cs=\"http://calendarserver.org/ns/\">{}</d:multistatus>",
+        responses.concat()
+    );
+
+    let mut resp = Response::new(Body::from(body));
+    *resp.status_mut() = StatusCode::MULTI_STATUS;
+    set_header(&mut resp, CONTENT_TYPE, "application/xml; charset=utf-8");
+    resp
+}
+
+fn options() -> Response<Body> {
+    let mut resp = status(StatusCode::OK);
+    set_header(&mut resp, "DAV", "1, 3, calendar-access");
+    set_header(
+        &mut resp,
+        "Allow",
+        "OPTIONS, GET, PUT, DELETE, PROPFIND, REPORT",
+    );
+    resp
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = code;
+    resp
+}
+
+fn set_header<K>(resp: &mut Response<Body>, name: K, value: &str)
+where
+    K: hyper::header::IntoHeaderName,
+{
+    if let Ok(value) = HeaderValue::from_str(value) {
+        resp.headers_mut().insert(name, value);
+    }
+}
+
+fn header<K>(req: &Request<Body>, name: K) -> Option<String>
+where
+    K: hyper::header::AsHeaderName,
+{
+    req.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+}
+
+// Returns the text content of all elements with the given local name, ignoring namespaces.
+fn element_texts(xml: &str, local_name: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+
+        let tag = &rest[..end];
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        let name = name.rsplit(':').next().unwrap_or(name);
+        rest = &rest[end + 1..];
+
+        let opening = !tag.starts_with(['/', '?', '!']) && !tag.ends_with('/');
+        if opening && name == local_name {
+            if let Some(close) = rest.find('<') {
+                texts.push(xml_unescape(rest[..close].trim()));
+            }
+        }
+    }
+
+    texts
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let addr: SocketAddr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDR.to_owned())
+        .parse()?;
+
+    let access_token =
+        std::env::var(TOKEN_ENV).map_err(|_| anyhow::anyhow!("{} is not set", TOKEN_ENV))?;
+
+    let bridge = Arc::new(Bridge {
+        service: Box::new(Service::with_token(&access_token)?),
+        aliases: Mutex::new(HashMap::new()),
+    });
+
+    let make_svc = make_service_fn(move |_conn| {
+        let bridge = bridge.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let bridge = bridge.clone();
+                async move {
+                    let method = req.method().clone();
+                    let path = req.uri().path().to_owned();
+
+                    let resp = bridge.handle(req).await.unwrap_or_else(|err| {
+                        eprintln!("{} {} failed: {}", method, path, err);
+                        status(error_status(&err))
+                    });
+                    Ok::<_, Infallible>(resp)
+                }
+            }))
+        }
+    });
+
+    eprintln!("serving CalDAV on http://{}", addr);
+    Server::bind(&addr).serve(make_svc).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use gtasks::{Tasklists, TasklistsOptions, Tasks};
+
+    use super::*;
+
+    fn task(etag: &str) -> Task {
+        Task {
+            etag: Some(etag.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_element_texts_ignoring_namespaces() {
+        let xml = r#"<?xml version="1.0"?>
+            <c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+                <d:prop><d:getetag/></d:prop>
+                <d:href>/list/a.ics</d:href>
+                <href xmlns="DAV:"> /list/b&amp;c.ics </href>
+            </c:calendar-multiget>"#;
+
+        assert_eq!(element_texts(xml, "href"), ["/list/a.ics", "/list/b&c.ics"]);
+        assert!(element_texts(xml, "getetag").is_empty());
+        assert!(element_texts("<d:href>unterminated", "href").is_empty());
+    }
+
+    #[test]
+    fn checks_preconditions() {
+        let existing = task("\"1\"");
+
+        assert!(preconditions_hold(Some(&existing), None, None));
+        assert!(preconditions_hold(Some(&existing), Some("\"1\""), None));
+        assert!(!preconditions_hold(Some(&existing), Some("\"2\""), None));
+        assert!(preconditions_hold(Some(&existing), Some("*"), None));
+        assert!(!preconditions_hold(None, Some("*"), None));
+        assert!(!preconditions_hold(None, Some("\"1\""), None));
+
+        assert!(preconditions_hold(None, None, Some("*")));
+        assert!(!preconditions_hold(Some(&existing), None, Some("*")));
+        assert!(!preconditions_hold(Some(&existing), None, Some("\"1\"")));
+        assert!(preconditions_hold(Some(&existing), None, Some("\"2\"")));
+    }
+
+    // Store whose reads of tasks fail while `fail` is set, counting the inserted tasks.
+    #[derive(Clone, Default)]
+    struct Flaky {
+        inner: Arc<gtasks::FakeTasks>,
+        fail: Arc<AtomicBool>,
+        inserts: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl TasksApi for Flaky {
+        async fn list_tasklists(&self, opt: Option<TasklistsOptions>) -> gtasks::Result<Tasklists> {
+            self.inner.list_tasklists(opt).await
+        }
+
+        async fn get_tasklist(&self, id: &str) -> gtasks::Result<Tasklist> {
+            self.inner.get_tasklist(id).await
+        }
+
+        async fn insert_tasklist(&self, v: Tasklist) -> gtasks::Result<Tasklist> {
+            self.inner.insert_tasklist(v).await
+        }
+
+        async fn update_tasklist(&self, v: Tasklist) -> gtasks::Result<Tasklist> {
+            self.inner.update_tasklist(v).await
+        }
+
+        async fn delete_tasklist(&self, id: &str) -> gtasks::Result<()> {
+            self.inner.delete_tasklist(id).await
+        }
+
+        async fn patch_tasklist(&self, tasklist_id: &str, v: Tasklist) -> gtasks::Result<Tasklist> {
+            self.inner.patch_tasklist(tasklist_id, v).await
+        }
+
+        async fn list_tasks(
+            &self,
+            tasklist_id: &str,
+            opt: Option<TaskOptions>,
+            etag: Option<String>,
+        ) -> gtasks::Result<Option<Tasks>> {
+            self.inner.list_tasks(tasklist_id, opt, etag).await
+        }
+
+        async fn get_task(
+            &self,
+            tasklist_id: &str,
+            task_id: &str,
+            etag: Option<String>,
+        ) -> gtasks::Result<Option<Task>> {
+            if self.fail.load(Ordering::Relaxed) {
+                let body = r#"{"error":{"code":503,"message":"Backend Error"}}"#;
+                return Err(TasksError::ResponseError(body.to_owned()));
+            }
+            self.inner.get_task(tasklist_id, task_id, etag).await
+        }
+
+        async fn insert_task(
+            &self,
+            tasklist_id: &str,
+            v: Task,
+            opts: Option<TaskInsertOptions>,
+        ) -> gtasks::Result<Task> {
+            self.inserts.fetch_add(1, Ordering::Relaxed);
+            self.inner.insert_task(tasklist_id, v, opts).await
+        }
+
+        async fn update_task(&self, tasklist_id: &str, v: Task) -> gtasks::Result<Task> {
+            self.inner.update_task(tasklist_id, v).await
+        }
+
+        async fn delete_task(&self, tasklist_id: &str, task_id: &str) -> gtasks::Result<()> {
+            self.inner.delete_task(tasklist_id, task_id).await
+        }
+
+        async fn clear_tasks(&self, tasklist_id: &str) -> gtasks::Result<()> {
+            self.inner.clear_tasks(tasklist_id).await
+        }
+
+        async fn move_task(
+            &self,
+            tasklist_id: &str,
+            task_id: &str,
+            opts: TaskInsertOptions,
+        ) -> gtasks::Result<Task> {
+            self.inner.move_task(tasklist_id, task_id, opts).await
+        }
+
+        async fn patch_task(
+            &self,
+            tasklist_id: &str,
+            task_id: &str,
+            v: Task,
+        ) -> gtasks::Result<Task> {
+            self.inner.patch_task(tasklist_id, task_id, v).await
+        }
+    }
+
+    fn bridge() -> (Bridge, Flaky) {
+        let flaky = Flaky::default();
+        let bridge = Bridge {
+            service: Box::new(flaky.clone()),
+            aliases: Mutex::new(HashMap::new()),
+        };
+        (bridge, flaky)
+    }
+
+    fn put(path: &str, ics: &str) -> Request<Body> {
+        Request::put(path).body(Body::from(ics.to_owned())).unwrap()
+    }
+
+    async fn handle(bridge: &Bridge, req: Request<Body>) -> Result<StatusCode, StatusCode> {
+        match bridge.handle(req).await {
+            Ok(resp) => Ok(resp.status()),
+            Err(err) => Err(error_status(&err)),
+        }
+    }
+
+    const TODO: &str = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:new\r\nSUMMARY:Call\r\n\
+                        END:VTODO\r\nEND:VCALENDAR\r\n";
+
+    #[tokio::test]
+    async fn put_does_not_insert_when_the_api_fails() {
+        let (bridge, flaky) = bridge();
+
+        flaky.fail.store(true, Ordering::Relaxed);
+        let failed = handle(&bridge, put("/@default/new.ics", TODO)).await;
+        assert_eq!(failed, Err(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(flaky.inserts.load(Ordering::Relaxed), 0);
+
+        flaky.fail.store(false, Ordering::Relaxed);
+        let created = handle(&bridge, put("/@default/new.ics", TODO)).await;
+        assert_eq!(created, Ok(StatusCode::CREATED));
+        assert_eq!(flaky.inserts.load(Ordering::Relaxed), 1);
+
+        let get = Request::get("/@default/unknown.ics")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(handle(&bridge, get).await, Ok(StatusCode::NOT_FOUND));
+        let propfind = Request::builder()
+            .method("PROPFIND")
+            .uri("/unknown/")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(handle(&bridge, propfind).await, Ok(StatusCode::NOT_FOUND));
+        let malformed = handle(&bridge, put("/@default/bad.ics", "BEGIN:VTODO\r\n")).await;
+        assert_eq!(malformed, Ok(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn put_moves_a_task_back_to_the_top_level() {
+        let (bridge, flaky) = bridge();
+        let parent = Task {
+            title: Some("Parent".to_owned()),
+            ..Default::default()
+        };
+        let parent = flaky
+            .inner
+            .insert_task("@default", parent, None)
+            .await
+            .unwrap();
+        let opts = TaskInsertOptions {
+            parent: parent.id.clone(),
+            previous: None,
+        };
+        let child = flaky
+            .inner
+            .insert_task("@default", Task::default(), Some(opts))
+            .await
+            .unwrap();
+        let id = child.id.unwrap();
+
+        let ics = format!(
+            "BEGIN:VTODO\r\nUID:{}\r\nSUMMARY:Child\r\nEND:VTODO\r\n",
+            id
+        );
+        let path = format!("/@default/{}.ics", id);
+        assert_eq!(
+            handle(&bridge, put(&path, &ics)).await,
+            Ok(StatusCode::NO_CONTENT)
+        );
+
+        let moved = flaky
+            .inner
+            .get_task("@default", &id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(moved.title.as_deref(), Some("Child"));
+        assert_eq!(moved.parent, None);
+    }
+}
@@ -0,0 +1,725 @@
+//! Terminal UI for browsing and editing Google Tasks.
+//!
+//! The access token is read from the `GTASKS_ACCESS_TOKEN` variable or from
+//! `$XDG_CONFIG_HOME/gtasks/credentials.json`.
+//!
+//! Edits are shown immediately and rolled back if the API call fails.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDate;
+use gtasks::{
+    credentials, Result, Service, Task, TaskInsertOptions, TaskOptions, TaskStatus, Tasklist,
+    TasksApi,
+};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+const PAGE_SIZE: u64 = 100;
+const HELP: &str = "q quit | tab switch | space done | > < indent | J K reorder | \
+                    h l collapse | n notes | d due | a add | r reload";
+
+#[derive(PartialEq, Eq)]
+enum Focus {
+    Lists,
+    Tasks,
+}
+
+enum InputKind {
+    Notes,
+    Due,
+    NewTask,
+}
+
+struct Input {
+    kind: InputKind,
+    buffer: String,
+}
+
+struct Row {
+    index: usize,
+    depth: usize,
+    has_children: bool,
+}
+
+// API call of an edit which has already been applied locally.
+enum Op {
+    Patch(String, Task),
+    Update(Task),
+    Move(String, TaskInsertOptions),
+    Insert(Task),
+}
+
+struct Pending {
+    op: Op,
+    snapshot: Vec<Task>,
+}
+
+struct App {
+    service: Box<dyn TasksApi>,
+    tasklists: Vec<Tasklist>,
+    list_state: ListState,
+    tasks: Vec<Task>,
+    rows: Vec<Row>,
+    task_state: ListState,
+    collapsed: HashSet<String>,
+    focus: Focus,
+    input: Option<Input>,
+    status: String,
+    next_local_id: usize,
+    quit: bool,
+}
+
+impl App {
+    fn new(service: impl TasksApi + 'static) -> Self {
+        App {
+            service: Box::new(service),
+            tasklists: Vec::new(),
+            list_state: ListState::default(),
+            tasks: Vec::new(),
+            rows: Vec::new(),
+            task_state: ListState::default(),
+            collapsed: HashSet::new(),
+            focus: Focus::Lists,
+            input: None,
+            status: HELP.to_owned(),
+            next_local_id: 0,
+            quit: false,
+        }
+    }
+
+    fn tasklist_id(&self) -> Option<String> {
+        self.list_state
+            .selected()
+            .and_then(|i| self.tasklists.get(i))
+            .and_then(|tasklist| tasklist.id.clone())
+    }
+
+    fn selected(&self) -> Option<&Task> {
+        self.task_state
+            .selected()
+            .and_then(|i| self.rows.get(i))
+            .map(|row| &self.tasks[row.index])
+    }
+
+    fn selected_id(&self) -> Option<String> {
+        self.selected().and_then(|task| task.id.clone())
+    }
+
+    async fn load_tasklists(&mut self) -> Result<()> {
+        self.tasklists = self.service.list_all_tasklists().await?;
+        self.list_state
+            .select((!self.tasklists.is_empty()).then_some(0));
+        self.load_tasks().await
+    }
+
+    async fn load_tasks(&mut self) -> Result<()> {
+        let tasklist_id = match self.tasklist_id() {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+
+        let mut tasks = Vec::new();
+        let mut page_token = None;
+        loop {
+            let opts = TaskOptions {
+                max_results: Some(PAGE_SIZE),
+                page_token,
+                show_completed: Some(true),
+                ..Default::default()
+            };
+
+            let page = match self
+                .service
+                .list_tasks(&tasklist_id, Some(opts), None)
+                .await?
+            {
+                Some(page) => page,
+                None => break,
+            };
+            tasks.extend(page.items.unwrap_or_default());
+
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        let selected = self.selected_id();
+        self.tasks = tasks;
+        self.rebuild();
+        self.select_task(selected.as_deref());
+        Ok(())
+    }
+
+    // Rebuilds the visible rows of the task tree.
+    fn rebuild(&mut self) {
+        let mut children: HashMap<Option<&str>, Vec<usize>> = HashMap::new();
+        let ids: HashSet<&str> = self.tasks.iter().filter_map(|t| t.id.as_deref()).collect();
+        for (i, task) in self.tasks.iter().enumerate() {
+            let parent = task.parent.as_deref().filter(|p| ids.contains(p));
+            children.entry(parent).or_default().push(i);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by(|a, b| self.tasks[*a].position.cmp(&self.tasks[*b].position));
+        }
+
+        let mut rows = Vec::with_capacity(self.tasks.len());
+        let mut stack: Vec<(usize, usize)> = children
+            .get(&None)
+            .into_iter()
+            .flatten()
+            .rev()
+            .map(|i| (*i, 0))
+            .collect();
+
+        while let Some((index, depth)) = stack.pop() {
+            let id = self.tasks[index].id.as_deref();
+            let nested = children.get(&id).filter(|c| !c.is_empty());
+            rows.push(Row {
+                index,
+                depth,
+                has_children: nested.is_some(),
+            });
+
+            if id.is_some_and(|id| self.collapsed.contains(id)) {
+                continue;
+            }
+            stack.extend(nested.into_iter().flatten().rev().map(|i| (*i, depth + 1)));
+        }
+
+        let last = rows.len().checked_sub(1);
+        let selected = self.task_state.selected().unwrap_or(0);
+        self.task_state.select(last.map(|last| selected.min(last)));
+        self.rows = rows;
+    }
+
+    fn select_task(&mut self, id: Option<&str>) {
+        if let Some(i) = self
+            .rows
+            .iter()
+            .position(|row| self.tasks[row.index].id.as_deref() == id && id.is_some())
+        {
+            self.task_state.select(Some(i));
+        }
+    }
+
+    // Returns the ids of the siblings of the parent, ordered by position.
+    fn siblings(&self, parent: Option<&str>) -> Vec<String> {
+        let mut siblings: Vec<&Task> = self
+            .tasks
+            .iter()
+            .filter(|task| task.parent.as_deref() == parent)
+            .collect();
+        siblings.sort_by(|a, b| a.position.cmp(&b.position));
+        siblings.iter().filter_map(|t| t.id.clone()).collect()
+    }
+
+    fn task_mut(&mut self, id: &str) -> Option<&mut Task> {
+        self.tasks.iter_mut().find(|t| t.id.as_deref() == Some(id))
+    }
+
+    // Moves the task locally under the parent after the previous sibling, renumbering the sibling positions.
+    fn place(&mut self, id: &str, parent: Option<String>, previous: Option<&str>) {
+        let mut siblings: Vec<String> = self
+            .siblings(parent.as_deref())
+            .into_iter()
+            .filter(|sibling| sibling != id)
+            .collect();
+        let at = previous
+            .and_then(|p| siblings.iter().position(|s| s == p))
+            .map_or(0, |i| i + 1);
+        siblings.insert(at, id.to_owned());
+
+        if let Some(task) = self.task_mut(id) {
+            task.parent = parent;
+        }
+        for (i, sibling) in siblings.iter().enumerate() {
+            if let Some(task) = self.task_mut(sibling) {
+                task.position = Some(format!("{:020}", i));
+            }
+        }
+
+        self.rebuild();
+        self.select_task(Some(id));
+    }
+
+    fn on_key(&mut self, code: KeyCode) -> Option<Pending> {
+        if self.input.is_some() {
+            return self.on_input_key(code);
+        }
+
+        match (code, &self.focus) {
+            (KeyCode::Char('q'), _) => self.quit = true,
+            (KeyCode::Tab, Focus::Lists) | (KeyCode::Enter, Focus::Lists) => {
+                self.focus = Focus::Tasks
+            }
+            (KeyCode::Tab, Focus::Tasks) | (KeyCode::Esc, Focus::Tasks) => {
+                self.focus = Focus::Lists
+            }
+            (KeyCode::Down, Focus::Lists) | (KeyCode::Char('j'), Focus::Lists) => {
+                self.list_state.select_next()
+            }
+            (KeyCode::Up, Focus::Lists) | (KeyCode::Char('k'), Focus::Lists) => {
+                self.list_state.select_previous()
+            }
+            (KeyCode::Down, Focus::Tasks) | (KeyCode::Char('j'), Focus::Tasks) => {
+                self.task_state.select_next()
+            }
+            (KeyCode::Up, Focus::Tasks) | (KeyCode::Char('k'), Focus::Tasks) => {
+                self.task_state.select_previous()
+            }
+            (KeyCode::Left, Focus::Tasks) | (KeyCode::Char('h'), Focus::Tasks) => {
+                if let Some(id) = self.selected_id() {
+                    self.collapsed.insert(id);
+                    self.rebuild();
+                }
+            }
+            (KeyCode::Right, Focus::Tasks) | (KeyCode::Char('l'), Focus::Tasks) => {
+                if let Some(id) = self.selected_id() {
+                    self.collapsed.remove(&id);
+                    self.rebuild();
+                }
+            }
+            (KeyCode::Char(' '), Focus::Tasks) | (KeyCode::Char('x'), Focus::Tasks) => {
+                return self.toggle_completed()
+            }
+            (KeyCode::Char('>'), Focus::Tasks) => return self.indent(),
+            (KeyCode::Char('<'), Focus::Tasks) => return self.outdent(),
+            (KeyCode::Char('K'), Focus::Tasks) => return self.reorder(-1),
+            (KeyCode::Char('J'), Focus::Tasks) => return self.reorder(1),
+            (KeyCode::Char('n'), Focus::Tasks) => {
+                let notes = self
+                    .selected()
+                    .map(|t| t.notes.clone().unwrap_or_default())?;
+                self.start_input(InputKind::Notes, notes.replace('\n', "\\n"));
+            }
+            (KeyCode::Char('d'), Focus::Tasks) => {
+                let due = self
+                    .selected()
+                    .map(|t| t.due.map(|d| d.format("%Y-%m-%d").to_string()))?;
+                self.start_input(InputKind::Due, due.unwrap_or_default());
+            }
+            (KeyCode::Char('a'), _) => self.start_input(InputKind::NewTask, String::new()),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn start_input(&mut self, kind: InputKind, buffer: String) {
+        self.status = "enter to save, esc to cancel".to_owned();
+        self.input = Some(Input { kind, buffer });
+    }
+
+    fn on_input_key(&mut self, code: KeyCode) -> Option<Pending> {
+        let input = self.input.as_mut()?;
+        match code {
+            KeyCode::Char(c) => input.buffer.push(c),
+            KeyCode::Backspace => {
+                input.buffer.pop();
+            }
+            KeyCode::Esc => {
+                self.input = None;
+                self.status = HELP.to_owned();
+            }
+            KeyCode::Enter => {
+                let input = self.input.take()?;
+                self.status = HELP.to_owned();
+                return self.submit(input);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn submit(&mut self, input: Input) -> Option<Pending> {
+        let snapshot = self.tasks.clone();
+
+        match input.kind {
+            InputKind::Notes => {
+                let id = self.selected_id()?;
+                let task = self.task_mut(&id)?;
+                let notes = input.buffer.replace("\\n", "\n");
+                task.notes = (!notes.is_empty()).then_some(notes);
+                let op = Op::Update(task.clone());
+                Some(Pending { op, snapshot })
+            }
+            InputKind::Due => {
+                let due = match input.buffer.trim() {
+                    "" => None,
+                    date => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                        Ok(date) => date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc()),
+                        Err(err) => {
+                            self.status = format!("invalid due date {}: {}", date, err);
+                            return None;
+                        }
+                    },
+                };
+
+                let id = self.selected_id()?;
+                let task = self.task_mut(&id)?;
+                task.due = due;
+                let op = Op::Update(task.clone());
+                Some(Pending { op, snapshot })
+            }
+            InputKind::NewTask => {
+                if input.buffer.trim().is_empty() {
+                    return None;
+                }
+
+                self.next_local_id += 1;
+                let local_id = format!("local-{}", self.next_local_id);
+                let task = Task {
+                    title: Some(input.buffer.trim().to_owned()),
+                    status: Some(TaskStatus::NeedsAction),
+                    ..Default::default()
+                };
+
+                self.tasks.push(Task {
+                    id: Some(local_id.clone()),
+                    ..task.clone()
+                });
+                self.place(&local_id, None, None);
+                self.focus = Focus::Tasks;
+                Some(Pending {
+                    op: Op::Insert(task),
+                    snapshot,
+                })
+            }
+        }
+    }
+
+    fn toggle_completed(&mut self) -> Option<Pending> {
+        let snapshot = self.tasks.clone();
+        let id = self.selected_id()?;
+        let task = self.task_mut(&id)?;
+
+        let status = match task.status {
+            Some(TaskStatus::Completed) => TaskStatus::NeedsAction,
+            _ => TaskStatus::Completed,
+        };
+        task.status = Some(status);
+        if status == TaskStatus::NeedsAction {
+            task.completed = None;
+        }
+
+        let patch = Task {
+            status: Some(status),
+            ..Default::default()
+        };
+        Some(Pending {
+            op: Op::Patch(id, patch),
+            snapshot,
+        })
+    }
+
+    // Moves the task under its previous sibling, as the last child.
+    fn indent(&mut self) -> Option<Pending> {
+        let task = self.selected()?;
+        let id = task.id.clone()?;
+        let siblings = self.siblings(task.parent.as_deref());
+
+        let at = siblings.iter().position(|s| *s == id)?;
+        let parent = siblings.get(at.checked_sub(1)?)?.clone();
+        let previous = self.siblings(Some(&parent)).last().cloned();
+
+        self.move_to(id, Some(parent), previous)
+    }
+
+    // Moves the task after its parent.
+    fn outdent(&mut self) -> Option<Pending> {
+        let task = self.selected()?;
+        let id = task.id.clone()?;
+        let parent = task.parent.clone()?;
+        let grandparent = self
+            .tasks
+            .iter()
+            .find(|t| t.id.as_deref() == Some(parent.as_str()))
+            .and_then(|t| t.parent.clone());
+
+        self.move_to(id, grandparent, Some(parent))
+    }
+
+    // Moves the task up or down among its siblings.
+    fn reorder(&mut self, offset: isize) -> Option<Pending> {
+        let task = self.selected()?;
+        let id = task.id.clone()?;
+        let parent = task.parent.clone();
+        let siblings: Vec<String> = self
+            .siblings(parent.as_deref())
+            .into_iter()
+            .filter(|s| *s != id)
+            .collect();
+
+        let at = self
+            .siblings(parent.as_deref())
+            .iter()
+            .position(|s| *s == id)?;
+        let target = at
+            .checked_add_signed(offset)
+            .filter(|t| *t <= siblings.len())?;
+        let previous = target.checked_sub(1).map(|p| siblings[p].clone());
+
+        self.move_to(id, parent, previous)
+    }
+
+    fn move_to(
+        &mut self,
+        id: String,
+        parent: Option<String>,
+        previous: Option<String>,
+    ) -> Option<Pending> {
+        let snapshot = self.tasks.clone();
+        self.place(&id, parent.clone(), previous.as_deref());
+
+        let opts = TaskInsertOptions { parent, previous };
+        Some(Pending {
+            op: Op::Move(id, opts),
+            snapshot,
+        })
+    }
+
+    // Sends the edit to the API, rolling the local state back if it fails.
+    async fn commit(&mut self, pending: Pending) {
+        let tasklist_id = match self.tasklist_id() {
+            Some(id) => id,
+            None => return,
+        };
+
+        let service = &self.service;
+        let result = match pending.op {
+            Op::Patch(id, patch) => service.patch_task(&tasklist_id, &id, patch).await.map(Some),
+            Op::Update(task) => service.update_task(&tasklist_id, task).await.map(Some),
+            Op::Move(id, opts) => service
+                .move_task(&tasklist_id, &id, opts)
+                .await
+                .map(|_| None),
+            Op::Insert(task) => service
+                .insert_task(&tasklist_id, task, None)
+                .await
+                .map(|_| None),
+        };
+
+        match result {
+            Ok(Some(updated)) => {
+                if let Some(task) = updated.id.clone().and_then(|id| self.task_mut(&id)) {
+                    *task = updated;
+                }
+                self.rebuild();
+            }
+            // moves and inserts change the positions of the siblings; the server has applied
+            // the edit, so a failed refresh keeps the local state
+            Ok(None) => {
+                if let Err(err) = self.load_tasks().await {
+                    self.status = format!("refresh failed: {}", err);
+                }
+            }
+            Err(err) => {
+                self.tasks = pending.snapshot;
+                self.rebuild();
+                self.status = format!("rolled back: {}", err);
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [lists, tasks] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(main);
+
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+        let focused = |focus: Focus| match self.focus == focus {
+            true => Style::default().add_modifier(Modifier::BOLD),
+            false => Style::default(),
+        };
+
+        let items: Vec<ListItem> = self
+            .tasklists
+            .iter()
+            .map(|t| ListItem::new(t.title.clone().unwrap_or_default()))
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title("Lists")
+                    .border_style(focused(Focus::Lists)),
+            )
+            .highlight_style(highlight);
+        frame.render_stateful_widget(list, lists, &mut self.list_state);
+
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let task = &self.tasks[row.index];
+                let fold = match (row.has_children, task.id.as_ref()) {
+                    (true, Some(id)) if self.collapsed.contains(id) => "+ ",
+                    (true, _) => "- ",
+                    _ => "  ",
+                };
+                let mark = match task.status {
+                    Some(TaskStatus::Completed) => "[x]",
+                    _ => "[ ]",
+                };
+                let due = task
+                    .due
+                    .map(|d| format!("  (due {})", d.format("%Y-%m-%d")))
+                    .unwrap_or_default();
+                let notes = if task.notes.is_some() { "  *" } else { "" };
+
+                ListItem::new(Line::from(format!(
+                    "{}{}{} {}{}{}",
+                    "  ".repeat(row.depth),
+                    fold,
+                    mark,
+                    task.title.as_deref().unwrap_or_default(),
+                    due,
+                    notes
+                )))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title("Tasks")
+                    .border_style(focused(Focus::Tasks)),
+            )
+            .highlight_style(highlight);
+        frame.render_stateful_widget(list, tasks, &mut self.task_state);
+
+        let line = match self.input.as_ref() {
+            Some(input) => {
+                let label = match input.kind {
+                    InputKind::Notes => "notes",
+                    InputKind::Due => "due (YYYY-MM-DD)",
+                    InputKind::NewTask => "new task",
+                };
+                format!("{}: {}_", label, input.buffer)
+            }
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(line), status);
+    }
+}
+
+async fn run(terminal: &mut DefaultTerminal, mut app: App) -> anyhow::Result<()> {
+    if let Err(err) = app.load_tasklists().await {
+        app.status = format!("failed to load task lists: {}", err);
+    }
+
+    while !app.quit {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        let previous_list = app.list_state.selected();
+        let reload = app.input.is_none() && key.code == KeyCode::Char('r');
+
+        if let Some(pending) = app.on_key(key.code) {
+            // show the optimistic state while the API call is in flight
+            terminal.draw(|frame| app.draw(frame))?;
+            app.commit(pending).await;
+        } else if reload || app.list_state.selected() != previous_list {
+            if let Err(err) = app.load_tasks().await {
+                app.status = format!("failed to load tasks: {}", err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let service = Service::with_auth(|| Ok(credentials::access_token()?))?;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, App::new(service)).await;
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use gtasks::FakeTasks;
+
+    use super::*;
+
+    // Returns the app showing a "Work" list with the tasks.
+    async fn app(titles: &[&str]) -> App {
+        let fake = FakeTasks::new();
+        let work = Tasklist {
+            title: Some("Work".to_owned()),
+            ..Default::default()
+        };
+        let work = fake.insert_tasklist(work).await.unwrap().id.unwrap();
+        for title in titles.iter().rev() {
+            let task = Task {
+                title: Some(title.to_string()),
+                ..Default::default()
+            };
+            fake.insert_task(&work, task, None).await.unwrap();
+        }
+
+        let mut app = App::new(fake);
+        app.load_tasklists().await.unwrap();
+        let at = app
+            .tasklists
+            .iter()
+            .position(|tasklist| tasklist.id.as_ref() == Some(&work));
+        app.list_state.select(at);
+        app.load_tasks().await.unwrap();
+        app
+    }
+
+    fn titles(app: &App) -> Vec<(&str, Option<TaskStatus>)> {
+        app.rows
+            .iter()
+            .map(|row| &app.tasks[row.index])
+            .map(|task| (task.title.as_deref().unwrap_or_default(), task.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn commits_an_edit() {
+        let mut app = app(&["a", "b"]).await;
+        app.task_state.select(Some(1));
+
+        let pending = app.toggle_completed().unwrap();
+        app.commit(pending).await;
+
+        let done = Some(TaskStatus::Completed);
+        let open = Some(TaskStatus::NeedsAction);
+        assert_eq!(titles(&app), [("a", open), ("b", done)]);
+        app.load_tasks().await.unwrap();
+        assert_eq!(titles(&app), [("a", open), ("b", done)]);
+    }
+
+    #[tokio::test]
+    async fn rolls_back_a_failed_edit() {
+        let mut app = app(&["a", "b"]).await;
+        let tasklist_id = app.tasklist_id().unwrap();
+        app.task_state.select(Some(1));
+        let b = app.selected_id().unwrap();
+
+        // the list is deleted behind the app's back, so the API call fails
+        app.service.delete_tasklist(&tasklist_id).await.unwrap();
+        let pending = app.move_to(b, None, None).unwrap();
+        assert_eq!(titles(&app)[0].0, "b");
+        app.commit(pending).await;
+
+        let open = Some(TaskStatus::NeedsAction);
+        assert_eq!(titles(&app), [("a", open), ("b", open)]);
+        assert!(app.status.starts_with("rolled back"), "{}", app.status);
+    }
+}
@@ -0,0 +1,292 @@
+//! Polls Google Tasks lists and forwards the detected changes to webhooks.
+//!
+//! Usage: `gtasks-webhook <config.json>`
+//!
+//! The access token is read from the `GTASKS_ACCESS_TOKEN` variable or from
+//! `$XDG_CONFIG_HOME/gtasks/credentials.json`. The file is read again for every request,
+//! so a token refreshed by another process is picked up without a restart.
+//!
+//! ```json
+//! {
+//!     "tasklists": ["<tasklist id>"],
+//!     "interval_secs": 60,
+//!     "max_attempts": 5,
+//!     "timeout_secs": 10,
+//!     "dead_letter_file": "gtasks-webhook.dead.jsonl",
+//!     "webhooks": [{ "url": "https://example.com/hook", "secret": "s3cr3t" }]
+//! }
+//! ```
+//!
+//! Each payload is signed with HMAC-SHA256 of the request body using the webhook secret.
+//! The hex encoded signature is sent in the `X-Gtasks-Signature: sha256=<signature>` header.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use gtasks::{credentials, Service, WatchEvent, Watcher};
+use hmac::{Hmac, Mac};
+use serde_derive::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::time::{interval, sleep, MissedTickBehavior};
+
+const SIGNATURE_HEADER: &str = "X-Gtasks-Signature";
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct Config {
+    tasklists: Vec<String>,
+    webhooks: Vec<Webhook>,
+
+    #[serde(default = "default_interval_secs")]
+    interval_secs: u64,
+
+    #[serde(default = "default_max_attempts")]
+    max_attempts: u32,
+
+    // timeout of a webhook delivery, an attempt which times out is retried
+    #[serde(default = "default_timeout_secs")]
+    timeout_secs: u64,
+
+    #[serde(default = "default_dead_letter_file")]
+    dead_letter_file: PathBuf,
+}
+
+#[derive(Deserialize, Clone)]
+struct Webhook {
+    url: String,
+    secret: String,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    sent_at: DateTime<Utc>,
+    event: &'a WatchEvent,
+}
+
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    webhook: &'a str,
+    attempts: u32,
+    error: String,
+    payload: serde_json::Value,
+}
+
+fn default_interval_secs() -> u64 {
+    60
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_dead_letter_file() -> PathBuf {
+    PathBuf::from("gtasks-webhook.dead.jsonl")
+}
+
+struct Dispatcher {
+    http_client: reqwest::Client,
+    webhooks: Vec<Webhook>,
+    max_attempts: u32,
+    dead_letter_file: PathBuf,
+}
+
+impl Dispatcher {
+    fn dispatch(self: &Arc<Self>, event: &WatchEvent) {
+        let payload = Payload {
+            sent_at: Utc::now(),
+            event,
+        };
+
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(err) => return eprintln!("failed to encode event: {}", err),
+        };
+
+        for webhook in self.webhooks.iter() {
+            let dispatcher = self.clone();
+            let webhook = webhook.clone();
+            let body = body.clone();
+
+            tokio::spawn(async move { dispatcher.deliver(&webhook, body).await });
+        }
+    }
+
+    async fn deliver(&self, webhook: &Webhook, body: Vec<u8>) {
+        let signature = sign(&webhook.secret, &body);
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let result = self
+                .http_client
+                .post(webhook.url.as_str())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+
+            let err = match result {
+                Ok(_) => return,
+                Err(err) => err,
+            };
+
+            if attempt >= self.max_attempts {
+                let letter = DeadLetter {
+                    webhook: webhook.url.as_str(),
+                    attempts: attempt,
+                    error: err.to_string(),
+                    payload: serde_json::from_slice(&body).unwrap_or_default(),
+                };
+
+                if let Err(err) = append_dead_letter(&self.dead_letter_file, &letter) {
+                    eprintln!("failed to write dead letter: {}", err);
+                }
+                return;
+            }
+
+            eprintln!(
+                "delivery to {} failed (attempt {}/{}): {}",
+                webhook.url, attempt, self.max_attempts, err
+            );
+            sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn append_dead_letter(path: &Path, letter: &DeadLetter) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(letter)?;
+    line.push(b'\n');
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&line)?;
+    Ok(())
+}
+
+fn load_config() -> anyhow::Result<Config> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: gtasks-webhook <config.json>"))?;
+
+    parse_config(&fs::read(path)?)
+}
+
+fn parse_config(json: &[u8]) -> anyhow::Result<Config> {
+    let config: Config = serde_json::from_slice(json)?;
+    if config.interval_secs == 0 {
+        anyhow::bail!("interval_secs must be at least 1");
+    }
+    if config.timeout_secs == 0 {
+        anyhow::bail!("timeout_secs must be at least 1");
+    }
+    Ok(config)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = load_config()?;
+    // fail early on a missing token, rather than on the first poll
+    credentials::access_token()?;
+
+    let service = Arc::new(Service::with_auth(|| Ok(credentials::access_token()?))?);
+    let mut watcher = Watcher::new(service, config.tasklists);
+
+    let dispatcher = Arc::new(Dispatcher {
+        http_client: reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?,
+        webhooks: config.webhooks,
+        max_attempts: config.max_attempts.max(1),
+        dead_letter_file: config.dead_letter_file,
+    });
+
+    let mut ticker = interval(Duration::from_secs(config.interval_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        match watcher.poll().await {
+            Ok(events) => {
+                for event in events.iter() {
+                    dispatcher.dispatch(event);
+                }
+            }
+            Err(err) => eprintln!("failed to poll task lists: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn appends_dead_letters_as_json_lines() {
+        let path =
+            std::env::temp_dir().join(format!("gtasks-webhook-dead-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        for attempts in [1, 2] {
+            let letter = DeadLetter {
+                webhook: "https://example.com/hook",
+                attempts,
+                error: "timeout".to_owned(),
+                payload: serde_json::json!({ "n": attempts }),
+            };
+            append_dead_letter(&path, &letter).unwrap();
+        }
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["attempts"], 1);
+        assert_eq!(lines[1]["payload"]["n"], 2);
+        assert_eq!(lines[1]["webhook"], "https://example.com/hook");
+    }
+
+    #[test]
+    fn rejects_zero_durations() {
+        let config = r#"{"tasklists": [], "webhooks": [], "interval_secs": 0}"#;
+        assert!(parse_config(config.as_bytes()).is_err());
+        let config = r#"{"tasklists": [], "webhooks": [], "timeout_secs": 0}"#;
+        assert!(parse_config(config.as_bytes()).is_err());
+
+        let config = r#"{"tasklists": [], "webhooks": []}"#;
+        let config = parse_config(config.as_bytes()).unwrap();
+        assert_eq!(config.interval_secs, 60);
+        assert_eq!(config.timeout_secs, 10);
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+use clap_complete::CompletionCandidate;
+use gtasks::{Task, Tasklist};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config;
+
+const NAMES_FILE: &str = "names.json";
+
+/// Names seen by the previous commands, stored in `$XDG_CACHE_HOME/gtasks/names.json`
+/// so that the shell completions need no request.
+#[derive(Serialize, Deserialize, Default)]
+pub struct NameCache {
+    #[serde(default)]
+    tasklists: Vec<Entry>,
+
+    /// Tasks by task list id.
+    #[serde(default)]
+    tasks: HashMap<String, Vec<Entry>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    id: String,
+    title: String,
+}
+
+impl NameCache {
+    /// Reads the cache, a missing or unreadable file yields an empty cache.
+    pub fn load() -> Self {
+        path()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache, failures are ignored as the cache only serves the completions.
+    pub fn save(&self) {
+        let (Some(path), Ok(content)) = (path(), serde_json::to_vec(self)) else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, content);
+    }
+
+    pub fn set_tasklists(&mut self, tasklists: &[Tasklist]) {
+        self.tasklists = entries(tasklists.iter().map(|t| (&t.id, &t.title)));
+        self.tasks
+            .retain(|id, _| self.tasklists.iter().any(|t| &t.id == id));
+    }
+
+    pub fn set_tasks(&mut self, tasklist_id: &str, tasks: &[Task]) {
+        let entries = entries(tasks.iter().map(|t| (&t.id, &t.title)));
+        self.tasks.insert(tasklist_id.to_owned(), entries);
+    }
+}
+
+fn entries<'a>(
+    items: impl Iterator<Item = (&'a Option<String>, &'a Option<String>)>,
+) -> Vec<Entry> {
+    items
+        .filter_map(|(id, title)| {
+            Some(Entry {
+                id: id.clone()?,
+                title: title.clone().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+fn path() -> Option<PathBuf> {
+    config::cache_dir().ok().map(|dir| dir.join(NAMES_FILE))
+}
+
+/// Completes task list names from the cache.
+pub fn complete_tasklists(current: &OsStr) -> Vec<CompletionCandidate> {
+    let cache = NameCache::load();
+    candidates(cache.tasklists.iter(), current)
+}
+
+/// Completes task names of all the cached task lists.
+pub fn complete_tasks(current: &OsStr) -> Vec<CompletionCandidate> {
+    let cache = NameCache::load();
+    candidates(cache.tasks.values().flatten(), current)
+}
+
+fn candidates<'a>(
+    entries: impl Iterator<Item = &'a Entry>,
+    current: &OsStr,
+) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy().to_lowercase();
+    let mut titles: Vec<&str> = entries
+        .map(|e| e.title.as_str())
+        .filter(|title| !title.is_empty() && title.to_lowercase().starts_with(&current))
+        .collect();
+    titles.sort_unstable();
+    titles.dedup();
+
+    titles.into_iter().map(CompletionCandidate::new).collect()
+}
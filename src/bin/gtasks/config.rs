@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::PathBuf;
+
+use gtasks::{credentials, TasksError};
+use serde_derive::Deserialize;
+
+use crate::output::Format;
+use crate::CliError;
+
+const CONFIG_FILE: &str = "config.json";
+const DEFAULT_TASKLIST: &str = "@default";
+
+/// Settings read from `$XDG_CONFIG_HOME/gtasks/config.json`.
+#[derive(Deserialize, Default)]
+pub struct Config {
+    /// Task list used when a command is given no `--list`.
+    #[serde(default)]
+    pub default_list: Option<String>,
+
+    /// Output format used when a command is given no `--output`.
+    #[serde(default)]
+    pub output: Option<Format>,
+}
+
+impl Config {
+    /// Reads the config file, a missing file yields the default settings.
+    pub fn load() -> Result<Self, CliError> {
+        let path = config_dir()?.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = fs::read(&path)
+            .map_err(|err| CliError::Config(format!("{}: {}", path.display(), err)))?;
+        serde_json::from_slice(&content)
+            .map_err(|err| CliError::Config(format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn tasklist(&self, list: Option<String>) -> String {
+        list.or_else(|| self.default_list.clone())
+            .unwrap_or_else(|| DEFAULT_TASKLIST.to_owned())
+    }
+}
+
+/// Returns the access token from the `GTASKS_ACCESS_TOKEN` variable
+/// or from `$XDG_CONFIG_HOME/gtasks/credentials.json`.
+pub fn access_token() -> Result<String, CliError> {
+    credentials::access_token().map_err(config_error)
+}
+
+pub fn config_dir() -> Result<PathBuf, CliError> {
+    credentials::config_dir().map_err(config_error)
+}
+
+pub fn cache_dir() -> Result<PathBuf, CliError> {
+    credentials::cache_dir().map_err(config_error)
+}
+
+// The credentials module reports a missing or malformed file as an invalid argument.
+fn config_error(err: TasksError) -> CliError {
+    match err {
+        TasksError::InvalidArgument(msg) => CliError::Config(msg),
+        err => CliError::Tasks(err),
+    }
+}
@@ -0,0 +1,539 @@
+//! Command-line client for Google Tasks.
+//!
+//! The access token is read from the `GTASKS_ACCESS_TOKEN` variable or from
+//! `$XDG_CONFIG_HOME/gtasks/credentials.json`, the settings from `$XDG_CONFIG_HOME/gtasks/config.json`.
+//!
+//! Task lists and tasks can be given by id or by name, see [`gtasks::resolve`].
+//! The names seen are cached for the shell completions, enabled with e.g.
+//! `source <(gtasks completions bash)`.
+
+mod cache;
+mod config;
+mod output;
+
+use std::io;
+use std::process::ExitCode;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::env::{CompleteEnv, Shells};
+use clap_complete::ArgValueCompleter;
+use gtasks::{
+    resolve, Service, Task, TaskInsertOptions, TaskOptions, TaskStatus, TasksApi, TasksError,
+};
+
+use cache::{complete_tasklists, complete_tasks, NameCache};
+use config::Config;
+use output::Format;
+
+// sysexits(3) codes
+const EX_USAGE: u8 = 64;
+const EX_DATAERR: u8 = 65;
+const EX_UNAVAILABLE: u8 = 69;
+const EX_IOERR: u8 = 74;
+const EX_PROTOCOL: u8 = 76;
+const EX_CONFIG: u8 = 78;
+
+const DEFAULT_TASKLIST: &str = "@default";
+const COMPLETE_ENV: &str = "COMPLETE";
+
+#[derive(Parser)]
+#[command(
+    name = "gtasks",
+    version,
+    about = "Manage Google Tasks from the command line"
+)]
+struct Cli {
+    /// Task list id or name, defaults to `default_list` of the config or to the default task list.
+    #[arg(short, long, global = true, add = ArgValueCompleter::new(complete_tasklists))]
+    list: Option<String>,
+
+    /// Output format, defaults to `output` of the config or to table.
+    #[arg(short, long, global = true, value_enum)]
+    output: Option<Format>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Api(ApiCommand),
+
+    /// Prints the shell completion script.
+    Completions { shell: Shell },
+}
+
+// Commands which call the API.
+#[derive(Subcommand)]
+enum ApiCommand {
+    /// Lists the task lists.
+    Lists,
+
+    /// Lists the tasks of a task list.
+    Ls {
+        /// Task list id or name, takes precedence over `--list`.
+        #[arg(add = ArgValueCompleter::new(complete_tasklists))]
+        list: Option<String>,
+
+        /// Includes the completed and hidden tasks.
+        #[arg(short, long)]
+        all: bool,
+    },
+
+    #[command(flatten)]
+    Task(TaskCommand),
+
+    /// Hides the completed tasks of a task list.
+    Clear {
+        /// Task list id or name, takes precedence over `--list`.
+        #[arg(add = ArgValueCompleter::new(complete_tasklists))]
+        list: Option<String>,
+    },
+}
+
+// Commands on a single task of the task list.
+#[derive(Subcommand)]
+enum TaskCommand {
+    /// Creates a task.
+    Add {
+        title: String,
+
+        #[arg(short, long)]
+        notes: Option<String>,
+
+        /// Due date as YYYY-MM-DD.
+        #[arg(short, long, value_parser = parse_due)]
+        due: Option<DateTime<Utc>>,
+
+        /// Parent task id or name.
+        #[arg(short, long, add = ArgValueCompleter::new(complete_tasks))]
+        parent: Option<String>,
+    },
+
+    /// Marks a task as completed.
+    Done {
+        #[arg(add = ArgValueCompleter::new(complete_tasks))]
+        task: String,
+    },
+
+    /// Marks a task as not completed.
+    Undo {
+        #[arg(add = ArgValueCompleter::new(complete_tasks))]
+        task: String,
+    },
+
+    /// Changes the title, notes or due date of a task.
+    Edit {
+        #[arg(add = ArgValueCompleter::new(complete_tasks))]
+        task: String,
+
+        #[arg(short, long)]
+        title: Option<String>,
+
+        #[arg(short, long)]
+        notes: Option<String>,
+
+        /// Due date as YYYY-MM-DD.
+        #[arg(short, long, value_parser = parse_due)]
+        due: Option<DateTime<Utc>>,
+    },
+
+    /// Moves a task under another parent or after another sibling.
+    Mv {
+        #[arg(add = ArgValueCompleter::new(complete_tasks))]
+        task: String,
+
+        /// New parent task id or name, the task is moved to the top level if omitted.
+        #[arg(short, long, add = ArgValueCompleter::new(complete_tasks))]
+        parent: Option<String>,
+
+        /// Previous sibling task id or name, the task is moved to the first position if omitted.
+        #[arg(short, long, add = ArgValueCompleter::new(complete_tasks))]
+        after: Option<String>,
+    },
+
+    /// Deletes a task.
+    Rm {
+        #[arg(add = ArgValueCompleter::new(complete_tasks))]
+        task: String,
+    },
+
+    /// Shows the details of a task.
+    Show {
+        #[arg(add = ArgValueCompleter::new(complete_tasks))]
+        task: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+enum CliError {
+    Tasks(TasksError),
+    Config(String),
+    Io(std::io::Error),
+}
+
+impl From<TasksError> for CliError {
+    fn from(err: TasksError) -> Self {
+        CliError::Tasks(err)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(err: std::io::Error) -> Self {
+        CliError::Io(err)
+    }
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Tasks(err) => match err {
+                TasksError::InvalidArgument(_)
+                | TasksError::NameNotFound(_)
+                | TasksError::AmbiguousName(_, _) => EX_USAGE,
+                TasksError::ParseError(_) | TasksError::JSONError(_) => EX_DATAERR,
+                TasksError::HttpError(_) | TasksError::MiddlewareError(_) => EX_UNAVAILABLE,
+                TasksError::ResponseError(_) => EX_PROTOCOL,
+                TasksError::IoError(_) => EX_IOERR,
+                #[cfg(feature = "csv")]
+                TasksError::CSVError(_) => EX_DATAERR,
+            },
+            CliError::Config(_) => EX_CONFIG,
+            CliError::Io(_) => EX_IOERR,
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Tasks(err) => write!(f, "{}", err),
+            CliError::Config(msg) => write!(f, "config error: {}", msg),
+            CliError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_ENV)
+        .complete();
+
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("gtasks: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), CliError> {
+    let command = match cli.command {
+        Command::Api(command) => command,
+        Command::Completions { shell } => return completions(shell),
+    };
+
+    let config = Config::load()?;
+    let format = cli.output.or(config.output).unwrap_or_default();
+    let service = Service::with_token(&config::access_token()?)?;
+    let mut names = Names {
+        service: &service,
+        cache: NameCache::load(),
+        tasks: None,
+    };
+
+    let list = config.tasklist(cli.list);
+    let result = match command {
+        ApiCommand::Lists => {
+            let tasklists = service.list_all_tasklists().await?;
+            names.cache.set_tasklists(&tasklists);
+            output::tasklists(format, &tasklists)?;
+            Ok(())
+        }
+        ApiCommand::Ls { list: ls_list, all } => {
+            let list = names.tasklist(&ls_list.unwrap_or(list)).await?;
+            let tasks = list_tasks(&service, &list, all).await?;
+            if all {
+                names.cache.set_tasks(&list, &tasks);
+            }
+            output::tasks(format, &tasks)?;
+            Ok(())
+        }
+        ApiCommand::Clear { list: clear_list } => {
+            let list = names.tasklist(&clear_list.unwrap_or(list)).await?;
+            Ok(service.clear_tasks(&list).await?)
+        }
+        ApiCommand::Task(command) => {
+            let list = names.tasklist(&list).await?;
+            run_task_command(&mut names, &list, format, command).await
+        }
+    };
+
+    names.cache.save();
+    result
+}
+
+async fn run_task_command(
+    names: &mut Names<'_>,
+    list: &str,
+    format: Format,
+    command: TaskCommand,
+) -> Result<(), CliError> {
+    let service = names.service;
+    match command {
+        TaskCommand::Add {
+            title,
+            notes,
+            due,
+            parent,
+        } => {
+            let task = Task {
+                title: Some(title),
+                notes,
+                due,
+                ..Default::default()
+            };
+            let opts = TaskInsertOptions {
+                parent: names.task_opt(list, parent).await?,
+                previous: None,
+            };
+
+            let task = service.insert_task(list, task, Some(opts)).await?;
+            output::task(format, &task)?;
+        }
+        TaskCommand::Done { task } => {
+            let task = names.task(list, &task).await?;
+            let task = set_status(service, list, &task, TaskStatus::Completed).await?;
+            output::task(format, &task)?;
+        }
+        TaskCommand::Undo { task } => {
+            let task = names.task(list, &task).await?;
+            let task = set_status(service, list, &task, TaskStatus::NeedsAction).await?;
+            output::task(format, &task)?;
+        }
+        TaskCommand::Edit {
+            task,
+            title,
+            notes,
+            due,
+        } => {
+            if title.is_none() && notes.is_none() && due.is_none() {
+                let msg = "nothing to edit, use --title, --notes or --due".to_owned();
+                return Err(TasksError::InvalidArgument(msg).into());
+            }
+
+            let task = names.task(list, &task).await?;
+            let patch = Task {
+                title,
+                notes,
+                due,
+                ..Default::default()
+            };
+            let task = service.patch_task(list, &task, patch).await?;
+            output::task(format, &task)?;
+        }
+        TaskCommand::Mv {
+            task,
+            parent,
+            after,
+        } => {
+            let task = names.task(list, &task).await?;
+            let opts = TaskInsertOptions {
+                parent: names.task_opt(list, parent).await?,
+                previous: names.task_opt(list, after).await?,
+            };
+            let task = service.move_task(list, &task, opts).await?;
+            output::task(format, &task)?;
+        }
+        TaskCommand::Rm { task } => {
+            let task = names.task(list, &task).await?;
+            service.delete_task(list, &task).await?
+        }
+        TaskCommand::Show { task } => {
+            let task = names.task(list, &task).await?;
+            match service.get_task(list, &task, None).await? {
+                Some(task) => output::task(format, &task)?,
+                None => return Err(TasksError::ResponseError("task not found".to_owned()).into()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Resolves the names given on the command line, refreshing the completion cache on the way.
+struct Names<'a> {
+    service: &'a Service,
+    cache: NameCache,
+
+    // tasks of the last task list searched, fetched once per command
+    tasks: Option<(String, Vec<Task>)>,
+}
+
+impl Names<'_> {
+    async fn tasklist(&mut self, query: &str) -> Result<String, CliError> {
+        if query == DEFAULT_TASKLIST {
+            return Ok(query.to_owned());
+        }
+
+        let tasklists = self.service.list_all_tasklists().await?;
+        self.cache.set_tasklists(&tasklists);
+
+        let tasklist = resolve::resolve(&tasklists, query)?;
+        Ok(tasklist.id.clone().unwrap_or_default())
+    }
+
+    async fn task(&mut self, tasklist_id: &str, query: &str) -> Result<String, CliError> {
+        let tasks = match self.tasks.take() {
+            Some((id, tasks)) if id == tasklist_id => tasks,
+            _ => {
+                let tasks = list_tasks(self.service, tasklist_id, true).await?;
+                self.cache.set_tasks(tasklist_id, &tasks);
+                tasks
+            }
+        };
+
+        let id = resolve::resolve(&tasks, query).map(|task| task.id.clone().unwrap_or_default());
+        self.tasks = Some((tasklist_id.to_owned(), tasks));
+        Ok(id?)
+    }
+
+    async fn task_opt(
+        &mut self,
+        tasklist_id: &str,
+        query: Option<String>,
+    ) -> Result<Option<String>, CliError> {
+        match query {
+            Some(query) => Ok(Some(self.task(tasklist_id, &query).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+async fn list_tasks(service: &Service, list: &str, all: bool) -> Result<Vec<Task>, CliError> {
+    let opts = TaskOptions {
+        max_results: Some(100),
+        show_completed: Some(all),
+        show_hidden: Some(all),
+        ..Default::default()
+    };
+
+    let mut tasks = Vec::new();
+    let mut page_token = None;
+    loop {
+        let opts = TaskOptions {
+            page_token,
+            ..opts.clone()
+        };
+        let page = service.list_tasks(list, Some(opts), None).await?;
+        let page = match page {
+            Some(page) => page,
+            None => break,
+        };
+        tasks.extend(page.items.unwrap_or_default());
+
+        page_token = page.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(tasks)
+}
+
+// Prints the script registering the dynamic completions, which call back into
+// the binary with the `COMPLETE` variable set.
+fn completions(shell: Shell) -> Result<(), CliError> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell.name()).ok_or_else(|| {
+        TasksError::InvalidArgument(format!("unsupported shell: {}", shell.name()))
+    })?;
+    let bin = std::env::args()
+        .next()
+        .unwrap_or_else(|| "gtasks".to_owned());
+
+    let mut out = io::stdout().lock();
+    completer.write_registration(COMPLETE_ENV, "gtasks", "gtasks", &bin, &mut out)?;
+    Ok(())
+}
+
+async fn set_status(
+    service: &Service,
+    list: &str,
+    task_id: &str,
+    status: TaskStatus,
+) -> Result<Task, CliError> {
+    let patch = Task {
+        status: Some(status),
+        ..Default::default()
+    };
+    Ok(service.patch_task(list, task_id, patch).await?)
+}
+
+fn parse_due(s: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        .map_err(|err| format!("expected YYYY-MM-DD: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_task_commands() {
+        let cli = Cli::try_parse_from(["gtasks", "-l", "Work", "done", "Milk"]).unwrap();
+        assert_eq!(cli.list.as_deref(), Some("Work"));
+        assert!(matches!(
+            cli.command,
+            Command::Api(ApiCommand::Task(TaskCommand::Done { task })) if task == "Milk"
+        ));
+
+        let cli = Cli::try_parse_from(["gtasks", "completions", "fish"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Completions { shell: Shell::Fish }
+        ));
+    }
+
+    #[test]
+    fn maps_errors_to_exit_codes() {
+        let not_found = CliError::from(TasksError::NameNotFound("Milk".to_owned()));
+        assert_eq!(not_found.exit_code(), EX_USAGE);
+
+        let ambiguous = TasksError::AmbiguousName("Milk".to_owned(), vec!["1".into(), "2".into()]);
+        assert_eq!(CliError::from(ambiguous).exit_code(), EX_USAGE);
+
+        let parse = TasksError::ParseError("bad".to_owned());
+        assert_eq!(CliError::from(parse).exit_code(), EX_DATAERR);
+        assert_eq!(
+            CliError::Config("missing".to_owned()).exit_code(),
+            EX_CONFIG
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use gtasks::{Task, TaskStatus, Tasklist};
+use serde::Serialize;
+use serde_derive::Deserialize;
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+}
+
+pub fn tasklists(format: Format, tasklists: &[Tasklist]) -> io::Result<()> {
+    if format == Format::Json {
+        return json(tasklists);
+    }
+
+    let rows = tasklists
+        .iter()
+        .map(|t| {
+            vec![
+                t.id.clone().unwrap_or_default(),
+                t.title.clone().unwrap_or_default(),
+                t.updated.map(|u| u.to_rfc3339()).unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    table(&["ID", "TITLE", "UPDATED"], rows)
+}
+
+// Prints the tasks as a tree, with the subtasks indented under their parents.
+pub fn tasks(format: Format, tasks: &[Task]) -> io::Result<()> {
+    if format == Format::Json {
+        return json(tasks);
+    }
+
+    let ids: HashSet<&str> = tasks.iter().filter_map(|t| t.id.as_deref()).collect();
+    let mut children: HashMap<Option<&str>, Vec<&Task>> = HashMap::new();
+    for task in tasks {
+        let parent = task.parent.as_deref().filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(task);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.position.cmp(&b.position));
+    }
+
+    let mut rows = Vec::with_capacity(tasks.len());
+    let mut stack: Vec<(usize, &Task)> = children
+        .get(&None)
+        .into_iter()
+        .flatten()
+        .rev()
+        .map(|task| (0, *task))
+        .collect();
+
+    while let Some((depth, task)) = stack.pop() {
+        rows.push(vec![
+            task.id.clone().unwrap_or_default(),
+            status_mark(task).to_owned(),
+            format!(
+                "{}{}",
+                "  ".repeat(depth),
+                task.title.as_deref().unwrap_or_default()
+            ),
+            task.due
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+        ]);
+
+        let nested = children
+            .get(&task.id.as_deref())
+            .into_iter()
+            .flatten()
+            .rev();
+        stack.extend(nested.map(|child| (depth + 1, *child)));
+    }
+
+    table(&["ID", "", "TITLE", "DUE"], rows)
+}
+
+pub fn task(format: Format, task: &Task) -> io::Result<()> {
+    if format == Format::Json {
+        return json(task);
+    }
+
+    let fields = [
+        ("id", task.id.clone()),
+        ("title", task.title.clone()),
+        ("status", Some(status_mark(task).to_owned())),
+        ("due", task.due.map(|d| d.format("%Y-%m-%d").to_string())),
+        ("completed", task.completed.map(|c| c.to_rfc3339())),
+        ("updated", task.updated.map(|u| u.to_rfc3339())),
+        ("parent", task.parent.clone()),
+        ("position", task.position.clone()),
+        ("notes", task.notes.clone()),
+    ];
+
+    let mut out = io::stdout().lock();
+    for (name, value) in fields.iter() {
+        if let Some(value) = value {
+            writeln!(out, "{:<10} {}", name, value.replace('\n', "\n           "))?;
+        }
+    }
+    Ok(())
+}
+
+fn status_mark(task: &Task) -> &'static str {
+    match task.status {
+        Some(TaskStatus::Completed) => "[x]",
+        _ => "[ ]",
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)
+}
+
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> io::Result<()> {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    let headers = headers.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(headers).chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
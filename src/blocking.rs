@@ -0,0 +1,180 @@
+//! Synchronous client for Google Tasks.
+//!
+//! [`Service`] has the methods of the async [`crate::Service`], which it runs on
+//! its own single-threaded Tokio runtime. As with `reqwest::blocking`, the methods
+//! must not be called from within an async runtime.
+
+use std::sync::Arc;
+
+use reqwest_middleware::Middleware;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::errors::Result;
+use crate::http::TokenProvider;
+use crate::snapshot::{AccountSnapshot, SnapshotOptions, TasklistSnapshot};
+use crate::{Task, TaskInsertOptions, TaskOptions, Tasklist, Tasklists, TasklistsOptions, Tasks};
+
+/// Service is a blocking abstraction over google tasks.
+pub struct Service {
+    inner: crate::Service,
+    runtime: Runtime,
+}
+
+impl Service {
+    /// Creates a new service with the given token provider.
+    pub fn with_auth<P>(token_provider: P) -> Result<Self>
+    where
+        P: TokenProvider,
+    {
+        Self::with_middleware(token_provider, Vec::new())
+    }
+
+    /// Creates a new service with the given token provider and additional middleware,
+    /// which handles the requests after the authorization, in the given order.
+    pub fn with_middleware<P>(
+        token_provider: P,
+        middleware: Vec<Arc<dyn Middleware>>,
+    ) -> Result<Self>
+    where
+        P: TokenProvider,
+    {
+        let inner = crate::Service::with_middleware(token_provider, middleware)?;
+        Self::from_async(inner)
+    }
+
+    /// Creates a new service with the given access token.
+    pub fn with_token(access_token: &str) -> Result<Self> {
+        Self::from_async(crate::Service::with_token(access_token)?)
+    }
+
+    /// Creates a blocking service running the requests of the given async service.
+    pub fn from_async(inner: crate::Service) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Service { inner, runtime })
+    }
+
+    /// Sets the root URL of the API, e.g. of a local test server.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.inner = self.inner.with_base_url(base_url);
+        self
+    }
+
+    /// Logs the request and response bodies at the debug level,
+    /// with the `Authorization` header and the notes of the tasks redacted.
+    #[cfg(feature = "tracing")]
+    pub fn with_body_logging(mut self, enabled: bool) -> Self {
+        self.inner = self.inner.with_body_logging(enabled);
+        self
+    }
+
+    /// Returns all the authenticated user's task lists.
+    pub fn list_tasklists(&self, opt: Option<TasklistsOptions>) -> Result<Tasklists> {
+        self.runtime.block_on(self.inner.list_tasklists(opt))
+    }
+
+    /// Returns the authenticated user's specified task list.
+    pub fn get_tasklist(&self, id: &str) -> Result<Tasklist> {
+        self.runtime.block_on(self.inner.get_tasklist(id))
+    }
+
+    /// Creates a new task list and adds it to the authenticated user's task lists.
+    pub fn insert_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+        self.runtime.block_on(self.inner.insert_tasklist(v))
+    }
+
+    /// Updates the authenticated user's specified task list.
+    pub fn update_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+        self.runtime.block_on(self.inner.update_tasklist(v))
+    }
+
+    /// Deletes the authenticated user's specified task list.
+    pub fn delete_tasklist(&self, id: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.delete_tasklist(id))
+    }
+
+    /// Updates the authenticated user's specified task list. This method supports patch semantics.
+    pub fn patch_tasklist(&self, tasklist_id: &str, v: Tasklist) -> Result<Tasklist> {
+        self.runtime
+            .block_on(self.inner.patch_tasklist(tasklist_id, v))
+    }
+
+    /// Returns all tasks in the specified task list.
+    pub fn list_tasks(
+        &self,
+        tasklist_id: &str,
+        opt: Option<TaskOptions>,
+        etag: Option<String>,
+    ) -> Result<Option<Tasks>> {
+        self.runtime
+            .block_on(self.inner.list_tasks(tasklist_id, opt, etag))
+    }
+
+    /// Returns the specified task.
+    pub fn get_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        etag: Option<String>,
+    ) -> Result<Option<Task>> {
+        self.runtime
+            .block_on(self.inner.get_task(tasklist_id, task_id, etag))
+    }
+
+    /// Creates a new task on the specified task list.
+    pub fn insert_task(
+        &self,
+        tasklist_id: &str,
+        v: Task,
+        opts: Option<TaskInsertOptions>,
+    ) -> Result<Task> {
+        self.runtime
+            .block_on(self.inner.insert_task(tasklist_id, v, opts))
+    }
+
+    /// Updates the specified task.
+    pub fn update_task(&self, tasklist_id: &str, v: Task) -> Result<Task> {
+        self.runtime
+            .block_on(self.inner.update_task(tasklist_id, v))
+    }
+
+    /// Deletes the specified task from the task list.
+    pub fn delete_task(&self, tasklist_id: &str, task_id: &str) -> Result<()> {
+        self.runtime
+            .block_on(self.inner.delete_task(tasklist_id, task_id))
+    }
+
+    /// Clears all completed tasks from the specified task list.
+    /// The affected tasks will be marked as 'hidden' and no longer be returned by default when retrieving all tasks for a task list.
+    pub fn clear_tasks(&self, tasklist_id: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.clear_tasks(tasklist_id))
+    }
+
+    /// Moves the specified task to another position in the task list.
+    /// This can include putting it as a child task under a new parent and/or move it to a different position among its sibling tasks.
+    pub fn move_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        opts: TaskInsertOptions,
+    ) -> Result<Task> {
+        self.runtime
+            .block_on(self.inner.move_task(tasklist_id, task_id, opts))
+    }
+
+    /// Updates the specified task. This method supports patch semantics.
+    pub fn patch_task(&self, tasklist_id: &str, task_id: &str, v: Task) -> Result<Task> {
+        self.runtime
+            .block_on(self.inner.patch_task(tasklist_id, task_id, v))
+    }
+
+    /// Returns all the task lists with their tasks, fetching the tasks of several task lists at the same time.
+    pub fn snapshot(&self, opts: Option<SnapshotOptions>) -> Result<AccountSnapshot> {
+        self.runtime.block_on(self.inner.snapshot(opts))
+    }
+
+    /// Returns the task lists named by the query, all of them if it names none, with the tasks
+    /// matching the query. See [`crate::query`] for the syntax.
+    pub fn query(&self, query: &str) -> Result<Vec<TasklistSnapshot>> {
+        self.runtime.block_on(self.inner.query(query))
+    }
+}
@@ -0,0 +1,320 @@
+//! Record/replay of HTTP interactions for deterministic tests.
+//!
+//! A [`Cassette`] is a middleware given to [`crate::Service::with_middleware`].
+//! In record mode, it forwards the requests and appends every request/response pair
+//! to a JSON file, with the credential headers and query parameters redacted. In replay mode, it answers the
+//! requests from the file without network, and fails on a request which was not recorded.
+//!
+//! ```no_run
+//! # fn example() -> gtasks::Result<()> {
+//! use std::sync::Arc;
+//! use gtasks::{cassette::Cassette, Service};
+//!
+//! let cassette = Arc::new(Cassette::replay("tests/cassettes/list.json")?);
+//! let service = Service::with_middleware(|| Ok("unused".to_owned()), vec![cassette])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::anyhow;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Request, Response, Url};
+use reqwest_middleware::{Middleware, Next, Result as MidWareResult};
+use serde_derive::{Deserialize, Serialize};
+use task_local_extensions::Extensions;
+
+use crate::errors::Result;
+
+const REDACTED: &str = "REDACTED";
+const SENSITIVE_PARAMS: [&str; 2] = ["access_token", "key"];
+const SENSITIVE_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+/// Whether the cassette records the interactions or replays them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Record,
+    Replay,
+}
+
+/// Recorded request and the response it got.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+/// Middleware recording the interactions to a cassette file or replaying them from it.
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    state: Mutex<State>,
+}
+
+struct State {
+    interactions: Vec<Interaction>,
+
+    /// Whether each interaction was replayed, so that repeated requests get the responses in order.
+    replayed: Vec<bool>,
+}
+
+impl Cassette {
+    /// Creates a cassette recording to the file, which is overwritten.
+    pub fn record<P: AsRef<Path>>(path: P) -> Self {
+        Self::new(path.as_ref(), Mode::Record, Vec::new())
+    }
+
+    /// Creates a cassette replaying the interactions of the file.
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read(path.as_ref())?;
+        let interactions = serde_json::from_slice(&content)?;
+        Ok(Self::new(path.as_ref(), Mode::Replay, interactions))
+    }
+
+    fn new(path: &Path, mode: Mode, interactions: Vec<Interaction>) -> Self {
+        Cassette {
+            path: path.to_owned(),
+            mode,
+            state: Mutex::new(State {
+                replayed: vec![false; interactions.len()],
+                interactions,
+            }),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the interactions recorded so far, or loaded from the file.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.lock().interactions.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    async fn record_interaction(
+        &self,
+        request: RecordedRequest,
+        resp: Response,
+    ) -> MidWareResult<Response> {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.bytes().await?;
+
+        let interaction = Interaction {
+            request,
+            response: RecordedResponse {
+                status: status.as_u16(),
+                headers: redacted_headers(&headers),
+                body: String::from_utf8_lossy(&body).into_owned(),
+            },
+        };
+
+        let mut state = self.lock();
+        state.interactions.push(interaction);
+        state.replayed.push(false);
+        let content = serde_json::to_vec_pretty(&state.interactions).map_err(|err| anyhow!(err))?;
+        fs::write(&self.path, content).map_err(|err| anyhow!(err))?;
+        drop(state);
+
+        let mut builder = http::Response::builder().status(status);
+        if let Some(response_headers) = builder.headers_mut() {
+            *response_headers = headers;
+        }
+        let resp = builder.body(body).map_err(|err| anyhow!(err))?;
+        Ok(Response::from(resp))
+    }
+
+    fn replay_interaction(&self, request: &RecordedRequest) -> MidWareResult<Response> {
+        let mut state = self.lock();
+        let found = state
+            .interactions
+            .iter()
+            .zip(state.replayed.iter())
+            .position(|(interaction, replayed)| {
+                !replayed && matches(&interaction.request, request)
+            });
+
+        let i = found.ok_or_else(|| {
+            anyhow!(
+                "no recorded interaction in {} for {} {}",
+                self.path.display(),
+                request.method,
+                request.url
+            )
+        })?;
+        state.replayed[i] = true;
+
+        let recorded = &state.interactions[i].response;
+        let mut builder = http::Response::builder().status(recorded.status);
+        for (name, value) in recorded.headers.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let resp = builder
+            .body(recorded.body.clone())
+            .map_err(|err| anyhow!(err))?;
+        Ok(Response::from(resp))
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl Middleware for Cassette {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MidWareResult<Response> {
+        let request = RecordedRequest {
+            method: req.method().to_string(),
+            url: redacted_url(req.url()),
+            headers: redacted_headers(req.headers()),
+            body: req
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| String::from_utf8_lossy(b).into_owned()),
+        };
+
+        match self.mode {
+            Mode::Record => {
+                let resp = next.run(req, extensions).await?;
+                self.record_interaction(request, resp).await
+            }
+            Mode::Replay => self.replay_interaction(&request),
+        }
+    }
+}
+
+// Requests match on the method, the URL and the body.
+fn matches(recorded: &RecordedRequest, request: &RecordedRequest) -> bool {
+    recorded.method == request.method
+        && recorded.url == request.url
+        && recorded.body == request.body
+}
+
+fn redacted_url(url: &Url) -> String {
+    let sensitive = |name: &str| SENSITIVE_PARAMS.contains(&name);
+    if !url.query_pairs().any(|(name, _)| sensitive(&name)) {
+        return url.to_string();
+    }
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = match sensitive(&name) {
+                true => REDACTED.to_owned(),
+                false => value.into_owned(),
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+
+    let mut url = url.clone();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url.to_string()
+}
+
+fn redacted_headers(headers: &HeaderMap<HeaderValue>) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().to_owned(), redact(name, value)))
+        .collect()
+}
+
+fn redact(name: &HeaderName, value: &HeaderValue) -> String {
+    if SENSITIVE_HEADERS.contains(&name.as_str()) {
+        return REDACTED.to_owned();
+    }
+    String::from_utf8_lossy(value.as_bytes()).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, url: &str) -> RecordedRequest {
+        RecordedRequest {
+            method: method.to_owned(),
+            url: url.to_owned(),
+            headers: BTreeMap::new(),
+            body: None,
+        }
+    }
+
+    fn interaction(url: &str, body: &str) -> Interaction {
+        Interaction {
+            request: request("GET", url),
+            response: RecordedResponse {
+                status: 200,
+                headers: BTreeMap::new(),
+                body: body.to_owned(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_in_order_and_rejects_unknown_requests() {
+        let interactions = vec![
+            interaction("https://example.com/a", "first"),
+            interaction("https://example.com/a", "second"),
+        ];
+        let cassette = Cassette::new(Path::new("unused.json"), Mode::Replay, interactions);
+
+        let a = request("GET", "https://example.com/a");
+        let first = cassette.replay_interaction(&a).unwrap();
+        assert_eq!(first.text().await.unwrap(), "first");
+        let second = cassette.replay_interaction(&a).unwrap();
+        assert_eq!(second.text().await.unwrap(), "second");
+
+        assert!(cassette.replay_interaction(&a).is_err());
+        assert!(cassette
+            .replay_interaction(&request("DELETE", "https://example.com/a"))
+            .is_err());
+    }
+
+    #[test]
+    fn redacts_credentials() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        headers.insert("if-none-match", HeaderValue::from_static("\"1\""));
+
+        let redacted = redacted_headers(&headers);
+        assert_eq!(redacted["authorization"], REDACTED);
+        assert_eq!(redacted["if-none-match"], "\"1\"");
+
+        let url = Url::parse("https://example.com/lists?key=secret&maxResults=5").unwrap();
+        assert_eq!(
+            redacted_url(&url),
+            "https://example.com/lists?key=REDACTED&maxResults=5"
+        );
+    }
+}
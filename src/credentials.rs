@@ -0,0 +1,77 @@
+//! Access token of the command line tools.
+//!
+//! The token is taken from the `GTASKS_ACCESS_TOKEN` variable or from
+//! `$XDG_CONFIG_HOME/gtasks/credentials.json`:
+//!
+//! ```json
+//! { "access_token": "..." }
+//! ```
+//!
+//! [`access_token`] reads the file on every call, so a token provider built on it
+//! picks up a token refreshed by another process:
+//!
+//! ```no_run
+//! let service = gtasks::Service::with_auth(|| Ok(gtasks::credentials::access_token()?))?;
+//! # Ok::<(), gtasks::TasksError>(())
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_derive::Deserialize;
+
+use crate::errors::{Result, TasksError::InvalidArgument};
+
+/// Variable holding the access token, it takes precedence over the credentials file.
+pub const TOKEN_ENV: &str = "GTASKS_ACCESS_TOKEN";
+
+const APP_DIR: &str = "gtasks";
+const CREDENTIALS_FILE: &str = "credentials.json";
+
+#[derive(Deserialize)]
+struct Credentials {
+    access_token: String,
+}
+
+/// Returns the access token from the `GTASKS_ACCESS_TOKEN` variable
+/// or from `$XDG_CONFIG_HOME/gtasks/credentials.json`.
+pub fn access_token() -> Result<String> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        return Ok(token);
+    }
+
+    let path = config_dir()?.join(CREDENTIALS_FILE);
+    let content = fs::read(&path).map_err(|err| {
+        InvalidArgument(format!(
+            "{}: {} (set {} or create the credentials file)",
+            path.display(),
+            err,
+            TOKEN_ENV
+        ))
+    })?;
+
+    let credentials: Credentials = serde_json::from_slice(&content)
+        .map_err(|err| InvalidArgument(format!("{}: {}", path.display(), err)))?;
+    Ok(credentials.access_token)
+}
+
+/// Returns `$XDG_CONFIG_HOME/gtasks`, falling back to `$HOME/.config/gtasks`.
+pub fn config_dir() -> Result<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Returns `$XDG_CACHE_HOME/gtasks`, falling back to `$HOME/.cache/gtasks`.
+pub fn cache_dir() -> Result<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+fn xdg_dir(var: &str, home_fallback: &str) -> Result<PathBuf> {
+    let base = match std::env::var_os(var).filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(home_fallback))
+            .ok_or_else(|| InvalidArgument(format!("neither {} nor HOME is set", var)))?,
+    };
+
+    Ok(base.join(APP_DIR))
+}
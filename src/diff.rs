@@ -0,0 +1,344 @@
+//! Differences between two snapshots of an account.
+//!
+//! Task lists and tasks are matched by their ids. Tasks marked as deleted count as absent.
+
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::snapshot::AccountSnapshot;
+use crate::{Task, Tasklist};
+
+/// Field of a task compared by the diff.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Field {
+    Title,
+    Notes,
+    Due,
+    Status,
+    Parent,
+    Position,
+}
+
+impl Field {
+    pub const ALL: [Field; 6] = [
+        Field::Title,
+        Field::Notes,
+        Field::Due,
+        Field::Status,
+        Field::Parent,
+        Field::Position,
+    ];
+
+    /// Whether the field places the task in the list, rather than being its content.
+    pub fn is_structural(self) -> bool {
+        matches!(self, Field::Parent | Field::Position)
+    }
+
+    /// Whether the two tasks have the same value of the field.
+    pub fn same(self, a: &Task, b: &Task) -> bool {
+        match self {
+            Field::Title => a.title == b.title,
+            Field::Notes => a.notes == b.notes,
+            Field::Due => a.due == b.due,
+            Field::Status => a.status == b.status,
+            Field::Parent => a.parent == b.parent,
+            Field::Position => a.position == b.position,
+        }
+    }
+
+    /// Sets the field of the task to the value of the other task, the status along with the completion time.
+    pub fn copy(self, from: &Task, to: &mut Task) {
+        match self {
+            Field::Title => to.title = from.title.clone(),
+            Field::Notes => to.notes = from.notes.clone(),
+            Field::Due => to.due = from.due,
+            Field::Status => {
+                to.status = from.status;
+                to.completed = from.completed;
+            }
+            Field::Parent => to.parent = from.parent.clone(),
+            Field::Position => to.position = from.position.clone(),
+        }
+    }
+}
+
+/// Returns the fields whose values differ between the two tasks.
+pub fn changed_fields(before: &Task, after: &Task) -> Vec<Field> {
+    Field::ALL
+        .into_iter()
+        .filter(|field| !field.same(before, after))
+        .collect()
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    pub tasklists: Vec<TasklistDiff>,
+    pub tasks: Vec<TaskDiff>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.tasklists.is_empty() && self.tasks.is_empty()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TasklistDiff {
+    pub tasklist_id: String,
+    pub change: TasklistChange,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum TasklistChange {
+    Added(Tasklist),
+    Removed(Tasklist),
+    Renamed { before: Tasklist, after: Tasklist },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TaskDiff {
+    pub tasklist_id: String,
+    pub task_id: String,
+    pub change: TaskChange,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskChange {
+    Added(Box<Task>),
+    Removed(Box<Task>),
+    Changed {
+        before: Box<Task>,
+        after: Box<Task>,
+        fields: Vec<Field>,
+    },
+}
+
+impl TaskDiff {
+    /// Whether the title, notes, due date or status changed.
+    pub fn is_modified(&self) -> bool {
+        match &self.change {
+            TaskChange::Changed { fields, .. } => fields.iter().any(|f| !f.is_structural()),
+            _ => false,
+        }
+    }
+
+    /// Whether the parent or the position changed.
+    pub fn is_moved(&self) -> bool {
+        match &self.change {
+            TaskChange::Changed { fields, .. } => fields.iter().any(|f| f.is_structural()),
+            _ => false,
+        }
+    }
+}
+
+/// Returns the changes from the `before` snapshot to the `after` one, in the order of
+/// the `after` snapshot followed by the removals in the order of the `before` snapshot.
+pub fn diff(before: &AccountSnapshot, after: &AccountSnapshot) -> SnapshotDiff {
+    let before_lists = tasklists_by_id(before);
+    let after_lists = tasklists_by_id(after);
+    let before_tasks = tasks_by_id(before);
+    let after_tasks = tasks_by_id(after);
+
+    let mut diff = SnapshotDiff::default();
+
+    for (id, tasklist) in after_lists.iter() {
+        let change = match before_lists.get(id) {
+            None => TasklistChange::Added((*tasklist).clone()),
+            Some(old) if old.title != tasklist.title => TasklistChange::Renamed {
+                before: (*old).clone(),
+                after: (*tasklist).clone(),
+            },
+            Some(_) => continue,
+        };
+        diff.tasklists.push(TasklistDiff {
+            tasklist_id: id.to_string(),
+            change,
+        });
+    }
+    for (id, tasklist) in before_lists.iter() {
+        if !after_lists.contains(id) {
+            diff.tasklists.push(TasklistDiff {
+                tasklist_id: id.to_string(),
+                change: TasklistChange::Removed((*tasklist).clone()),
+            });
+        }
+    }
+
+    for ((tasklist_id, task_id), task) in after_tasks.iter() {
+        let change = match before_tasks.get(&(tasklist_id, task_id)) {
+            None => TaskChange::Added(Box::new((*task).clone())),
+            Some(old) => {
+                let fields = changed_fields(old, task);
+                if fields.is_empty() {
+                    continue;
+                }
+                TaskChange::Changed {
+                    before: Box::new((*old).clone()),
+                    after: Box::new((*task).clone()),
+                    fields,
+                }
+            }
+        };
+        diff.tasks.push(TaskDiff {
+            tasklist_id: tasklist_id.to_string(),
+            task_id: task_id.to_string(),
+            change,
+        });
+    }
+    for ((tasklist_id, task_id), task) in before_tasks.iter() {
+        if !after_tasks.contains(&(tasklist_id, task_id)) {
+            diff.tasks.push(TaskDiff {
+                tasklist_id: tasklist_id.to_string(),
+                task_id: task_id.to_string(),
+                change: TaskChange::Removed(Box::new((*task).clone())),
+            });
+        }
+    }
+
+    diff
+}
+
+// Entries in the order of the snapshot, with a lookup by id.
+pub(crate) struct Ordered<K, V> {
+    keys: Vec<K>,
+    values: HashMap<K, V>,
+}
+
+impl<K: std::hash::Hash + Eq + Clone, V> Ordered<K, V> {
+    fn new() -> Self {
+        Ordered {
+            keys: Vec::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.values.insert(key.clone(), value).is_none() {
+            self.keys.push(key);
+        }
+    }
+
+    pub(crate) fn get(&self, key: &K) -> Option<&V> {
+        self.values.get(key)
+    }
+
+    pub(crate) fn contains(&self, key: &K) -> bool {
+        self.values.contains_key(key)
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &K> {
+        self.keys.iter()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.keys.iter().map(move |k| (k, &self.values[k]))
+    }
+}
+
+pub(crate) fn tasklists_by_id(snapshot: &AccountSnapshot) -> Ordered<&str, &Tasklist> {
+    let mut tasklists = Ordered::new();
+    for list in snapshot.tasklists.iter() {
+        if let Some(id) = list.tasklist.id.as_deref() {
+            tasklists.insert(id, &list.tasklist);
+        }
+    }
+    tasklists
+}
+
+pub(crate) fn tasks_by_id(snapshot: &AccountSnapshot) -> Ordered<(&str, &str), &Task> {
+    let mut tasks = Ordered::new();
+    for list in snapshot.tasklists.iter() {
+        let tasklist_id = match list.tasklist.id.as_deref() {
+            Some(id) => id,
+            None => continue,
+        };
+        for task in list.tasks.iter() {
+            if task.deleted == Some(true) {
+                continue;
+            }
+            if let Some(id) = task.id.as_deref() {
+                tasks.insert((tasklist_id, id), task);
+            }
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::TasklistSnapshot;
+    use crate::TaskStatus;
+    use chrono::Utc;
+
+    fn task(id: &str, title: &str, position: &str) -> Task {
+        Task {
+            id: Some(id.to_owned()),
+            title: Some(title.to_owned()),
+            position: Some(position.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(lists: Vec<(&str, &str, Vec<Task>)>) -> AccountSnapshot {
+        AccountSnapshot {
+            taken_at: Utc::now(),
+            tasklists: lists
+                .into_iter()
+                .map(|(id, title, tasks)| TasklistSnapshot {
+                    tasklist: Tasklist {
+                        id: Some(id.to_owned()),
+                        title: Some(title.to_owned()),
+                        ..Default::default()
+                    },
+                    tasks,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn diffs_tasklists_and_tasks() {
+        let before = snapshot(vec![
+            ("l1", "Work", vec![task("a", "a", "1"), task("b", "b", "2")]),
+            ("l2", "Home", vec![task("c", "c", "1")]),
+        ]);
+
+        let mut done = task("a", "a", "3");
+        done.status = Some(TaskStatus::Completed);
+        let mut deleted = task("c", "c", "1");
+        deleted.deleted = Some(true);
+        let after = snapshot(vec![
+            ("l1", "Office", vec![done, task("d", "d", "4")]),
+            ("l2", "Home", vec![deleted]),
+            ("l3", "New", vec![]),
+        ]);
+
+        let diff = diff(&before, &after);
+        let lists: Vec<_> = diff
+            .tasklists
+            .iter()
+            .map(|d| (d.tasklist_id.as_str(), &d.change))
+            .collect();
+        assert!(matches!(lists[0], ("l1", TasklistChange::Renamed { .. })));
+        assert!(matches!(lists[1], ("l3", TasklistChange::Added(_))));
+        assert_eq!(lists.len(), 2);
+
+        let tasks: Vec<_> = diff.tasks.iter().map(|d| d.task_id.as_str()).collect();
+        assert_eq!(tasks, ["a", "d", "b", "c"]);
+        match &diff.tasks[0].change {
+            TaskChange::Changed { fields, .. } => {
+                assert_eq!(fields, &[Field::Status, Field::Position])
+            }
+            other => panic!("unexpected change: {:?}", other),
+        }
+        assert!(diff.tasks[0].is_modified() && diff.tasks[0].is_moved());
+        assert!(matches!(diff.tasks[1].change, TaskChange::Added(_)));
+        assert!(matches!(diff.tasks[2].change, TaskChange::Removed(_)));
+        assert!(matches!(diff.tasks[3].change, TaskChange::Removed(_)));
+    }
+}
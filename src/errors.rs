@@ -13,9 +13,25 @@ pub enum TasksError {
     #[error("JSON error: {0}")]
     JSONError(#[from] serde_json::Error),
 
+    #[cfg(feature = "csv")]
+    #[error("CSV error: {0}")]
+    CSVError(#[from] csv::Error),
+
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+
     #[error("invalid argument: {0}")]
     InvalidArgument(String),
 
     #[error("invalid response: {0}")]
     ResponseError(String),
+
+    #[error("parse error: {0}")]
+    ParseError(String),
+
+    #[error("no match for name: {0}")]
+    NameNotFound(String),
+
+    #[error("ambiguous name {0}, it matches: {}", .1.join(", "))]
+    AmbiguousName(String, Vec<String>),
 }
@@ -0,0 +1,735 @@
+//! In-memory implementation of [`TasksApi`] for tests.
+//!
+//! [`FakeTasks`] follows the semantics of the Google Tasks API: etags change with
+//! every modification, list results are paginated and filtered by the `show*`
+//! and date options, tasks are ordered by position among their siblings,
+//! deleting a task deletes its subtasks and clearing a list hides its completed tasks.
+//! Failures are [`TasksError::ResponseError`]s holding the JSON error body of the API.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, NaiveTime, Utc};
+use serde_json::json;
+
+use crate::errors::{
+    Result,
+    TasksError::{self, InvalidArgument, ResponseError},
+};
+use crate::{
+    Task, TaskInsertOptions, TaskOptions, TaskStatus, Tasklist, Tasklists, TasklistsOptions, Tasks,
+    TasksApi, BASE_URL,
+};
+
+const DEFAULT_TASKLIST: &str = "@default";
+const DEFAULT_TASKLIST_TITLE: &str = "My Tasks";
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+/// In-memory task store, created with the default task list of an account.
+pub struct FakeTasks {
+    state: Mutex<State>,
+    counter: AtomicU64,
+}
+
+struct State {
+    default_id: String,
+    lists: Vec<List>,
+}
+
+struct List {
+    tasklist: Tasklist,
+
+    /// ETag of the task collection, changes with any of its tasks.
+    tasks_etag: String,
+
+    tasks: Vec<Task>,
+}
+
+impl Default for FakeTasks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeTasks {
+    pub fn new() -> Self {
+        let fake = FakeTasks {
+            state: Mutex::new(State {
+                default_id: String::new(),
+                lists: Vec::new(),
+            }),
+            counter: AtomicU64::new(0),
+        };
+
+        let default_list = fake.new_list(DEFAULT_TASKLIST_TITLE.to_owned());
+        let mut state = fake.lock();
+        state.default_id = default_list.tasklist.id.clone().unwrap_or_default();
+        state.lists.push(default_list);
+        drop(state);
+
+        fake
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // a panicking test thread leaves the state consistent, every change is applied at once
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn next(&self) -> u64 {
+        self.counter.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn etag(&self) -> String {
+        format!("\"{}\"", self.next())
+    }
+
+    fn new_list(&self, title: String) -> List {
+        let id = format!("list{}", self.next());
+        List {
+            tasklist: Tasklist {
+                kind: Some("tasks#taskList".to_owned()),
+                self_link: Some(format!("{}/users/@me/lists/{}", BASE_URL, id)),
+                id: Some(id),
+                etag: Some(self.etag()),
+                title: Some(title),
+                updated: Some(Utc::now()),
+            },
+            tasks_etag: self.etag(),
+            tasks: Vec::new(),
+        }
+    }
+
+    // Marks the task as modified.
+    fn touch(&self, task: &mut Task) {
+        task.etag = Some(self.etag());
+        task.updated = Some(Utc::now());
+    }
+
+    // Inserts the task among the siblings under the parent after the previous sibling,
+    // renumbering the positions of the siblings.
+    fn place(&self, list: &mut List, task_id: &str, opts: &TaskInsertOptions) -> Result<()> {
+        let parent = opts.parent.as_deref();
+        if let Some(parent) = parent {
+            let is_descendant = std::iter::successors(Some(parent), |id| {
+                list.task(id).ok().and_then(|t| t.parent.as_deref())
+            })
+            .any(|id| id == task_id);
+            match list.task(parent) {
+                Ok(p) if p.deleted != Some(true) && !is_descendant => {}
+                _ => return Err(bad_request(format!("invalid parent task: {}", parent))),
+            }
+        }
+
+        let mut siblings = list.children(parent);
+        siblings.retain(|id| id != task_id);
+
+        let index = match opts.previous.as_deref() {
+            Some(previous) => match siblings.iter().position(|id| id == previous) {
+                Some(i) => i + 1,
+                None => return Err(bad_request(format!("invalid previous task: {}", previous))),
+            },
+            None => 0,
+        };
+        siblings.insert(index, task_id.to_owned());
+
+        for (i, id) in siblings.iter().enumerate() {
+            let position = format!("{:020}", i);
+            let task = list.task_mut(id)?;
+            if id == task_id {
+                task.parent = parent.map(str::to_owned);
+            }
+            if task.position.as_deref() != Some(position.as_str()) {
+                task.position = Some(position);
+                task.etag = Some(self.etag());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl State {
+    fn list(&self, id: &str) -> Result<&List> {
+        let id = self.resolve(id);
+        self.lists
+            .iter()
+            .find(|l| l.tasklist.id.as_deref() == Some(id))
+            .ok_or_else(|| not_found(format!("task list not found: {}", id)))
+    }
+
+    fn list_mut(&mut self, id: &str) -> Result<&mut List> {
+        let id = self.resolve(id).to_owned();
+        self.lists
+            .iter_mut()
+            .find(|l| l.tasklist.id.as_deref() == Some(id.as_str()))
+            .ok_or_else(|| not_found(format!("task list not found: {}", id)))
+    }
+
+    fn resolve<'a>(&'a self, id: &'a str) -> &'a str {
+        match id {
+            DEFAULT_TASKLIST => &self.default_id,
+            _ => id,
+        }
+    }
+}
+
+impl List {
+    fn task(&self, id: &str) -> Result<&Task> {
+        self.tasks
+            .iter()
+            .find(|t| t.id.as_deref() == Some(id))
+            .ok_or_else(|| not_found(format!("task not found: {}", id)))
+    }
+
+    fn task_mut(&mut self, id: &str) -> Result<&mut Task> {
+        self.tasks
+            .iter_mut()
+            .find(|t| t.id.as_deref() == Some(id))
+            .ok_or_else(|| not_found(format!("task not found: {}", id)))
+    }
+
+    // Returns the ids of the tasks under the parent, ordered by position.
+    fn children(&self, parent: Option<&str>) -> Vec<String> {
+        let mut children: Vec<&Task> = self
+            .tasks
+            .iter()
+            .filter(|t| t.parent.as_deref() == parent && t.deleted != Some(true))
+            .collect();
+        children.sort_by(|a, b| a.position.cmp(&b.position));
+        children.into_iter().filter_map(|t| t.id.clone()).collect()
+    }
+
+    // Returns the tasks depth-first, every parent before its subtasks.
+    fn ordered(&self) -> Vec<&Task> {
+        let mut children: HashMap<Option<&str>, Vec<&Task>> = HashMap::new();
+        for task in self.tasks.iter() {
+            children
+                .entry(task.parent.as_deref())
+                .or_default()
+                .push(task);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by(|a, b| a.position.cmp(&b.position));
+        }
+
+        let mut ordered = Vec::with_capacity(self.tasks.len());
+        let mut stack: Vec<&Task> = children.remove(&None).unwrap_or_default();
+        stack.reverse();
+        while let Some(task) = stack.pop() {
+            ordered.push(task);
+            if let Some(nested) = children.remove(&task.id.as_deref()) {
+                stack.extend(nested.into_iter().rev());
+            }
+        }
+        ordered
+    }
+
+    fn descendants(&self, id: &str) -> Vec<String> {
+        let mut ids = vec![id.to_owned()];
+        let mut i = 0;
+        while i < ids.len() {
+            let nested = self
+                .tasks
+                .iter()
+                .filter(|t| t.parent.as_deref() == Some(ids[i].as_str()))
+                .filter_map(|t| t.id.clone());
+            ids.extend(nested.collect::<Vec<_>>());
+            i += 1;
+        }
+        ids
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl TasksApi for FakeTasks {
+    async fn list_tasklists(&self, opt: Option<TasklistsOptions>) -> Result<Tasklists> {
+        let opt = opt.unwrap_or_default();
+        let state = self.lock();
+
+        let tasklists: Vec<&Tasklist> = state.lists.iter().map(|l| &l.tasklist).collect();
+        let (items, next_page_token) = page(&tasklists, opt.max_results, opt.page_token)?;
+
+        Ok(Tasklists {
+            kind: "tasks#taskLists".to_owned(),
+            etag: self.etag(),
+            next_page_token,
+            items: items.iter().copied().cloned().collect(),
+        })
+    }
+
+    async fn get_tasklist(&self, id: &str) -> Result<Tasklist> {
+        Ok(self.lock().list(id)?.tasklist.clone())
+    }
+
+    async fn insert_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+        let list = self.new_list(v.title.unwrap_or_default());
+        let tasklist = list.tasklist.clone();
+        self.lock().lists.push(list);
+        Ok(tasklist)
+    }
+
+    async fn update_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+        let id = match v.id.as_ref() {
+            Some(id) => id,
+            None => return Err(InvalidArgument("tasklist id cannot be None".to_owned())),
+        };
+
+        let mut state = self.lock();
+        let tasklist = &mut state.list_mut(id)?.tasklist;
+        tasklist.title = Some(v.title.unwrap_or_default());
+        tasklist.etag = Some(self.etag());
+        tasklist.updated = Some(Utc::now());
+        Ok(tasklist.clone())
+    }
+
+    async fn delete_tasklist(&self, id: &str) -> Result<()> {
+        let mut state = self.lock();
+        let id = state.resolve(id).to_owned();
+        if id == state.default_id {
+            return Err(bad_request(
+                "the default task list cannot be deleted".to_owned(),
+            ));
+        }
+
+        state.list(&id)?;
+        state
+            .lists
+            .retain(|l| l.tasklist.id.as_deref() != Some(id.as_str()));
+        Ok(())
+    }
+
+    async fn patch_tasklist(&self, tasklist_id: &str, v: Tasklist) -> Result<Tasklist> {
+        let mut state = self.lock();
+        let tasklist = &mut state.list_mut(tasklist_id)?.tasklist;
+        if v.title.is_some() {
+            tasklist.title = v.title;
+        }
+        tasklist.etag = Some(self.etag());
+        tasklist.updated = Some(Utc::now());
+        Ok(tasklist.clone())
+    }
+
+    async fn list_tasks(
+        &self,
+        tasklist_id: &str,
+        opt: Option<TaskOptions>,
+        etag: Option<String>,
+    ) -> Result<Option<Tasks>> {
+        let opt = opt.unwrap_or_default();
+        let state = self.lock();
+        let list = state.list(tasklist_id)?;
+
+        if etag.as_deref() == Some(list.tasks_etag.as_str()) {
+            return Ok(None);
+        }
+
+        let completed_min = parse_time("completedMin", opt.completed_min.as_deref())?;
+        let completed_max = parse_time("completedMax", opt.completed_max.as_deref())?;
+        let in_range = |value: Option<DateTime<Utc>>, min, max| match (min, max) {
+            (None, None) => true,
+            (min, max) => value
+                .is_some_and(|v| min.is_none_or(|min| v >= min) && max.is_none_or(|max| v <= max)),
+        };
+
+        let tasks: Vec<&Task> = list
+            .ordered()
+            .into_iter()
+            .filter(|t| {
+                opt.show_completed.unwrap_or(true) || t.status != Some(TaskStatus::Completed)
+            })
+            .filter(|t| opt.show_deleted.unwrap_or(false) || t.deleted != Some(true))
+            .filter(|t| opt.show_hidden.unwrap_or(false) || t.hidden != Some(true))
+            .filter(|t| in_range(t.due, opt.due_min, opt.due_max))
+            .filter(|t| in_range(t.completed, completed_min, completed_max))
+            .filter(|t| in_range(t.updated, opt.updated_min, None))
+            .collect();
+
+        let (items, next_page_token) = page(&tasks, opt.max_results, opt.page_token)?;
+        Ok(Some(Tasks {
+            kind: "tasks#tasks".to_owned(),
+            etag: list.tasks_etag.clone(),
+            next_page_token,
+            items: Some(items.iter().copied().cloned().collect())
+                .filter(|i: &Vec<Task>| !i.is_empty()),
+        }))
+    }
+
+    async fn get_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        etag: Option<String>,
+    ) -> Result<Option<Task>> {
+        let state = self.lock();
+        let task = state.list(tasklist_id)?.task(task_id)?;
+
+        if etag.is_some() && etag == task.etag {
+            return Ok(None);
+        }
+        Ok(Some(task.clone()))
+    }
+
+    async fn insert_task(
+        &self,
+        tasklist_id: &str,
+        v: Task,
+        opts: Option<TaskInsertOptions>,
+    ) -> Result<Task> {
+        let opts = opts.unwrap_or_default();
+        let mut state = self.lock();
+        let list = state.list_mut(tasklist_id)?;
+
+        let id = format!("task{}", self.next());
+        let list_id = list.tasklist.id.clone().unwrap_or_default();
+        let mut task = Task {
+            kind: Some("tasks#task".to_owned()),
+            self_link: Some(format!("{}/lists/{}/tasks/{}", BASE_URL, list_id, id)),
+            id: Some(id.clone()),
+            title: v.title,
+            notes: v.notes,
+            links: v.links,
+            due: v.due.map(date_only),
+            ..Default::default()
+        };
+        set_status(
+            &mut task,
+            v.status.unwrap_or(TaskStatus::NeedsAction),
+            v.completed,
+        );
+        self.touch(&mut task);
+
+        list.tasks.push(task);
+        if let Err(err) = self.place(list, &id, &opts) {
+            list.tasks.pop();
+            return Err(err);
+        }
+        list.tasks_etag = self.etag();
+
+        Ok(list.task(&id)?.clone())
+    }
+
+    async fn update_task(&self, tasklist_id: &str, v: Task) -> Result<Task> {
+        let id = match v.id.as_ref() {
+            Some(id) => id,
+            None => return Err(InvalidArgument("task id cannot be None".to_owned())),
+        };
+
+        let mut state = self.lock();
+        let list = state.list_mut(tasklist_id)?;
+        let task = list.task_mut(id)?;
+
+        task.title = v.title;
+        task.notes = v.notes;
+        task.links = v.links;
+        task.due = v.due.map(date_only);
+        task.deleted = v.deleted;
+        set_status(
+            task,
+            v.status.unwrap_or(TaskStatus::NeedsAction),
+            v.completed,
+        );
+        self.touch(task);
+
+        let task = task.clone();
+        list.tasks_etag = self.etag();
+        Ok(task)
+    }
+
+    async fn delete_task(&self, tasklist_id: &str, task_id: &str) -> Result<()> {
+        let mut state = self.lock();
+        let list = state.list_mut(tasklist_id)?;
+        list.task(task_id)?;
+
+        for id in list.descendants(task_id) {
+            let task = list.task_mut(&id)?;
+            task.deleted = Some(true);
+            self.touch(task);
+        }
+        list.tasks_etag = self.etag();
+        Ok(())
+    }
+
+    async fn clear_tasks(&self, tasklist_id: &str) -> Result<()> {
+        let mut state = self.lock();
+        let list = state.list_mut(tasklist_id)?;
+
+        for task in list.tasks.iter_mut() {
+            if task.status == Some(TaskStatus::Completed) && task.hidden != Some(true) {
+                task.hidden = Some(true);
+                self.touch(task);
+            }
+        }
+        list.tasks_etag = self.etag();
+        Ok(())
+    }
+
+    async fn move_task(
+        &self,
+        tasklist_id: &str,
+        task_id: &str,
+        opts: TaskInsertOptions,
+    ) -> Result<Task> {
+        let mut state = self.lock();
+        let list = state.list_mut(tasklist_id)?;
+        list.task(task_id)?;
+
+        self.place(list, task_id, &opts)?;
+        let task = list.task_mut(task_id)?;
+        self.touch(task);
+
+        let task = task.clone();
+        list.tasks_etag = self.etag();
+        Ok(task)
+    }
+
+    async fn patch_task(&self, tasklist_id: &str, task_id: &str, v: Task) -> Result<Task> {
+        let mut state = self.lock();
+        let list = state.list_mut(tasklist_id)?;
+        let task = list.task_mut(task_id)?;
+
+        if v.title.is_some() {
+            task.title = v.title;
+        }
+        if v.notes.is_some() {
+            task.notes = v.notes;
+        }
+        if v.links.is_some() {
+            task.links = v.links;
+        }
+        if v.due.is_some() {
+            task.due = v.due.map(date_only);
+        }
+        if v.deleted.is_some() {
+            task.deleted = v.deleted;
+        }
+        if let Some(status) = v.status {
+            set_status(task, status, v.completed);
+        }
+        self.touch(task);
+
+        let task = task.clone();
+        list.tasks_etag = self.etag();
+        Ok(task)
+    }
+}
+
+// Returns the page of the items selected by the options, and the token of the next page.
+fn page<T>(
+    items: &[T],
+    max_results: Option<u64>,
+    page_token: Option<String>,
+) -> Result<(&[T], Option<String>)> {
+    let size = max_results
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE) as usize;
+    let start = match page_token {
+        Some(token) => token
+            .parse::<usize>()
+            .map_err(|_| bad_request(format!("invalid page token: {}", token)))?,
+        None => 0,
+    };
+
+    let start = start.min(items.len());
+    let end = (start + size).min(items.len());
+    let next = Some(end.to_string()).filter(|_| end < items.len());
+    Ok((&items[start..end], next))
+}
+
+fn parse_time(name: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>> {
+    value
+        .map(|v| {
+            DateTime::parse_from_rfc3339(v)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|err| bad_request(format!("invalid {}: {}", name, err)))
+        })
+        .transpose()
+}
+
+fn not_found(message: String) -> TasksError {
+    api_error(404, "notFound", message)
+}
+
+fn bad_request(message: String) -> TasksError {
+    api_error(400, "invalid", message)
+}
+
+fn api_error(code: u16, reason: &str, message: String) -> TasksError {
+    ResponseError(error_body(code, reason, &message))
+}
+
+// Returns the JSON error body the API responds with.
+pub(crate) fn error_body(code: u16, reason: &str, message: &str) -> String {
+    json!({
+        "error": {
+            "code": code,
+            "message": message,
+            "errors": [{"message": message, "domain": "global", "reason": reason}],
+        }
+    })
+    .to_string()
+}
+
+// The API discards the time portion of due dates.
+fn date_only(due: DateTime<Utc>) -> DateTime<Utc> {
+    due.date_naive().and_time(NaiveTime::MIN).and_utc()
+}
+
+fn set_status(task: &mut Task, status: TaskStatus, completed: Option<DateTime<Utc>>) {
+    task.completed = match status {
+        TaskStatus::Completed => completed.or(task.completed).or_else(|| Some(Utc::now())),
+        TaskStatus::NeedsAction => None,
+    };
+    task.status = Some(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(title: &str) -> Task {
+        Task {
+            title: Some(title.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn after(parent: Option<&Task>, previous: Option<&Task>) -> Option<TaskInsertOptions> {
+        Some(TaskInsertOptions {
+            parent: parent.and_then(|t| t.id.clone()),
+            previous: previous.and_then(|t| t.id.clone()),
+        })
+    }
+
+    async fn titles(fake: &FakeTasks, opt: TaskOptions) -> Vec<String> {
+        let tasks = fake.list_tasks("@default", Some(opt), None).await.unwrap();
+        tasks
+            .and_then(|t| t.items)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|t| t.title)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn orders_moves_and_deletes_subtrees() {
+        let fake = FakeTasks::new();
+        let a = fake.insert_task("@default", task("a"), None).await.unwrap();
+        let c = fake
+            .insert_task("@default", task("c"), after(None, Some(&a)))
+            .await
+            .unwrap();
+        let b = fake
+            .insert_task("@default", task("b"), after(None, Some(&a)))
+            .await
+            .unwrap();
+        let a1 = fake
+            .insert_task("@default", task("a1"), after(Some(&a), None))
+            .await
+            .unwrap();
+        assert_eq!(
+            titles(&fake, TaskOptions::default()).await,
+            ["a", "a1", "b", "c"]
+        );
+
+        fake.move_task(
+            "@default",
+            a.id.as_deref().unwrap(),
+            after(None, Some(&c)).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            titles(&fake, TaskOptions::default()).await,
+            ["b", "c", "a", "a1"]
+        );
+
+        let cycle = fake
+            .move_task(
+                "@default",
+                a.id.as_deref().unwrap(),
+                after(Some(&a1), None).unwrap(),
+            )
+            .await;
+        assert!(matches!(cycle, Err(ResponseError(_))));
+
+        fake.delete_task("@default", a.id.as_deref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(titles(&fake, TaskOptions::default()).await, ["b", "c"]);
+
+        let deleted = TaskOptions {
+            show_deleted: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(titles(&fake, deleted).await, ["b", "c", "a", "a1"]);
+
+        let b = fake
+            .get_task("@default", b.id.as_deref().unwrap(), None)
+            .await;
+        let c = fake
+            .get_task("@default", c.id.as_deref().unwrap(), None)
+            .await;
+        assert!(b.unwrap().unwrap().position < c.unwrap().unwrap().position);
+    }
+
+    #[tokio::test]
+    async fn filters_paginates_and_checks_etags() {
+        let fake = FakeTasks::new();
+        for title in ["a", "b", "c"] {
+            let t = fake
+                .insert_task("@default", task(title), None)
+                .await
+                .unwrap();
+            if title == "b" {
+                let done = Task {
+                    status: Some(TaskStatus::Completed),
+                    ..Default::default()
+                };
+                let done = fake
+                    .patch_task("@default", t.id.as_deref().unwrap(), done)
+                    .await;
+                assert!(done.unwrap().completed.is_some());
+            }
+        }
+
+        let page = TaskOptions {
+            max_results: Some(2),
+            ..Default::default()
+        };
+        let first = fake
+            .list_tasks("@default", Some(page.clone()), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.items.unwrap().len(), 2);
+        let next = TaskOptions {
+            page_token: first.next_page_token,
+            ..page
+        };
+        assert_eq!(titles(&fake, next).await, ["a"]);
+
+        fake.clear_tasks("@default").await.unwrap();
+        assert_eq!(titles(&fake, TaskOptions::default()).await, ["c", "a"]);
+        let open = TaskOptions {
+            show_completed: Some(false),
+            show_hidden: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(titles(&fake, open).await, ["c", "a"]);
+
+        let etag = fake
+            .list_tasks("@default", None, None)
+            .await
+            .unwrap()
+            .unwrap()
+            .etag;
+        let unchanged = fake.list_tasks("@default", None, Some(etag.clone())).await;
+        assert!(unchanged.unwrap().is_none());
+        fake.insert_task("@default", task("d"), None).await.unwrap();
+        let changed = fake.list_tasks("@default", None, Some(etag)).await;
+        assert!(changed.unwrap().is_some());
+    }
+}
@@ -0,0 +1,106 @@
+//! Footer of machine-readable properties at the end of the notes of a task.
+//!
+//! The footer starts at the last `-- gtasks` line of the notes and holds one iCalendar-style
+//! `NAME[;PARAMS]:VALUE` property per line. The text before it is written by people and kept
+//! as it is. Each module reads and writes its own properties and keeps the others.
+
+/// Line starting the footer of the notes.
+const SEPARATOR: &str = "-- gtasks";
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Footer {
+    original: Option<String>,
+    text: String,
+    parsed: Vec<String>,
+    lines: Vec<String>,
+}
+
+impl Footer {
+    pub(crate) fn parse(notes: Option<&str>) -> Self {
+        let (text, footer) = split(notes.unwrap_or_default());
+        let lines: Vec<String> = footer
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect();
+        Footer {
+            original: notes.map(str::to_owned),
+            text: text.to_owned(),
+            parsed: lines.clone(),
+            lines,
+        }
+    }
+
+    /// Returns the value of the property, the parameters such as `VALUE=DATE` ignored.
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.lines.iter().find_map(|line| {
+            let (head, value) = line.split_once(':')?;
+            let prop = head.split(';').next().unwrap_or(head);
+            prop.eq_ignore_ascii_case(name).then_some(value)
+        })
+    }
+
+    /// Sets the value of the property, or removes it when `None`.
+    pub(crate) fn set(&mut self, name: &str, value: Option<&str>) {
+        let position = self.lines.iter().position(|line| is_property(line, name));
+        self.lines.retain(|line| !is_property(line, name));
+        if let Some(value) = value {
+            let line = format!("{}:{}", name, value);
+            let index = position.unwrap_or(self.lines.len()).min(self.lines.len());
+            self.lines.insert(index, line);
+        }
+    }
+
+    /// Renders the notes, `None` when there is neither text nor property.
+    /// The notes are returned as they were parsed unless a property has changed,
+    /// the text is kept as it is either way.
+    pub(crate) fn notes(&self) -> Option<String> {
+        if self.lines == self.parsed {
+            return self.original.clone();
+        }
+        if self.lines.is_empty() {
+            return Some(self.text.clone()).filter(|text| !text.trim().is_empty());
+        }
+
+        let mut notes = self.text.clone();
+        if !notes.trim().is_empty() {
+            // a blank line between the text and the footer
+            while !notes.ends_with("\n\n") {
+                notes.push('\n');
+            }
+        } else {
+            notes.clear();
+        }
+        notes.push_str(SEPARATOR);
+        for line in self.lines.iter() {
+            notes.push('\n');
+            notes.push_str(line);
+        }
+        Some(notes)
+    }
+}
+
+/// Splits the notes into the text and the footer properties.
+pub(crate) fn split(notes: &str) -> (&str, Option<&str>) {
+    let mut offset = 0;
+    let mut found = None;
+    for line in notes.split_inclusive('\n') {
+        if line.trim_end() == SEPARATOR {
+            found = Some((offset, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    match found {
+        Some((start, end)) => (notes[..start].trim_end(), Some(&notes[end..])),
+        None => (notes, None),
+    }
+}
+
+fn is_property(line: &str, name: &str) -> bool {
+    let head = line.split(':').next().unwrap_or(line);
+    head.split(';')
+        .next()
+        .is_some_and(|prop| prop.eq_ignore_ascii_case(name))
+}
@@ -1,5 +1,6 @@
 use std::pin::Pin;
 use std::result::Result as StdResult;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Error as AnyError};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
@@ -30,18 +31,33 @@ where
         AuthMiddleware(Box::pin(token_provider))
     }
 
-    pub(crate) fn init_http_client(self) -> Result<HttpClient> {
-        let mut headers = HeaderMap::new();
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        let http_client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
-
-        Ok(ClientBuilder::new(http_client).with(self).build())
+    // Builds the client with the authorization followed by the given middleware,
+    // on top of the connection pool of the given reqwest client.
+    pub(crate) fn init_http_client(
+        self,
+        client: reqwest::Client,
+        middleware: Vec<Arc<dyn Middleware>>,
+    ) -> HttpClient {
+        let builder = middleware.into_iter().fold(
+            ClientBuilder::new(client).with(self),
+            ClientBuilder::with_arc,
+        );
+        builder.build()
     }
 }
 
-#[async_trait::async_trait]
+// Builds the reqwest client, which holds the connection pool.
+pub(crate) fn reqwest_client() -> Result<reqwest::Client> {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()?;
+    Ok(client)
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
 impl<TP> Middleware for AuthMiddleware<TP>
 where
     TP: TokenProvider,
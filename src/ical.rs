@@ -0,0 +1,436 @@
+//! Conversion between Google Tasks and iCalendar (RFC 5545) VTODO components.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::errors::{Result, TasksError::ParseError};
+use crate::{tasks, Task, TaskStatus, Tasklist, Tasks, TasksApi};
+
+const PRODID: &str = "-//makarski//gtasks-rs//EN";
+const POSITION_PROP: &str = "X-GTASKS-POSITION";
+const MAX_LINE_OCTETS: usize = 75;
+
+/// VTODO parsed from an iCalendar document.
+#[derive(Debug, Clone, Default)]
+pub struct Todo {
+    /// Value of the UID property.
+    pub uid: Option<String>,
+
+    /// UID of the parent VTODO, taken from the RELATED-TO property.
+    pub parent_uid: Option<String>,
+
+    /// Position among the sibling tasks, see [`Task::position`].
+    pub position: Option<String>,
+
+    /// Task with the title, notes, status, due and completed fields populated.
+    pub task: Task,
+}
+
+/// Renders the task list and its tasks as a VCALENDAR of VTODOs.
+pub fn export(tasklist: &Tasklist, tasks: &Tasks) -> String {
+    let mut out = String::new();
+    begin_calendar(&mut out, tasklist.title.as_deref());
+
+    for task in tasks.items.iter().flatten() {
+        write_vtodo(&mut out, task);
+    }
+
+    write_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Renders a single task as a VCALENDAR with one VTODO.
+pub fn export_task(task: &Task) -> String {
+    let mut out = String::new();
+    begin_calendar(&mut out, None);
+    write_vtodo(&mut out, task);
+    write_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Parses all VTODO components of the iCalendar document in their original order.
+pub fn parse(input: &str) -> Result<Vec<Todo>> {
+    let mut todos = Vec::new();
+    let mut current: Option<Todo> = None;
+    let mut nested = 0;
+
+    for line in unfold(input) {
+        let (name, params, value) = split_property(&line)?;
+
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => {
+                current = Some(Todo::default())
+            }
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VTODO") => todos.extend(current.take()),
+            (_, Some(todo)) if nested == 0 => parse_property(todo, &name, &params, value)?,
+            _ => {}
+        }
+    }
+
+    if current.is_some() {
+        return Err(ParseError("unterminated VTODO component".to_owned()));
+    }
+
+    Ok(todos)
+}
+
+/// Parses the iCalendar document and creates its VTODOs in the specified task list.
+/// Subtasks are created under their parents and the siblings keep their order.
+/// Returns the created tasks.
+pub async fn import<A: TasksApi + ?Sized>(
+    service: &A,
+    tasklist_id: &str,
+    input: &str,
+) -> Result<Vec<Task>> {
+    let todos = parse(input)?;
+
+    let uids: HashMap<&str, usize> = todos
+        .iter()
+        .enumerate()
+        .filter_map(|(i, todo)| todo.uid.as_deref().map(|uid| (uid, i)))
+        .collect();
+
+    let mut order: Vec<usize> = (0..todos.len()).collect();
+    order.sort_by(|a, b| todos[*a].position.cmp(&todos[*b].position));
+    let index: HashMap<usize, usize> = order.iter().enumerate().map(|(n, i)| (*i, n)).collect();
+
+    let nodes = order
+        .iter()
+        .map(|i| {
+            let todo = &todos[*i];
+            let parent = todo
+                .parent_uid
+                .as_deref()
+                .and_then(|uid| uids.get(uid))
+                .and_then(|p| index.get(p).copied());
+            (todo.task.clone(), parent)
+        })
+        .collect();
+
+    tasks::insert_tree(service, tasklist_id, nodes).await
+}
+
+fn begin_calendar(out: &mut String, name: Option<&str>) {
+    write_line(out, "BEGIN:VCALENDAR");
+    write_line(out, "VERSION:2.0");
+    write_line(out, &format!("PRODID:{}", PRODID));
+    if let Some(name) = name {
+        write_line(out, &format!("X-WR-CALNAME:{}", escape(name)));
+    }
+}
+
+fn write_vtodo(out: &mut String, task: &Task) {
+    write_line(out, "BEGIN:VTODO");
+
+    if let Some(id) = task.id.as_ref() {
+        write_line(out, &format!("UID:{}", id));
+    }
+
+    let stamp = task.updated.unwrap_or_else(Utc::now);
+    write_line(out, &format!("DTSTAMP:{}", format_datetime(&stamp)));
+
+    if let Some(updated) = task.updated.as_ref() {
+        write_line(out, &format!("LAST-MODIFIED:{}", format_datetime(updated)));
+    }
+    if let Some(title) = task.title.as_ref() {
+        write_line(out, &format!("SUMMARY:{}", escape(title)));
+    }
+    if let Some(notes) = task.notes.as_ref() {
+        write_line(out, &format!("DESCRIPTION:{}", escape(notes)));
+    }
+    if let Some(due) = task.due.as_ref() {
+        write_line(out, &format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+    }
+
+    let status = match task.status {
+        Some(TaskStatus::Completed) => "COMPLETED",
+        _ => "NEEDS-ACTION",
+    };
+    write_line(out, &format!("STATUS:{}", status));
+
+    if let Some(completed) = task.completed.as_ref() {
+        write_line(out, &format!("COMPLETED:{}", format_datetime(completed)));
+    }
+    if let Some(parent) = task.parent.as_ref() {
+        write_line(out, &format!("RELATED-TO;RELTYPE=PARENT:{}", parent));
+    }
+    if let Some(position) = task.position.as_ref() {
+        write_line(out, &format!("{}:{}", POSITION_PROP, position));
+    }
+
+    write_line(out, "END:VTODO");
+}
+
+fn parse_property(todo: &mut Todo, name: &str, params: &str, value: String) -> Result<()> {
+    match name {
+        "UID" => todo.uid = Some(value),
+        "SUMMARY" => todo.task.title = Some(unescape(&value)),
+        "DESCRIPTION" => todo.task.notes = Some(unescape(&value)),
+        "DUE" => todo.task.due = Some(parse_datetime(&value)?),
+        "COMPLETED" => todo.task.completed = Some(parse_datetime(&value)?),
+        "STATUS" => {
+            todo.task.status = match value.to_ascii_uppercase().as_str() {
+                "COMPLETED" => Some(TaskStatus::Completed),
+                _ => Some(TaskStatus::NeedsAction),
+            }
+        }
+        "RELATED-TO" => {
+            let reltype = params
+                .split(';')
+                .find_map(|p| p.strip_prefix("RELTYPE="))
+                .unwrap_or("PARENT");
+            if reltype.eq_ignore_ascii_case("PARENT") {
+                todo.parent_uid = Some(value);
+            }
+        }
+        POSITION_PROP => todo.position = Some(value),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// Splits a content line into the upper-cased name, the raw parameters and the value.
+fn split_property(line: &str) -> Result<(String, String, String)> {
+    let colon = find_value_separator(line)
+        .ok_or_else(|| ParseError(format!("invalid content line: {}", line)))?;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, params) = match head.find(';') {
+        Some(i) => (&head[..i], &head[i + 1..]),
+        None => (head, ""),
+    };
+
+    Ok((
+        name.to_ascii_uppercase(),
+        params.to_owned(),
+        value.to_owned(),
+    ))
+}
+
+// Finds the colon separating the value, skipping colons in quoted parameter values.
+fn find_value_separator(line: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_datetime(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+
+    let naive = value.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(naive, "%Y%m%dT%H%M%S")
+        .map(|dt| dt.and_utc())
+        .map_err(|err| ParseError(format!("invalid date-time {}: {}", value, err)))
+}
+
+fn format_datetime(dt: &DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// Writes the content line, folded to lines of at most 75 octets.
+fn write_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in input.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(items: Vec<Task>) -> Tasks {
+        Tasks {
+            kind: "tasks#tasks".to_owned(),
+            etag: "etag".to_owned(),
+            next_page_token: None,
+            items: Some(items),
+        }
+    }
+
+    #[test]
+    fn export_parse_round_trip() {
+        let tasklist = Tasklist {
+            title: Some("Inbox".to_owned()),
+            ..Default::default()
+        };
+        let parent = Task {
+            id: Some("p1".to_owned()),
+            title: Some("Plan; the, trip".to_owned()),
+            notes: Some("line one\nline two".to_owned()),
+            due: Some("2024-03-01T00:00:00Z".parse().unwrap()),
+            status: Some(TaskStatus::NeedsAction),
+            position: Some("00000000000000000000".to_owned()),
+            ..Default::default()
+        };
+        let child = Task {
+            id: Some("c1".to_owned()),
+            title: Some("Book flights ".repeat(10)),
+            status: Some(TaskStatus::Completed),
+            completed: Some("2024-02-10T08:30:00Z".parse().unwrap()),
+            parent: Some("p1".to_owned()),
+            ..Default::default()
+        };
+
+        let out = export(&tasklist, &tasks(vec![parent, child]));
+        assert!(out.lines().all(|line| line.len() <= MAX_LINE_OCTETS + 1));
+
+        let todos = parse(&out).unwrap();
+        assert_eq!(todos.len(), 2);
+
+        assert_eq!(todos[0].uid.as_deref(), Some("p1"));
+        assert_eq!(todos[0].task.title.as_deref(), Some("Plan; the, trip"));
+        assert_eq!(todos[0].task.notes.as_deref(), Some("line one\nline two"));
+        assert_eq!(todos[0].position.as_deref(), Some("00000000000000000000"));
+        assert_eq!(
+            todos[0].task.due,
+            Some("2024-03-01T00:00:00Z".parse().unwrap())
+        );
+
+        assert_eq!(todos[1].parent_uid.as_deref(), Some("p1"));
+        assert_eq!(todos[1].task.title, Some("Book flights ".repeat(10)));
+        assert_eq!(todos[1].task.status, Some(TaskStatus::Completed));
+        assert_eq!(
+            todos[1].task.completed,
+            Some("2024-02-10T08:30:00Z".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_skips_nested_components() {
+        let input = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:Call\r\n Bob\r\nBEGIN:VALARM\r\nDESCRIPTION:ignored\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+
+        let todos = parse(input).unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].task.title.as_deref(), Some("CallBob"));
+        assert_eq!(todos[0].task.notes, None);
+    }
+
+    #[test]
+    fn parse_unterminated_vtodo() {
+        assert!(parse("BEGIN:VTODO\r\nSUMMARY:Call\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn import_nests_and_orders_siblings() {
+        let todo = |uid: &str, parent: Option<&str>, position: &str| {
+            let mut lines = format!("BEGIN:VTODO\r\nUID:{}\r\nSUMMARY:{}\r\n", uid, uid);
+            if let Some(parent) = parent {
+                lines.push_str(&format!("RELATED-TO:{}\r\n", parent));
+            }
+            lines + &format!("{}:{}\r\nEND:VTODO\r\n", POSITION_PROP, position)
+        };
+        let input = [
+            todo("b", None, "2"),
+            todo("a2", Some("a"), "2"),
+            todo("a", None, "1"),
+            todo("a1", Some("a"), "1"),
+            todo("self", Some("self"), "3"),
+        ]
+        .concat();
+
+        let fake = crate::FakeTasks::new();
+        import(&fake, "@default", &input).await.unwrap();
+        assert_eq!(tree(&fake).await, ["a", "  a1", "  a2", "b", "self"]);
+    }
+
+    #[tokio::test]
+    async fn import_breaks_related_to_cycles() {
+        let todo = |uid: &str, parent: &str| {
+            format!(
+                "BEGIN:VTODO\r\nUID:{}\r\nSUMMARY:{}\r\nRELATED-TO:{}\r\nEND:VTODO\r\n",
+                uid, uid, parent
+            )
+        };
+        let input = [todo("a", "b"), todo("b", "a")].concat();
+
+        let fake = crate::FakeTasks::new();
+        let created = import(&fake, "@default", &input).await.unwrap();
+        assert_eq!(created.len(), 2);
+        assert_eq!(tree(&fake).await, ["a", "b"]);
+    }
+
+    // Returns the titles of the default list in position order, indented by depth.
+    async fn tree(fake: &crate::FakeTasks) -> Vec<String> {
+        let tasks = tasks::list_all(fake, "@default", Default::default())
+            .await
+            .unwrap();
+        let mut tasks: Vec<&Task> = tasks.iter().collect();
+        tasks.sort_by(|a, b| a.position.cmp(&b.position));
+        let mut out = Vec::new();
+        fn walk(tasks: &[&Task], parent: Option<&str>, depth: usize, out: &mut Vec<String>) {
+            for task in tasks.iter().filter(|t| t.parent.as_deref() == parent) {
+                let title = task.title.as_deref().unwrap_or_default();
+                out.push(format!("{}{}", "  ".repeat(depth), title));
+                walk(tasks, task.id.as_deref(), depth + 1, out);
+            }
+        }
+        walk(&tasks, None, 0, &mut out);
+        out
+    }
+}
@@ -1,12 +1,54 @@
+#[cfg(feature = "tracing")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 use reqwest::Response;
+use reqwest_middleware::Middleware;
+use serde::de::DeserializeOwned;
 
+mod api;
+pub mod backup;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+#[cfg(all(feature = "cassette", not(target_arch = "wasm32")))]
+pub mod cassette;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod credentials;
+pub mod diff;
 mod errors;
+mod fake;
+mod footer;
 mod http;
+pub mod ical;
+pub mod markdown;
+pub mod merge;
+pub mod metadata;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mirror;
+#[cfg(all(feature = "pool", not(target_arch = "wasm32")))]
+pub mod pool;
+pub mod query;
+pub mod recurrence;
+pub mod resolve;
+pub mod snapshot;
 mod tasklists;
 mod tasks;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod todotxt;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "watch")]
+mod watcher;
 
-use errors::{Result, TasksError::ResponseError};
+use errors::TasksError::ResponseError;
 use http::{AuthMiddleware, HttpClient};
+use snapshot::{AccountSnapshot, SnapshotOptions, TasklistSnapshot};
+
+pub use api::TasksApi;
+pub use errors::{Result, TasksError};
+pub use fake::FakeTasks;
 
 pub use tasklists::{
     ListOptions as TasklistsOptions, {Tasklist, Tasklists},
@@ -17,11 +59,17 @@ pub use tasks::{
     {Task, TaskLink, TaskStatus, Tasks},
 };
 
+#[cfg(feature = "watch")]
+pub use watcher::{Event as WatchEvent, Watcher};
+
 const BASE_URL: &str = "https://www.googleapis.com/tasks/v1";
 
 /// Service is an abstraction over google tasks.
 pub struct Service {
     http_client: HttpClient,
+    base_url: String,
+    #[cfg(feature = "tracing")]
+    body_logging: Arc<AtomicBool>,
 }
 
 impl Service {
@@ -30,9 +78,41 @@ impl Service {
     where
         P: http::TokenProvider,
     {
-        let http_client = AuthMiddleware::new(token_provider).init_http_client()?;
+        Self::with_middleware(token_provider, Vec::new())
+    }
+
+    /// Creates a new service with the given token provider and additional middleware,
+    /// which handles the requests after the authorization, in the given order.
+    pub fn with_middleware<P>(
+        token_provider: P,
+        middleware: Vec<Arc<dyn Middleware>>,
+    ) -> Result<Self>
+    where
+        P: http::TokenProvider,
+    {
+        let client = http::reqwest_client()?;
+        Ok(Self::with_client(client, token_provider, middleware))
+    }
+
+    // Creates a service sharing the connection pool of the reqwest client.
+    pub(crate) fn with_client<P>(
+        client: reqwest::Client,
+        token_provider: P,
+        middleware: Vec<Arc<dyn Middleware>>,
+    ) -> Self
+    where
+        P: http::TokenProvider,
+    {
+        #[cfg(feature = "tracing")]
+        let (middleware, body_logging) = trace::with_trace_middleware(middleware);
+        let http_client = AuthMiddleware::new(token_provider).init_http_client(client, middleware);
 
-        Ok(Service { http_client })
+        Service {
+            http_client,
+            base_url: BASE_URL.to_owned(),
+            #[cfg(feature = "tracing")]
+            body_logging,
+        }
     }
 
     /// Creates a new service with the given access token.
@@ -41,6 +121,20 @@ impl Service {
         Self::with_auth(move || Ok(access_token.clone()))
     }
 
+    /// Sets the root URL of the API, e.g. of a local test server.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Logs the request and response bodies at the debug level,
+    /// with the `Authorization` header and the notes of the tasks redacted.
+    #[cfg(feature = "tracing")]
+    pub fn with_body_logging(self, enabled: bool) -> Self {
+        self.body_logging.store(enabled, Ordering::Relaxed);
+        self
+    }
+
     /// Creates a new service with the given access token.
     #[deprecated(since = "0.5.0", note = "Please use `Service::with_token` instead")]
     pub fn new(access_token: &str) -> Result<Self> {
@@ -49,32 +143,32 @@ impl Service {
 
     /// Returns all the authenticated user's task lists.
     pub async fn list_tasklists(&self, opt: Option<TasklistsOptions>) -> Result<Tasklists> {
-        tasklists::list(&self.http_client, opt).await
+        tasklists::list(&self.http_client, &self.base_url, opt).await
     }
 
     /// Returns the authenticated user's specified task list.
     pub async fn get_tasklist(&self, id: &str) -> Result<Tasklist> {
-        tasklists::get(&self.http_client, id).await
+        tasklists::get(&self.http_client, &self.base_url, id).await
     }
 
     /// Creates a new task list and adds it to the authenticated user's task lists.
     pub async fn insert_tasklist(&self, v: tasklists::Tasklist) -> Result<Tasklist> {
-        tasklists::insert(&self.http_client, v).await
+        tasklists::insert(&self.http_client, &self.base_url, v).await
     }
 
     /// Updates the authenticated user's specified task list.
     pub async fn update_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
-        tasklists::update(&self.http_client, v).await
+        tasklists::update(&self.http_client, &self.base_url, v).await
     }
 
     /// Deletes the authenticated user's specified task list.
     pub async fn delete_tasklist(&self, id: &str) -> Result<()> {
-        tasklists::delete(&self.http_client, id).await
+        tasklists::delete(&self.http_client, &self.base_url, id).await
     }
 
     /// Updates the authenticated user's specified task list. This method supports patch semantics.
     pub async fn patch_tasklist(&self, tasklist_id: &str, v: Tasklist) -> Result<Tasklist> {
-        tasklists::patch(&self.http_client, tasklist_id, v).await
+        tasklists::patch(&self.http_client, &self.base_url, tasklist_id, v).await
     }
 
     /// Returns all tasks in the specified task list.
@@ -84,7 +178,7 @@ impl Service {
         opt: Option<TaskOptions>,
         etag: Option<String>,
     ) -> Result<Option<Tasks>> {
-        tasks::list(&self.http_client, tasklist_id, opt, etag).await
+        tasks::list(&self.http_client, &self.base_url, tasklist_id, opt, etag).await
     }
 
     /// Returns the specified task.
@@ -94,7 +188,14 @@ impl Service {
         task_id: &str,
         etag: Option<String>,
     ) -> Result<Option<Task>> {
-        tasks::get(&self.http_client, tasklist_id, task_id, etag).await
+        tasks::get(
+            &self.http_client,
+            &self.base_url,
+            tasklist_id,
+            task_id,
+            etag,
+        )
+        .await
     }
 
     /// Creates a new task on the specified task list.
@@ -104,23 +205,23 @@ impl Service {
         v: Task,
         opts: Option<TaskInsertOptions>,
     ) -> Result<Task> {
-        tasks::insert(&self.http_client, tasklist_id, v, opts).await
+        tasks::insert(&self.http_client, &self.base_url, tasklist_id, v, opts).await
     }
 
     /// Updates the specified task.
     pub async fn update_task(&self, tasklist_id: &str, v: Task) -> Result<Task> {
-        tasks::update(&self.http_client, tasklist_id, v).await
+        tasks::update(&self.http_client, &self.base_url, tasklist_id, v).await
     }
 
     /// Deletes the specified task from the task list.
     pub async fn delete_task(&self, tasklist_id: &str, task_id: &str) -> Result<()> {
-        tasks::delete(&self.http_client, tasklist_id, task_id).await
+        tasks::delete(&self.http_client, &self.base_url, tasklist_id, task_id).await
     }
 
     /// Clears all completed tasks from the specified task list.
     /// The affected tasks will be marked as 'hidden' and no longer be returned by default when retrieving all tasks for a task list.
     pub async fn clear_tasks(&self, tasklist_id: &str) -> Result<()> {
-        tasks::clear(&self.http_client, tasklist_id).await
+        tasks::clear(&self.http_client, &self.base_url, tasklist_id).await
     }
 
     /// Moves the specified task to another position in the task list.
@@ -131,12 +232,30 @@ impl Service {
         task_id: &str,
         opts: TaskInsertOptions,
     ) -> Result<Task> {
-        tasks::move_task(&self.http_client, tasklist_id, task_id, opts).await
+        tasks::move_task(
+            &self.http_client,
+            &self.base_url,
+            tasklist_id,
+            task_id,
+            opts,
+        )
+        .await
     }
 
     /// Updates the specified task. This method supports patch semantics.
     pub async fn patch_task(&self, tasklist_id: &str, task_id: &str, v: Task) -> Result<Task> {
-        tasks::patch(&self.http_client, tasklist_id, task_id, v).await
+        tasks::patch(&self.http_client, &self.base_url, tasklist_id, task_id, v).await
+    }
+
+    /// Returns all the task lists with their tasks, fetching the tasks of several task lists at the same time.
+    pub async fn snapshot(&self, opts: Option<SnapshotOptions>) -> Result<AccountSnapshot> {
+        snapshot::snapshot(self, opts.unwrap_or_default()).await
+    }
+
+    /// Returns the task lists named by the query, all of them if it names none, with the tasks
+    /// matching the query. See [`query`] for the syntax.
+    pub async fn query(&self, query: &str) -> Result<Vec<TasklistSnapshot>> {
+        query::run(self, &query::Query::parse(query)?).await
     }
 }
 
@@ -148,10 +267,7 @@ async fn ensure_status_success(resp: Response) -> Result<Response> {
     Ok(resp)
 }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
+// Parses the JSON body, without relying on the `json` feature of reqwest.
+async fn parse_json<T: DeserializeOwned>(resp: Response) -> Result<T> {
+    Ok(serde_json::from_slice(&resp.bytes().await?)?)
 }
@@ -0,0 +1,544 @@
+//! Conversion between Google Tasks and Markdown checklists.
+//!
+//! ```markdown
+//! # Groceries
+//!
+//! - [ ] Milk (due: 2024-03-01)
+//!   2 bottles, low fat
+//!   - [x] Check the fridge
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::errors::{Result, TasksError::ResponseError};
+use crate::{tasks, Task, TaskInsertOptions, TaskOptions, TaskStatus, Tasklist, Tasks, TasksApi};
+
+const INDENT: &str = "  ";
+const DUE_PREFIX: &str = " (due: ";
+const DEFAULT_TITLE: &str = "Imported";
+
+/// Markdown checklist parsed from a document.
+#[derive(Debug, Clone, Default)]
+pub struct Checklist {
+    /// Text of the first level-one heading, if any.
+    pub title: Option<String>,
+
+    /// Top-level checklist items.
+    pub items: Vec<Item>,
+}
+
+/// Checklist item with its nested items.
+#[derive(Debug, Clone, Default)]
+pub struct Item {
+    pub title: String,
+    pub checked: bool,
+    pub notes: Option<String>,
+    pub due: Option<DateTime<Utc>>,
+    pub children: Vec<Item>,
+}
+
+/// Defines how a checklist is applied to a task list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Creates a task for every checklist item.
+    Create,
+
+    /// Creates only the items which have no task with the same title under the same parent,
+    /// and completes the existing tasks whose items are checked.
+    Diff,
+}
+
+/// Tasks changed by an import.
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub created: Vec<Task>,
+    pub completed: Vec<Task>,
+}
+
+/// Renders the task list as a Markdown checklist.
+/// Subtasks are indented under their parents and siblings are ordered by position.
+pub fn export(tasklist: &Tasklist, tasks: &Tasks) -> String {
+    let items: Vec<&Task> = tasks
+        .items
+        .iter()
+        .flatten()
+        .filter(|task| !task.deleted.unwrap_or(false))
+        .collect();
+
+    let ids: HashSet<&str> = items.iter().filter_map(|t| t.id.as_deref()).collect();
+    let mut children: HashMap<Option<&str>, Vec<&Task>> = HashMap::new();
+    for task in items {
+        let parent = task.parent.as_deref().filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(task);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.position.cmp(&b.position));
+    }
+
+    let mut out = String::new();
+    if let Some(title) = tasklist.title.as_ref() {
+        out.push_str(&format!("# {}\n\n", title));
+    }
+    write_items(&mut out, &children, None, 0);
+    out
+}
+
+/// Parses the checklist items of a Markdown document.
+/// Lines which are neither items nor indented under an item are ignored.
+pub fn parse(input: &str) -> Result<Checklist> {
+    let mut checklist = Checklist::default();
+    // path to the last item, with the indentation of every item on the path
+    let mut path: Vec<(usize, usize)> = Vec::new();
+
+    for line in input.lines() {
+        // only spaces and tabs indent, so that the indent is a char boundary
+        let trimmed = line.trim_start_matches([' ', '\t']);
+        let indent = line.len() - trimmed.len();
+
+        if let Some(title) = trimmed.strip_prefix("# ") {
+            if checklist.title.is_none() && path.is_empty() && indent == 0 {
+                checklist.title = Some(title.trim().to_owned());
+                continue;
+            }
+        }
+
+        if let Some(item) = parse_item(trimmed) {
+            while path.last().is_some_and(|(_, i)| *i >= indent) {
+                path.pop();
+            }
+
+            let siblings = match path.is_empty() {
+                true => &mut checklist.items,
+                false => &mut item_mut(&mut checklist.items, &path).children,
+            };
+            siblings.push(item);
+            path.push((siblings.len() - 1, indent));
+            continue;
+        }
+
+        let item_indent = match path.last() {
+            Some((_, item_indent)) => *item_indent,
+            None => continue,
+        };
+
+        if trimmed.is_empty() || indent > item_indent {
+            let item = item_mut(&mut checklist.items, &path);
+            let text = &line[indent.min(item_indent + INDENT.len())..];
+            let notes = item.notes.get_or_insert_with(String::new);
+            if !notes.is_empty() {
+                notes.push('\n');
+            }
+            notes.push_str(text.trim_end());
+        } else {
+            path.clear();
+        }
+    }
+
+    trim_notes(&mut checklist.items);
+    Ok(checklist)
+}
+
+/// Parses the Markdown checklist and applies it to the specified task list.
+pub async fn import<A: TasksApi + ?Sized>(
+    service: &A,
+    tasklist_id: &str,
+    input: &str,
+    mode: ImportMode,
+) -> Result<ImportSummary> {
+    let checklist = parse(input)?;
+    import_checklist(service, tasklist_id, &checklist, mode).await
+}
+
+/// Parses the Markdown checklist and creates a new task list with its items.
+/// The task list is titled after the first level-one heading of the document.
+pub async fn import_as_tasklist<A: TasksApi + ?Sized>(
+    service: &A,
+    input: &str,
+) -> Result<(Tasklist, ImportSummary)> {
+    let checklist = parse(input)?;
+    let tasklist = Tasklist {
+        title: Some(
+            checklist
+                .title
+                .clone()
+                .unwrap_or_else(|| DEFAULT_TITLE.to_owned()),
+        ),
+        ..Default::default()
+    };
+
+    let tasklist = service.insert_tasklist(tasklist).await?;
+    let tasklist_id = tasklist
+        .id
+        .clone()
+        .ok_or_else(|| ResponseError("created task list has no id".to_owned()))?;
+
+    let summary = import_checklist(service, &tasklist_id, &checklist, ImportMode::Create).await?;
+    Ok((tasklist, summary))
+}
+
+async fn import_checklist<A: TasksApi + ?Sized>(
+    service: &A,
+    tasklist_id: &str,
+    checklist: &Checklist,
+    mode: ImportMode,
+) -> Result<ImportSummary> {
+    let existing = match mode {
+        ImportMode::Create => Vec::new(),
+        ImportMode::Diff => {
+            let opts = TaskOptions {
+                max_results: Some(100),
+                show_completed: Some(true),
+                show_hidden: Some(true),
+                ..Default::default()
+            };
+            tasks::list_all(service, tasklist_id, opts).await?
+        }
+    };
+
+    let mut summary = ImportSummary::default();
+    let mut matched: HashSet<&str> = HashSet::new();
+
+    // each level holds the parent task id, the remaining items and the previous sibling id
+    let mut levels = vec![(None, checklist.items.iter(), None)];
+
+    while let Some((parent, items, previous)) = levels.last_mut() {
+        let item = match items.next() {
+            Some(item) => item,
+            None => {
+                levels.pop();
+                continue;
+            }
+        };
+
+        let found = existing.iter().find(|task| {
+            task.parent == *parent
+                && task.title.as_deref() == Some(item.title.as_str())
+                && task.id.as_deref().is_some_and(|id| !matched.contains(id))
+        });
+
+        let id = match found {
+            Some(task) => {
+                matched.extend(task.id.as_deref());
+                if item.checked && task.status != Some(TaskStatus::Completed) {
+                    let patch = Task {
+                        status: Some(TaskStatus::Completed),
+                        ..Default::default()
+                    };
+                    let task_id = task.id.as_deref().unwrap_or_default();
+                    let task = service.patch_task(tasklist_id, task_id, patch).await?;
+                    summary.completed.push(task);
+                }
+                task.id.clone()
+            }
+            None => {
+                let opts = TaskInsertOptions {
+                    parent: parent.clone(),
+                    previous: previous.clone(),
+                };
+                let task = service
+                    .insert_task(tasklist_id, to_task(item), Some(opts))
+                    .await?;
+                let id = task.id.clone();
+                summary.created.push(task);
+                id
+            }
+        };
+
+        *previous = id.clone();
+        if !item.children.is_empty() {
+            levels.push((id, item.children.iter(), None));
+        }
+    }
+
+    Ok(summary)
+}
+
+fn to_task(item: &Item) -> Task {
+    let status = match item.checked {
+        true => TaskStatus::Completed,
+        false => TaskStatus::NeedsAction,
+    };
+
+    Task {
+        title: Some(item.title.clone()),
+        notes: item.notes.clone(),
+        due: item.due,
+        status: Some(status),
+        ..Default::default()
+    }
+}
+
+fn write_items(
+    out: &mut String,
+    children: &HashMap<Option<&str>, Vec<&Task>>,
+    parent: Option<&str>,
+    depth: usize,
+) {
+    let indent = INDENT.repeat(depth);
+
+    for task in children.get(&parent).into_iter().flatten() {
+        let mark = match task.status {
+            Some(TaskStatus::Completed) => 'x',
+            _ => ' ',
+        };
+        let title = task.title.as_deref().unwrap_or_default().replace('\n', " ");
+
+        out.push_str(&format!("{}- [{}] {}", indent, mark, title));
+        if let Some(due) = task.due.as_ref() {
+            out.push_str(&format!("{}{})", DUE_PREFIX, due.format("%Y-%m-%d")));
+        }
+        out.push('\n');
+
+        for line in task.notes.iter().flat_map(|notes| notes.lines()) {
+            match line.is_empty() {
+                true => out.push('\n'),
+                false => out.push_str(&format!("{}{}{}\n", indent, INDENT, line)),
+            }
+        }
+
+        if task.id.is_some() {
+            write_items(out, children, task.id.as_deref(), depth + 1);
+        }
+    }
+}
+
+// Parses a checklist item, an invalid due date is kept as a part of the title.
+fn parse_item(line: &str) -> Option<Item> {
+    let rest = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))?;
+
+    let (checked, title) = match rest.get(..3) {
+        Some("[ ]") => (false, &rest[3..]),
+        Some("[x]") | Some("[X]") => (true, &rest[3..]),
+        _ => return None,
+    };
+
+    let mut title = title.trim();
+    let mut due = None;
+
+    if let Some(start) = title.rfind(DUE_PREFIX) {
+        let date = title[start + DUE_PREFIX.len()..]
+            .strip_suffix(')')
+            .and_then(|date| NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok());
+        if let Some(date) = date {
+            due = date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc());
+            title = title[..start].trim_end();
+        }
+    }
+
+    Some(Item {
+        title: title.to_owned(),
+        checked,
+        due,
+        ..Default::default()
+    })
+}
+
+fn item_mut<'a>(items: &'a mut [Item], path: &[(usize, usize)]) -> &'a mut Item {
+    let (first, rest) = path.split_first().expect("item path is not empty");
+    rest.iter()
+        .fold(&mut items[first.0], |item, (i, _)| &mut item.children[*i])
+}
+
+fn trim_notes(items: &mut [Item]) {
+    for item in items.iter_mut() {
+        item.notes = item
+            .notes
+            .take()
+            .map(|notes| notes.trim_end().to_owned())
+            .filter(|notes| !notes.is_empty());
+        trim_notes(&mut item.children);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, parent: Option<&str>, position: &str, title: &str) -> Task {
+        Task {
+            id: Some(id.to_owned()),
+            parent: parent.map(str::to_owned),
+            position: Some(position.to_owned()),
+            title: Some(title.to_owned()),
+            status: Some(TaskStatus::NeedsAction),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn export_orders_and_nests_tasks() {
+        let mut milk = task("1", None, "002", "Milk");
+        milk.notes = Some("2 bottles\n\nlow fat".to_owned());
+        milk.due = Some("2024-03-01T00:00:00Z".parse().unwrap());
+        let mut fridge = task("3", Some("1"), "001", "Check the fridge");
+        fridge.status = Some(TaskStatus::Completed);
+
+        let tasks = Tasks {
+            kind: "tasks#tasks".to_owned(),
+            etag: "etag".to_owned(),
+            next_page_token: None,
+            items: Some(vec![milk, task("2", None, "001", "Bread"), fridge]),
+        };
+        let tasklist = Tasklist {
+            title: Some("Groceries".to_owned()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            export(&tasklist, &tasks),
+            "# Groceries\n\n\
+             - [ ] Bread\n\
+             - [ ] Milk (due: 2024-03-01)\n  2 bottles\n\n  low fat\n  \
+             - [x] Check the fridge\n"
+        );
+    }
+
+    #[test]
+    fn parse_nested_checklist() {
+        let input = "# Groceries\n\nSome intro.\n\n\
+                     - [ ] Milk (due: 2024-03-01)\n  2 bottles\n\n  low fat\n  \
+                     - [x] Check the fridge\n    - [ ] Open it\n\
+                     * [X] Bread\n\
+                     - not an item\n";
+
+        let checklist = parse(input).unwrap();
+        assert_eq!(checklist.title.as_deref(), Some("Groceries"));
+        assert_eq!(checklist.items.len(), 2);
+
+        let milk = &checklist.items[0];
+        assert_eq!(milk.title, "Milk");
+        assert!(!milk.checked);
+        assert_eq!(milk.due, Some("2024-03-01T00:00:00Z".parse().unwrap()));
+        assert_eq!(milk.notes.as_deref(), Some("2 bottles\n\nlow fat"));
+        assert_eq!(milk.children[0].title, "Check the fridge");
+        assert!(milk.children[0].checked);
+        assert_eq!(milk.children[0].children[0].title, "Open it");
+
+        assert_eq!(checklist.items[1].title, "Bread");
+        assert!(checklist.items[1].checked);
+        assert_eq!(checklist.items[1].notes, None);
+    }
+
+    #[test]
+    fn parse_non_ascii_indentation() {
+        let checklist = parse("- [ ] Milk\n\u{3000}\u{3000}note\n- [ ] Bread\n").unwrap();
+        assert_eq!(checklist.items.len(), 2);
+        assert_eq!(checklist.items[0].notes, None);
+
+        let checklist = parse("- [ ] Milk\n   \u{3000}note\n").unwrap();
+        assert_eq!(checklist.items[0].notes.as_deref(), Some(" \u{3000}note"));
+    }
+
+    #[test]
+    fn parse_keeps_invalid_due_dates_in_the_title() {
+        let checklist = parse(
+            "- [ ] Milk (due: tomorrow)
+- [ ] Bread (due: 2024-03-01
+",
+        )
+        .unwrap();
+        assert_eq!(checklist.items[0].title, "Milk (due: tomorrow)");
+        assert_eq!(checklist.items[0].due, None);
+        assert_eq!(checklist.items[1].title, "Bread (due: 2024-03-01");
+    }
+
+    const GROCERIES: &str = "# Groceries\n\n\
+                             - [ ] Milk\n  - [x] Check the fridge\n  - [ ] Open it\n\
+                             - [x] Bread\n";
+
+    // Returns the titles of the task list in position order, indented by depth.
+    async fn titles(fake: &crate::FakeTasks, tasklist_id: &str) -> Vec<String> {
+        let opts = TaskOptions {
+            show_completed: Some(true),
+            show_hidden: Some(true),
+            ..Default::default()
+        };
+        let items = tasks::list_all(fake, tasklist_id, opts).await.unwrap();
+        let tasks = Tasks {
+            kind: "tasks#tasks".to_owned(),
+            etag: String::new(),
+            next_page_token: None,
+            items: Some(items),
+        };
+        let markdown = export(&Tasklist::default(), &tasks);
+        markdown.lines().map(str::to_owned).collect()
+    }
+
+    #[tokio::test]
+    async fn import_creates_every_item() {
+        let fake = crate::FakeTasks::new();
+        let summary = import(&fake, "@default", GROCERIES, ImportMode::Create)
+            .await
+            .unwrap();
+        assert_eq!(summary.created.len(), 4);
+        assert!(summary.completed.is_empty());
+
+        // an item is created even if a task with the same title exists, at the top of the list
+        import(&fake, "@default", "- [ ] Milk\n", ImportMode::Create)
+            .await
+            .unwrap();
+        assert_eq!(
+            titles(&fake, "@default").await,
+            [
+                "- [ ] Milk",
+                "- [ ] Milk",
+                "  - [x] Check the fridge",
+                "  - [ ] Open it",
+                "- [x] Bread",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn import_diff_creates_missing_items_and_completes_checked_ones() {
+        let fake = crate::FakeTasks::new();
+        import(
+            &fake,
+            "@default",
+            "- [ ] Milk\n  - [ ] Check the fridge\n- [ ] Bread\n",
+            ImportMode::Create,
+        )
+        .await
+        .unwrap();
+
+        let summary = import(&fake, "@default", GROCERIES, ImportMode::Diff)
+            .await
+            .unwrap();
+        let created: Vec<_> = summary.created.iter().map(|t| t.title.as_deref()).collect();
+        assert_eq!(created, [Some("Open it")]);
+        let completed: Vec<_> = summary
+            .completed
+            .iter()
+            .map(|t| t.title.as_deref())
+            .collect();
+        assert_eq!(completed, [Some("Check the fridge"), Some("Bread")]);
+
+        assert_eq!(
+            titles(&fake, "@default").await,
+            [
+                "- [ ] Milk",
+                "  - [x] Check the fridge",
+                "  - [ ] Open it",
+                "- [x] Bread",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn import_as_tasklist_titles_the_list_after_the_heading() {
+        let fake = crate::FakeTasks::new();
+        let (tasklist, summary) = import_as_tasklist(&fake, GROCERIES).await.unwrap();
+        assert_eq!(tasklist.title.as_deref(), Some("Groceries"));
+        assert_eq!(summary.created.len(), 4);
+
+        let id = tasklist.id.unwrap();
+        assert_eq!(titles(&fake, &id).await.len(), 4);
+
+        let (tasklist, _) = import_as_tasklist(&fake, "- [ ] Milk\n").await.unwrap();
+        assert_eq!(tasklist.title.as_deref(), Some(DEFAULT_TITLE));
+    }
+}
@@ -0,0 +1,999 @@
+//! Three-way merge of account snapshots into a plan of API calls.
+//!
+//! [`merge`] combines the changes of a `local` snapshot and of the `remote` account since
+//! their common `base` into a [`MergePlan`], the calls which bring the remote account to the
+//! merged state. A task changed on both sides is merged field by field. A field changed on
+//! both sides to different values, or a task deleted on one side and changed on the other,
+//! is a [`Conflict`], resolved by a [`ConflictPolicy`]. [`apply`] runs the plan.
+//!
+//! ```no_run
+//! # async fn example(service: &gtasks::Service, base: gtasks::snapshot::AccountSnapshot,
+//! #     local: gtasks::snapshot::AccountSnapshot) -> gtasks::Result<()> {
+//! use gtasks::merge::{self, NewestWins};
+//!
+//! let remote = service.snapshot(None).await?;
+//! let plan = merge::merge(&base, &local, &remote, &NewestWins);
+//! merge::apply(service, &plan).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::backup::IdMapping;
+use crate::diff::{changed_fields, tasklists_by_id, tasks_by_id, Field};
+use crate::errors::{Result, TasksError::ResponseError};
+use crate::snapshot::AccountSnapshot;
+use crate::{Task, TaskInsertOptions, Tasklist, TasksApi};
+
+/// Changes of both sides which cannot be merged. A side without the task or the
+/// task list deleted it.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum Conflict {
+    Task {
+        tasklist_id: String,
+        task_id: String,
+        base: Option<Box<Task>>,
+        local: Option<Box<Task>>,
+        remote: Option<Box<Task>>,
+
+        /// Fields changed on both sides, or changed on the side which kept the task.
+        fields: Vec<Field>,
+    },
+    Tasklist {
+        tasklist_id: String,
+        base: Option<Box<Tasklist>>,
+        local: Option<Box<Tasklist>>,
+        remote: Option<Box<Tasklist>>,
+    },
+}
+
+/// Side whose changes are kept.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Resolution {
+    Local,
+    Remote,
+}
+
+/// Decides which side of a conflict is kept.
+/// Implemented by closures taking the conflict and by [`PreferLocal`], [`PreferRemote`] and [`NewestWins`].
+pub trait ConflictPolicy {
+    fn resolve(&self, conflict: &Conflict) -> Resolution;
+}
+
+impl<F> ConflictPolicy for F
+where
+    F: Fn(&Conflict) -> Resolution,
+{
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        self(conflict)
+    }
+}
+
+/// Keeps the local changes.
+pub struct PreferLocal;
+
+impl ConflictPolicy for PreferLocal {
+    fn resolve(&self, _: &Conflict) -> Resolution {
+        Resolution::Local
+    }
+}
+
+/// Keeps the remote changes.
+pub struct PreferRemote;
+
+impl ConflictPolicy for PreferRemote {
+    fn resolve(&self, _: &Conflict) -> Resolution {
+        Resolution::Remote
+    }
+}
+
+/// Keeps the side modified last, by the `updated` time. A modification wins over a deletion,
+/// whose time is unknown, and the remote side wins a tie.
+pub struct NewestWins;
+
+impl ConflictPolicy for NewestWins {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        let (local, remote) = match conflict {
+            Conflict::Task { local, remote, .. } => (
+                local.as_ref().map(|t| t.updated),
+                remote.as_ref().map(|t| t.updated),
+            ),
+            Conflict::Tasklist { local, remote, .. } => (
+                local.as_ref().map(|t| t.updated),
+                remote.as_ref().map(|t| t.updated),
+            ),
+        };
+        match (local, remote) {
+            (Some(_), None) => Resolution::Local,
+            (Some(local), Some(remote)) if local > remote => Resolution::Local,
+            _ => Resolution::Remote,
+        }
+    }
+}
+
+/// Call of the API. The ids are those of the local snapshot; the ids of the task lists
+/// and tasks created by the plan are mapped to the created ones by [`apply`].
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum Operation {
+    InsertTasklist {
+        tasklist_id: String,
+        tasklist: Tasklist,
+    },
+    PatchTasklist {
+        tasklist_id: String,
+        tasklist: Tasklist,
+    },
+    DeleteTasklist {
+        tasklist_id: String,
+    },
+    InsertTask {
+        tasklist_id: String,
+        task_id: String,
+        task: Task,
+        parent: Option<String>,
+        previous: Option<String>,
+    },
+    UpdateTask {
+        tasklist_id: String,
+        task: Task,
+    },
+    MoveTask {
+        tasklist_id: String,
+        task_id: String,
+        parent: Option<String>,
+        previous: Option<String>,
+    },
+    DeleteTask {
+        tasklist_id: String,
+        task_id: String,
+    },
+}
+
+/// Calls bringing the remote account to the merged state, in the order to run them.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct MergePlan {
+    pub operations: Vec<Operation>,
+
+    /// Conflicts met by the merge and how they were resolved.
+    pub conflicts: Vec<(Conflict, Resolution)>,
+}
+
+// What the merge does with the tasks of a task list.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ListPlan {
+    // merge the tasks of both sides
+    Merge,
+    // the list is created, with all the local tasks
+    Create,
+    // the list is deleted or kept as it is on the remote side
+    Skip,
+}
+
+// What the merge does with a local task.
+#[derive(Default)]
+struct TaskPlan {
+    insert: bool,
+    update: Option<Task>,
+    moved: bool,
+}
+
+/// Merges the local and remote changes since the base into a plan for the remote account.
+pub fn merge<'a>(
+    base: &'a AccountSnapshot,
+    local: &'a AccountSnapshot,
+    remote: &'a AccountSnapshot,
+    policy: &dyn ConflictPolicy,
+) -> MergePlan {
+    let base_lists = tasklists_by_id(base);
+    let local_lists = tasklists_by_id(local);
+    let remote_lists = tasklists_by_id(remote);
+    let base_tasks = tasks_by_id(base);
+    let local_tasks = tasks_by_id(local);
+    let remote_tasks = tasks_by_id(remote);
+
+    let mut plan = MergePlan::default();
+    let mut resolve = |conflict: Conflict| {
+        let resolution = policy.resolve(&conflict);
+        plan.conflicts.push((conflict, resolution));
+        resolution
+    };
+
+    // whether the remote side changed any task of the list since the base
+    let remote_changed_tasks = |tasklist_id: &str| {
+        let changed = remote_tasks.iter().any(|(key, task)| {
+            key.0 == tasklist_id
+                && base_tasks
+                    .get(key)
+                    .is_none_or(|base| !changed_fields(base, task).is_empty())
+        });
+        let removed = base_tasks
+            .keys()
+            .any(|key| key.0 == tasklist_id && !remote_tasks.contains(key));
+        changed || removed
+    };
+    // whether the local side changed any task of the list since the base
+    let local_changed_tasks = |tasklist_id: &str| {
+        let changed = local_tasks.iter().any(|(key, task)| {
+            key.0 == tasklist_id
+                && base_tasks
+                    .get(key)
+                    .is_none_or(|base| !changed_fields(base, task).is_empty())
+        });
+        let removed = base_tasks
+            .keys()
+            .any(|key| key.0 == tasklist_id && !local_tasks.contains(key));
+        changed || removed
+    };
+
+    let mut list_ops = Vec::new();
+    let mut list_deletes = Vec::new();
+    let mut lists: HashMap<&str, ListPlan> = HashMap::new();
+
+    for (id, l) in local_lists.iter() {
+        let insert = Operation::InsertTasklist {
+            tasklist_id: id.to_string(),
+            tasklist: Tasklist {
+                title: l.title.clone(),
+                ..Default::default()
+            },
+        };
+        let patch = Operation::PatchTasklist {
+            tasklist_id: id.to_string(),
+            tasklist: Tasklist {
+                title: l.title.clone(),
+                ..Default::default()
+            },
+        };
+        let conflict = || Conflict::Tasklist {
+            tasklist_id: id.to_string(),
+            base: base_lists.get(id).map(|t| Box::new((*t).clone())),
+            local: Some(Box::new((*l).clone())),
+            remote: remote_lists.get(id).map(|t| Box::new((*t).clone())),
+        };
+
+        let list_plan = match (base_lists.get(id), remote_lists.get(id)) {
+            (None, None) => {
+                list_ops.push(insert);
+                ListPlan::Create
+            }
+            (Some(b), None) if b.title == l.title && !local_changed_tasks(id) => ListPlan::Skip,
+            (Some(_), None) => match resolve(conflict()) {
+                Resolution::Local => {
+                    list_ops.push(insert);
+                    ListPlan::Create
+                }
+                Resolution::Remote => ListPlan::Skip,
+            },
+            (b, Some(r)) => {
+                let local_changed = b.is_none_or(|b| b.title != l.title);
+                let remote_changed = b.is_none_or(|b| b.title != r.title);
+                if local_changed
+                    && r.title != l.title
+                    && (!remote_changed || resolve(conflict()) == Resolution::Local)
+                {
+                    list_ops.push(patch);
+                }
+                ListPlan::Merge
+            }
+        };
+        lists.insert(id, list_plan);
+    }
+
+    for (id, b) in base_lists.iter() {
+        if local_lists.contains(id) {
+            continue;
+        }
+        lists.insert(id, ListPlan::Skip);
+        let r = match remote_lists.get(id) {
+            Some(r) => r,
+            None => continue,
+        };
+        let remote_changed = r.title != b.title || remote_changed_tasks(id);
+        let conflict = || Conflict::Tasklist {
+            tasklist_id: id.to_string(),
+            base: Some(Box::new((*b).clone())),
+            local: None,
+            remote: Some(Box::new((*r).clone())),
+        };
+        if !remote_changed || resolve(conflict()) == Resolution::Local {
+            list_deletes.push(Operation::DeleteTasklist {
+                tasklist_id: id.to_string(),
+            });
+        }
+    }
+
+    // decisions for the tasks, then the calls in the order of the local task trees
+    let mut tasks: HashMap<(&str, &str), TaskPlan> = HashMap::new();
+    let mut deletes: Vec<(&str, &str)> = Vec::new();
+
+    for (key, l) in local_tasks.iter() {
+        let task_plan = match lists.get(key.0) {
+            Some(ListPlan::Create) => TaskPlan {
+                insert: true,
+                ..Default::default()
+            },
+            Some(ListPlan::Merge) => {
+                let b = base_tasks.get(key).copied();
+                let r = remote_tasks.get(key).copied();
+                let conflict = |fields| Conflict::Task {
+                    tasklist_id: key.0.to_owned(),
+                    task_id: key.1.to_owned(),
+                    base: b.cloned().map(Box::new),
+                    local: Some(Box::new((*l).clone())),
+                    remote: r.cloned().map(Box::new),
+                    fields,
+                };
+                match (b, r) {
+                    (None, None) => TaskPlan {
+                        insert: true,
+                        ..Default::default()
+                    },
+                    (Some(b), None) => {
+                        let fields = changed_fields(b, l);
+                        if fields.is_empty() || resolve(conflict(fields)) == Resolution::Remote {
+                            continue;
+                        }
+                        TaskPlan {
+                            insert: true,
+                            ..Default::default()
+                        }
+                    }
+                    (b, Some(r)) => {
+                        let mut taken = Vec::new();
+                        let mut conflicting = Vec::new();
+                        for field in Field::ALL {
+                            if field.same(l, r) {
+                                continue;
+                            }
+                            let local_changed = b.is_none_or(|b| !field.same(b, l));
+                            let remote_changed = b.is_none_or(|b| !field.same(b, r));
+                            match (local_changed, remote_changed) {
+                                (true, false) => taken.push(field),
+                                (true, true) => conflicting.push(field),
+                                _ => {}
+                            }
+                        }
+                        if !conflicting.is_empty()
+                            && resolve(conflict(conflicting.clone())) == Resolution::Local
+                        {
+                            taken.extend(conflicting);
+                        }
+
+                        let mut merged = r.clone();
+                        for field in taken.iter() {
+                            field.copy(l, &mut merged);
+                        }
+                        TaskPlan {
+                            insert: false,
+                            update: taken.iter().any(|f| !f.is_structural()).then_some(merged),
+                            moved: taken.iter().any(|f| f.is_structural()),
+                        }
+                    }
+                }
+            }
+            _ => continue,
+        };
+        tasks.insert(*key, task_plan);
+    }
+
+    for (key, b) in base_tasks.iter() {
+        if lists.get(key.0) != Some(&ListPlan::Merge) || local_tasks.contains(key) {
+            continue;
+        }
+        let r = match remote_tasks.get(key) {
+            Some(r) => r,
+            None => continue,
+        };
+        let fields = changed_fields(b, r);
+        let conflict = Conflict::Task {
+            tasklist_id: key.0.to_owned(),
+            task_id: key.1.to_owned(),
+            base: Some(Box::new((*b).clone())),
+            local: None,
+            remote: Some(Box::new((*r).clone())),
+            fields: fields.clone(),
+        };
+        if fields.is_empty() || resolve(conflict) == Resolution::Local {
+            deletes.push(*key);
+        }
+    }
+
+    // tasks which exist on the remote side once the plan ran
+    let deleted: HashSet<(&str, &str)> = deletes.iter().copied().collect();
+    let exists = |key: &(&str, &str)| {
+        tasks.get(key).is_some_and(|t| t.insert)
+            || (remote_tasks.contains(key) && !deleted.contains(key))
+    };
+
+    let mut task_ops = Vec::new();
+    for list in local.tasklists.iter() {
+        let tasklist_id = match list.tasklist.id.as_deref() {
+            Some(id) => id,
+            None => continue,
+        };
+        for (task, parent, previous) in tree_order(&list.tasks) {
+            let id = task.id.as_deref().unwrap_or_default();
+            let task_plan = match tasks.get(&(tasklist_id, id)) {
+                Some(task_plan) => task_plan,
+                None => continue,
+            };
+
+            // the task is placed after the closest previous sibling which exists
+            let parent = parent.filter(|p| exists(&(tasklist_id, p)));
+            let previous = previous
+                .into_iter()
+                .find(|p| exists(&(tasklist_id, p)))
+                .map(str::to_owned);
+
+            if task_plan.insert {
+                task_ops.push(Operation::InsertTask {
+                    tasklist_id: tasklist_id.to_owned(),
+                    task_id: id.to_owned(),
+                    task: Task {
+                        title: task.title.clone(),
+                        notes: task.notes.clone(),
+                        status: task.status,
+                        due: task.due,
+                        completed: task.completed,
+                        links: task.links.clone(),
+                        ..Default::default()
+                    },
+                    parent: parent.map(str::to_owned),
+                    previous,
+                });
+                continue;
+            }
+            if let Some(merged) = task_plan.update.clone() {
+                task_ops.push(Operation::UpdateTask {
+                    tasklist_id: tasklist_id.to_owned(),
+                    task: merged,
+                });
+            }
+            if task_plan.moved {
+                task_ops.push(Operation::MoveTask {
+                    tasklist_id: tasklist_id.to_owned(),
+                    task_id: id.to_owned(),
+                    parent: parent.map(str::to_owned),
+                    previous,
+                });
+            }
+        }
+    }
+
+    // deleting a task deletes its subtasks
+    let remote_parent = |key: &(&'a str, &'a str)| -> Option<(&'a str, &'a str)> {
+        let parent = remote_tasks.get(key)?.parent.as_deref()?;
+        Some((key.0, parent))
+    };
+    let delete_ops = deletes.iter().filter_map(|key| {
+        let ancestor_deleted = std::iter::successors(remote_parent(key), remote_parent)
+            .take(remote_tasks.keys().count())
+            .any(|ancestor| deleted.contains(&ancestor));
+        (!ancestor_deleted).then(|| Operation::DeleteTask {
+            tasklist_id: key.0.to_owned(),
+            task_id: key.1.to_owned(),
+        })
+    });
+
+    plan.operations = list_ops
+        .into_iter()
+        .chain(task_ops)
+        .chain(delete_ops.collect::<Vec<_>>())
+        .chain(list_deletes)
+        .collect();
+    plan
+}
+
+// Returns the tasks depth-first, parents before their subtasks and siblings by position,
+// with the parent id and the ids of the previous siblings, closest first.
+fn tree_order(tasks: &[Task]) -> Vec<(&Task, Option<&str>, Vec<&str>)> {
+    let tasks: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| t.deleted != Some(true) && t.id.is_some())
+        .collect();
+    let ids: HashSet<&str> = tasks.iter().filter_map(|t| t.id.as_deref()).collect();
+
+    let mut children: HashMap<Option<&str>, Vec<&Task>> = HashMap::new();
+    for task in tasks.iter() {
+        let parent = task.parent.as_deref().filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(task);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.position.cmp(&b.position));
+    }
+
+    let mut ordered = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(None, 0)];
+    while let Some((parent, i)) = stack.pop() {
+        let siblings = match children.get(&parent) {
+            Some(siblings) if i < siblings.len() => siblings,
+            _ => continue,
+        };
+        let task = siblings[i];
+        stack.push((parent, i + 1));
+
+        let id = task.id.as_deref().unwrap_or_default();
+        if !visited.insert(id) {
+            continue;
+        }
+        let previous = siblings[..i]
+            .iter()
+            .rev()
+            .filter_map(|t| t.id.as_deref())
+            .collect();
+        ordered.push((task, parent, previous));
+        stack.push((Some(id), 0));
+    }
+    ordered
+}
+
+/// Runs the calls of the plan, returning the ids of the created task lists and tasks
+/// by their local ids.
+pub async fn apply<A: TasksApi + ?Sized>(service: &A, plan: &MergePlan) -> Result<IdMapping> {
+    let mut mapping = IdMapping::default();
+    let mapped = |ids: &HashMap<String, String>, id: &str| -> String {
+        ids.get(id).cloned().unwrap_or_else(|| id.to_owned())
+    };
+
+    for op in plan.operations.iter() {
+        match op {
+            Operation::InsertTasklist {
+                tasklist_id,
+                tasklist,
+            } => {
+                let created = service.insert_tasklist(tasklist.clone()).await?;
+                let id = created
+                    .id
+                    .ok_or_else(|| ResponseError("created task list has no id".to_owned()))?;
+                mapping.tasklists.insert(tasklist_id.clone(), id);
+            }
+            Operation::PatchTasklist {
+                tasklist_id,
+                tasklist,
+            } => {
+                let id = mapped(&mapping.tasklists, tasklist_id);
+                service.patch_tasklist(&id, tasklist.clone()).await?;
+            }
+            Operation::DeleteTasklist { tasklist_id } => {
+                let id = mapped(&mapping.tasklists, tasklist_id);
+                service.delete_tasklist(&id).await?;
+            }
+            Operation::InsertTask {
+                tasklist_id,
+                task_id,
+                task,
+                parent,
+                previous,
+            } => {
+                let opts = TaskInsertOptions {
+                    parent: parent.as_deref().map(|p| mapped(&mapping.tasks, p)),
+                    previous: previous.as_deref().map(|p| mapped(&mapping.tasks, p)),
+                };
+                let list = mapped(&mapping.tasklists, tasklist_id);
+                let created = service.insert_task(&list, task.clone(), Some(opts)).await?;
+                let id = created
+                    .id
+                    .ok_or_else(|| ResponseError("created task has no id".to_owned()))?;
+                mapping.tasks.insert(task_id.clone(), id);
+            }
+            Operation::UpdateTask { tasklist_id, task } => {
+                let list = mapped(&mapping.tasklists, tasklist_id);
+                service.update_task(&list, task.clone()).await?;
+            }
+            Operation::MoveTask {
+                tasklist_id,
+                task_id,
+                parent,
+                previous,
+            } => {
+                let opts = TaskInsertOptions {
+                    parent: parent.as_deref().map(|p| mapped(&mapping.tasks, p)),
+                    previous: previous.as_deref().map(|p| mapped(&mapping.tasks, p)),
+                };
+                let list = mapped(&mapping.tasklists, tasklist_id);
+                service.move_task(&list, task_id, opts).await?;
+            }
+            Operation::DeleteTask {
+                tasklist_id,
+                task_id,
+            } => {
+                let list = mapped(&mapping.tasklists, tasklist_id);
+                service.delete_task(&list, task_id).await?;
+            }
+        }
+    }
+
+    Ok(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{self, SnapshotOptions, TasklistSnapshot};
+    use crate::{FakeTasks, TaskStatus};
+
+    fn task(title: &str) -> Task {
+        Task {
+            title: Some(title.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn find<'a>(snapshot: &'a mut AccountSnapshot, title: &str) -> &'a mut Task {
+        snapshot.tasklists[0]
+            .tasks
+            .iter_mut()
+            .find(|t| t.title.as_deref() == Some(title))
+            .unwrap()
+    }
+
+    async fn titles(fake: &FakeTasks) -> Vec<(String, Option<String>)> {
+        let snapshot = snapshot::snapshot(fake, SnapshotOptions::default())
+            .await
+            .unwrap();
+        let tasks = &snapshot.tasklists[0].tasks;
+        let title = |id: &Option<String>| {
+            tasks
+                .iter()
+                .find(|t| t.id == *id)
+                .and_then(|t| t.title.clone())
+        };
+        tasks
+            .iter()
+            .map(|t| {
+                (
+                    t.title.clone().unwrap(),
+                    t.parent.as_ref().and(title(&t.parent)),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn merges_both_sides() {
+        let fake = FakeTasks::new();
+        let mut previous = None;
+        for title in ["a", "b", "c", "d"] {
+            let opts = TaskInsertOptions {
+                parent: None,
+                previous: previous.take(),
+            };
+            let created = fake.insert_task("@default", task(title), Some(opts));
+            previous = created.await.unwrap().id;
+        }
+        let base = snapshot::snapshot(&fake, SnapshotOptions::default())
+            .await
+            .unwrap();
+
+        // local: completes a, deletes b, renames c, adds e under a
+        let mut local = base.clone();
+        find(&mut local, "a").status = Some(TaskStatus::Completed);
+        local.tasklists[0]
+            .tasks
+            .retain(|t| t.title.as_deref() != Some("b"));
+        find(&mut local, "c").title = Some("c local".to_owned());
+        let a_id = find(&mut local, "a").id.clone();
+        local.tasklists[0].tasks.push(Task {
+            id: Some("local-e".to_owned()),
+            parent: a_id,
+            position: Some("0".to_owned()),
+            ..task("e")
+        });
+
+        // remote: adds notes to a, renames c, deletes d
+        let ids: HashMap<String, String> = base.tasklists[0]
+            .tasks
+            .iter()
+            .map(|t| (t.title.clone().unwrap(), t.id.clone().unwrap()))
+            .collect();
+        let notes = Task {
+            notes: Some("remote notes".to_owned()),
+            ..Default::default()
+        };
+        fake.patch_task("@default", &ids["a"], notes).await.unwrap();
+        fake.patch_task("@default", &ids["c"], task("c remote"))
+            .await
+            .unwrap();
+        fake.delete_task("@default", &ids["d"]).await.unwrap();
+        let remote = snapshot::snapshot(&fake, SnapshotOptions::default())
+            .await
+            .unwrap();
+
+        let kept = merge(&base, &local, &remote, &PreferRemote);
+        assert!(kept.operations.iter().all(|op| match op {
+            Operation::UpdateTask { task, .. } => task.title.as_deref() != Some("c local"),
+            _ => true,
+        }));
+
+        let plan = merge(&base, &local, &remote, &PreferLocal);
+        assert_eq!(plan.conflicts.len(), 1);
+        assert!(matches!(
+            &plan.conflicts[0].0,
+            Conflict::Task { fields, .. } if fields == &[Field::Title]
+        ));
+
+        let mapping = apply(&fake, &plan).await.unwrap();
+        assert!(mapping.tasks.contains_key("local-e"));
+        assert_eq!(
+            titles(&fake).await,
+            [
+                ("a".to_owned(), None),
+                ("e".to_owned(), Some("a".to_owned())),
+                ("c local".to_owned(), None),
+            ]
+        );
+        let merged = fake
+            .get_task("@default", &ids["a"], None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(merged.notes.as_deref(), Some("remote notes"));
+        assert_eq!(merged.status, Some(TaskStatus::Completed));
+    }
+
+    fn item(id: &str, title: &str, updated: i64) -> Task {
+        Task {
+            id: Some(id.to_owned()),
+            title: Some(title.to_owned()),
+            position: Some(format!("{:020}", 0)),
+            updated: chrono::DateTime::from_timestamp(updated, 0),
+            ..Default::default()
+        }
+    }
+
+    fn list(id: &str, title: &str, tasks: Vec<Task>) -> TasklistSnapshot {
+        TasklistSnapshot {
+            tasklist: Tasklist {
+                id: Some(id.to_owned()),
+                title: Some(title.to_owned()),
+                ..Default::default()
+            },
+            tasks,
+        }
+    }
+
+    fn account(tasklists: Vec<TasklistSnapshot>) -> AccountSnapshot {
+        AccountSnapshot {
+            taken_at: chrono::Utc::now(),
+            tasklists,
+        }
+    }
+
+    // Summarizes the operations of the plan, e.g. "patch_list l1 A".
+    fn ops(plan: &MergePlan) -> Vec<String> {
+        plan.operations
+            .iter()
+            .map(|op| match op {
+                Operation::InsertTasklist {
+                    tasklist_id,
+                    tasklist,
+                } => format!(
+                    "insert_list {} {}",
+                    tasklist_id,
+                    tasklist.title.as_deref().unwrap_or_default()
+                ),
+                Operation::PatchTasklist {
+                    tasklist_id,
+                    tasklist,
+                } => format!(
+                    "patch_list {} {}",
+                    tasklist_id,
+                    tasklist.title.as_deref().unwrap_or_default()
+                ),
+                Operation::DeleteTasklist { tasklist_id } => {
+                    format!("delete_list {}", tasklist_id)
+                }
+                Operation::InsertTask { task_id, .. } => format!("insert {}", task_id),
+                Operation::UpdateTask { task, .. } => format!(
+                    "update {} {}",
+                    task.id.as_deref().unwrap_or_default(),
+                    task.title.as_deref().unwrap_or_default()
+                ),
+                Operation::MoveTask { task_id, .. } => format!("move {}", task_id),
+                Operation::DeleteTask { task_id, .. } => format!("delete {}", task_id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_tasklist_changes() {
+        let base = account(vec![
+            list("renamed", "Renamed", vec![]),
+            list("both", "Both", vec![]),
+            list("gone", "Gone", vec![item("g", "g", 0)]),
+            list("kept", "Kept", vec![item("k", "k", 0)]),
+            list("remote_gone", "Remote gone", vec![item("r", "r", 0)]),
+            list("edited", "Edited", vec![item("e", "e", 0)]),
+        ]);
+        let local = account(vec![
+            list("renamed", "Renamed locally", vec![]),
+            list("both", "Both locally", vec![]),
+            list(
+                "remote_gone",
+                "Remote gone locally",
+                vec![item("r", "r", 0)],
+            ),
+            list("edited", "Edited", vec![item("e", "e locally", 1)]),
+            list("new", "New", vec![item("n", "n", 0)]),
+        ]);
+        let remote = account(vec![
+            list("renamed", "Renamed", vec![]),
+            list("both", "Both remotely", vec![]),
+            list("gone", "Gone", vec![item("g", "g", 0)]),
+            list("kept", "Kept", vec![item("k", "k remotely", 1)]),
+        ]);
+
+        let plan = merge(&base, &local, &remote, &PreferLocal);
+        assert_eq!(
+            ops(&plan),
+            [
+                "patch_list renamed Renamed locally",
+                "patch_list both Both locally",
+                "insert_list remote_gone Remote gone locally",
+                "insert_list edited Edited",
+                "insert_list new New",
+                "insert r",
+                "insert e",
+                "insert n",
+                "delete_list gone",
+                "delete_list kept",
+            ]
+        );
+        let conflicts: Vec<_> = plan
+            .conflicts
+            .iter()
+            .map(|(conflict, _)| match conflict {
+                Conflict::Tasklist { tasklist_id, .. } => tasklist_id.as_str(),
+                Conflict::Task { .. } => "task",
+            })
+            .collect();
+        assert_eq!(conflicts, ["both", "remote_gone", "edited", "kept"]);
+
+        let plan = merge(&base, &local, &remote, &PreferRemote);
+        assert_eq!(
+            ops(&plan),
+            [
+                "patch_list renamed Renamed locally",
+                "insert_list new New",
+                "insert n",
+                "delete_list gone",
+            ]
+        );
+        assert!(plan
+            .conflicts
+            .iter()
+            .all(|(_, resolution)| *resolution == Resolution::Remote));
+    }
+
+    #[test]
+    fn newest_wins() {
+        let conflict = |local: Option<i64>, remote: Option<i64>| Conflict::Task {
+            tasklist_id: "l".to_owned(),
+            task_id: "t".to_owned(),
+            base: None,
+            local: local.map(|t| Box::new(item("t", "local", t))),
+            remote: remote.map(|t| Box::new(item("t", "remote", t))),
+            fields: vec![Field::Title],
+        };
+        assert_eq!(
+            NewestWins.resolve(&conflict(Some(2), Some(1))),
+            Resolution::Local
+        );
+        assert_eq!(
+            NewestWins.resolve(&conflict(Some(1), Some(2))),
+            Resolution::Remote
+        );
+        assert_eq!(
+            NewestWins.resolve(&conflict(Some(1), Some(1))),
+            Resolution::Remote
+        );
+        assert_eq!(
+            NewestWins.resolve(&conflict(Some(1), None)),
+            Resolution::Local
+        );
+        assert_eq!(
+            NewestWins.resolve(&conflict(None, Some(1))),
+            Resolution::Remote
+        );
+
+        let base = account(vec![list(
+            "l",
+            "L",
+            vec![item("a", "a", 0), item("b", "b", 0)],
+        )]);
+        let local = account(vec![list(
+            "l",
+            "L",
+            vec![item("a", "a local", 2), item("b", "b local", 1)],
+        )]);
+        let remote = account(vec![list(
+            "l",
+            "L",
+            vec![item("a", "a remote", 1), item("b", "b remote", 2)],
+        )]);
+        let plan = merge(&base, &local, &remote, &NewestWins);
+        assert_eq!(ops(&plan), ["update a a local"]);
+        assert_eq!(plan.conflicts.len(), 2);
+    }
+
+    #[test]
+    fn merges_deletions_against_modifications() {
+        let base = account(vec![list(
+            "l",
+            "L",
+            vec![
+                item("local_deleted", "x", 0),
+                item("remote_deleted", "y", 0),
+            ],
+        )]);
+        // each side deletes the task the other side modifies
+        let local = account(vec![list(
+            "l",
+            "L",
+            vec![item("remote_deleted", "y local", 1)],
+        )]);
+        let remote = account(vec![list(
+            "l",
+            "L",
+            vec![item("local_deleted", "x remote", 1)],
+        )]);
+
+        let plan = merge(&base, &local, &remote, &PreferLocal);
+        assert_eq!(
+            ops(&plan),
+            ["insert remote_deleted", "delete local_deleted"]
+        );
+        for (conflict, _) in plan.conflicts.iter() {
+            match conflict {
+                Conflict::Task {
+                    task_id,
+                    local,
+                    remote,
+                    fields,
+                    ..
+                } => {
+                    assert_eq!(local.is_none(), task_id == "local_deleted");
+                    assert_eq!(remote.is_none(), task_id == "remote_deleted");
+                    assert_eq!(fields, &[Field::Title]);
+                }
+                Conflict::Tasklist { .. } => panic!("unexpected task list conflict"),
+            }
+        }
+
+        let plan = merge(&base, &local, &remote, &PreferRemote);
+        assert!(plan.operations.is_empty());
+        assert_eq!(plan.conflicts.len(), 2);
+    }
+
+    #[test]
+    fn deletes_only_the_top_deleted_ancestor() {
+        let child = Task {
+            parent: Some("parent".to_owned()),
+            ..item("child", "child", 0)
+        };
+        let grandchild = Task {
+            parent: Some("child".to_owned()),
+            ..item("grandchild", "grandchild", 0)
+        };
+        let tasks = vec![
+            item("parent", "parent", 0),
+            child,
+            grandchild,
+            item("other", "other", 0),
+        ];
+        let base = account(vec![list("l", "L", tasks.clone())]);
+        let remote = base.clone();
+        let local = account(vec![list("l", "L", vec![item("other", "other", 0)])]);
+
+        let plan = merge(&base, &local, &remote, &PreferLocal);
+        assert_eq!(ops(&plan), ["delete parent"]);
+        assert!(plan.conflicts.is_empty());
+    }
+}
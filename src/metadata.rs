@@ -0,0 +1,487 @@
+//! Tags, priority and time estimate of tasks, which the Google Tasks API does not support.
+//!
+//! The metadata is kept in the footer at the end of the notes, after a `-- gtasks` line, next to
+//! the recurrence of [`crate::recurrence`], as iCalendar (RFC 5545) properties. The text written
+//! before the footer is left as it is.
+//!
+//! ```text
+//! Ask about the invoice
+//!
+//! -- gtasks
+//! CATEGORIES:work,billing
+//! PRIORITY:1
+//! X-GTASKS-ESTIMATE:PT30M
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::Duration;
+
+use crate::errors::{Result, TasksError::ParseError};
+use crate::footer::Footer;
+use crate::{tasks, Task, TaskOptions, TasksApi};
+
+const TAGS_PROP: &str = "CATEGORIES";
+const PRIORITY_PROP: &str = "PRIORITY";
+const ESTIMATE_PROP: &str = "X-GTASKS-ESTIMATE";
+
+/// Priority of a task, ordered from the most urgent.
+///
+/// Kept as the `PRIORITY` property of RFC 5545, where 1 to 4 are high, 5 is medium
+/// and 6 to 9 are low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+/// Metadata of a task.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Tags, in the order they were added.
+    pub tags: Vec<String>,
+
+    pub priority: Option<Priority>,
+
+    /// Estimated time to complete the task.
+    pub estimate: Option<Duration>,
+}
+
+impl Priority {
+    fn from_level(level: u8) -> Option<Priority> {
+        match level {
+            1..=4 => Some(Priority::High),
+            5 => Some(Priority::Medium),
+            6..=9 => Some(Priority::Low),
+            _ => None,
+        }
+    }
+
+    fn level(self) -> u8 {
+        match self {
+            Priority::High => 1,
+            Priority::Medium => 5,
+            Priority::Low => 9,
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = crate::TasksError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "high" | "h" => Ok(Priority::High),
+            "medium" | "m" => Ok(Priority::Medium),
+            "low" | "l" => Ok(Priority::Low),
+            _ => Err(ParseError(format!("invalid priority: {}", s))),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Priority::High => "high",
+            Priority::Medium => "medium",
+            Priority::Low => "low",
+        })
+    }
+}
+
+impl Metadata {
+    /// Reads the metadata from the footer of the notes.
+    pub fn parse(notes: &str) -> Result<Metadata> {
+        let footer = Footer::parse(Some(notes));
+
+        let tags = footer.get(TAGS_PROP).map(split_tags).unwrap_or_default();
+        let priority = match footer.get(PRIORITY_PROP) {
+            Some(value) => {
+                let level: u8 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| ParseError(format!("invalid priority: {}", value)))?;
+                Priority::from_level(level)
+            }
+            None => None,
+        };
+        let estimate = footer.get(ESTIMATE_PROP).map(parse_duration).transpose()?;
+
+        Ok(Metadata {
+            tags,
+            priority,
+            estimate,
+        })
+    }
+
+    /// Reads the metadata of the task.
+    pub fn of(task: &Task) -> Result<Metadata> {
+        Metadata::parse(task.notes.as_deref().unwrap_or_default())
+    }
+
+    /// Writes the metadata to the footer of the notes of the task, keeping the text of the notes
+    /// and the other properties of the footer. Empty metadata removes the properties.
+    pub fn apply(&self, task: &mut Task) {
+        let mut footer = Footer::parse(task.notes.as_deref());
+        let tags: Vec<String> = self.tags.iter().map(|tag| escape(tag)).collect();
+        let tags = Some(tags.join(",")).filter(|tags| !tags.is_empty());
+        let level = self.priority.map(|p| p.level().to_string());
+        let estimate = self.estimate.map(format_duration);
+
+        footer.set(TAGS_PROP, tags.as_deref());
+        footer.set(PRIORITY_PROP, level.as_deref());
+        footer.set(ESTIMATE_PROP, estimate.as_deref());
+        task.notes = footer.notes();
+    }
+
+    /// Whether the task has the tag, ignoring the case.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Adds the tag, unless the task already has it.
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_owned());
+        }
+    }
+
+    /// Removes the tag, ignoring the case.
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+    }
+}
+
+/// Criteria of [`filter`] and [`list`]. The default matches every task.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Tags the tasks must all have, ignoring the case.
+    pub tags: Vec<String>,
+
+    /// Lowest priority the tasks must have; tasks without a priority do not match.
+    pub min_priority: Option<Priority>,
+}
+
+impl Filter {
+    /// Whether the task matches. A task whose metadata cannot be read matches only
+    /// the default filter.
+    pub fn matches(&self, task: &Task) -> bool {
+        if self.tags.is_empty() && self.min_priority.is_none() {
+            return true;
+        }
+        let metadata = match Metadata::of(task) {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+
+        let priority = match (self.min_priority, metadata.priority) {
+            (None, _) => true,
+            (Some(min), Some(priority)) => priority <= min,
+            (Some(_), None) => false,
+        };
+        priority && self.tags.iter().all(|tag| metadata.has_tag(tag))
+    }
+}
+
+/// Returns the tasks which match the filter, in their order.
+pub fn filter<'a, I>(tasks: I, filter: &Filter) -> Vec<&'a Task>
+where
+    I: IntoIterator<Item = &'a Task>,
+{
+    tasks
+        .into_iter()
+        .filter(|task| filter.matches(task))
+        .collect()
+}
+
+/// Fetches the tasks of the task list, then keeps those which match the filter.
+pub async fn list<A: TasksApi + ?Sized>(
+    api: &A,
+    tasklist_id: &str,
+    opts: TaskOptions,
+    filter: &Filter,
+) -> Result<Vec<Task>> {
+    let mut tasks = tasks::list_all(api, tasklist_id, opts).await?;
+    tasks.retain(|task| filter.matches(task));
+    Ok(tasks)
+}
+
+// Splits a CATEGORIES value at the commas which are not escaped.
+fn split_tags(value: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut tag = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => tag.extend(chars.next()),
+            ',' => tags.push(std::mem::take(&mut tag)),
+            c => tag.push(c),
+        }
+    }
+    tags.push(tag);
+    tags.into_iter()
+        .map(|tag| tag.trim().to_owned())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+fn escape(tag: &str) -> String {
+    tag.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('\n', " ")
+}
+
+// Parses a duration of RFC 5545, e.g. `PT1H30M` or `P2D`.
+fn parse_duration(value: &str) -> Result<Duration> {
+    let invalid = || ParseError(format!("invalid duration: {}", value));
+    let trimmed = value.trim();
+    let (negative, rest) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    let mut parts = 0;
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if !in_time && number.is_empty() => in_time = true,
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                let unit = match (c, in_time) {
+                    ('W', false) => 7 * 24 * 3600,
+                    ('D', false) => 24 * 3600,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return Err(invalid()),
+                };
+                seconds = n
+                    .checked_mul(unit)
+                    .and_then(|s| s.checked_add(seconds))
+                    .ok_or_else(invalid)?;
+                number.clear();
+                parts += 1;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    if parts == 0 || !number.is_empty() {
+        return Err(invalid());
+    }
+
+    let duration = Duration::try_seconds(seconds).ok_or_else(invalid)?;
+    Ok(if negative { -duration } else { duration })
+}
+
+fn format_duration(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let total = duration.num_seconds().abs();
+    let (days, hours, minutes, seconds) = (
+        total / 86400,
+        total % 86400 / 3600,
+        total % 3600 / 60,
+        total % 60,
+    );
+
+    let mut out = format!("{}P", sign);
+    if days > 0 {
+        out.push_str(&format!("{}D", days));
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+        out.push('T');
+        if hours > 0 {
+            out.push_str(&format!("{}H", hours));
+        }
+        if minutes > 0 {
+            out.push_str(&format!("{}M", minutes));
+        }
+        if seconds > 0 || total == 0 {
+            out.push_str(&format!("{}S", seconds));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recurrence::Recurrence;
+    use crate::{FakeTasks, Tasklist};
+
+    fn task(notes: &str) -> Task {
+        Task {
+            notes: Some(notes.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reads_and_writes_the_footer() {
+        let mut invoice = task("Ask about the invoice\n\n#not-a-tag in the text");
+        let metadata = Metadata {
+            tags: vec!["work".to_owned(), "a,b".to_owned()],
+            priority: Some(Priority::High),
+            estimate: Some(Duration::minutes(90)),
+        };
+        metadata.apply(&mut invoice);
+        assert_eq!(
+            invoice.notes.as_deref(),
+            Some(
+                "Ask about the invoice\n\n#not-a-tag in the text\n\n-- gtasks\n\
+                 CATEGORIES:work,a\\,b\nPRIORITY:1\nX-GTASKS-ESTIMATE:PT1H30M"
+            )
+        );
+        assert_eq!(Metadata::of(&invoice).unwrap(), metadata);
+
+        // the recurrence in the same footer is kept
+        let rule = "FREQ=DAILY".parse().unwrap();
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        Recurrence::new(rule, start).apply(&mut invoice);
+        Metadata::default().apply(&mut invoice);
+        assert_eq!(
+            invoice.notes.as_deref(),
+            Some(
+                "Ask about the invoice\n\n#not-a-tag in the text\n\n-- gtasks\n\
+                 RRULE:FREQ=DAILY\nDTSTART:20261019"
+            )
+        );
+        assert!(Recurrence::of(&invoice).unwrap().is_some());
+
+        let mut plain = task("just text");
+        Metadata::default().apply(&mut plain);
+        assert_eq!(plain.notes.as_deref(), Some("just text"));
+
+        let levels = task("-- gtasks\nPRIORITY:7\nCATEGORIES:Home");
+        let metadata = Metadata::of(&levels).unwrap();
+        assert_eq!(metadata.priority, Some(Priority::Low));
+        assert!(metadata.has_tag("home"));
+        assert!(Metadata::of(&task("-- gtasks\nPRIORITY:high")).is_err());
+    }
+
+    #[test]
+    fn keeps_the_notes_verbatim() {
+        for notes in [None, Some(""), Some("text\n"), Some("  text  \n\n")] {
+            let mut unchanged = Task {
+                notes: notes.map(str::to_owned),
+                ..Default::default()
+            };
+            Metadata::default().apply(&mut unchanged);
+            assert_eq!(unchanged.notes.as_deref(), notes);
+        }
+
+        let mut tagged = task("  text\n");
+        let metadata = Metadata {
+            tags: vec!["work".to_owned()],
+            ..Default::default()
+        };
+        metadata.apply(&mut tagged);
+        assert_eq!(
+            tagged.notes.as_deref(),
+            Some("  text\n\n-- gtasks\nCATEGORIES:work")
+        );
+        metadata.apply(&mut tagged);
+        assert_eq!(
+            tagged.notes.as_deref(),
+            Some("  text\n\n-- gtasks\nCATEGORIES:work")
+        );
+        Metadata::default().apply(&mut tagged);
+        assert_eq!(tagged.notes.as_deref(), Some("  text"));
+
+        let mut spaced = task("text\n-- gtasks\n  PRIORITY:1  \n");
+        Metadata {
+            priority: Some(Priority::High),
+            ..Default::default()
+        }
+        .apply(&mut spaced);
+        assert_eq!(
+            spaced.notes.as_deref(),
+            Some("text\n-- gtasks\n  PRIORITY:1  \n")
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        let cases = [
+            ("PT30M", Duration::minutes(30)),
+            ("P1DT2H", Duration::hours(26)),
+            ("P1W", Duration::days(7)),
+            ("-PT15S", Duration::seconds(-15)),
+        ];
+        for (text, duration) in cases {
+            assert_eq!(parse_duration(text).unwrap(), duration, "{}", text);
+        }
+        assert_eq!(format_duration(Duration::hours(26)), "P1DT2H");
+        assert_eq!(format_duration(Duration::zero()), "PT0S");
+        for invalid in ["30M", "PT", "P1H", "PT5", "P1X"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn filters_by_tag_and_priority() {
+        let fake = FakeTasks::new();
+        let tasklist = fake
+            .insert_tasklist(Tasklist {
+                title: Some("Work".to_owned()),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+
+        let cases = [
+            ("a", vec!["work"], Some(Priority::High)),
+            ("b", vec!["work", "home"], Some(Priority::Low)),
+            ("c", vec!["Work"], None),
+            ("d", vec![], Some(Priority::Medium)),
+        ];
+        for (title, tags, priority) in cases {
+            let mut task = Task {
+                title: Some(title.to_owned()),
+                ..Default::default()
+            };
+            Metadata {
+                tags: tags.into_iter().map(str::to_owned).collect(),
+                priority,
+                estimate: None,
+            }
+            .apply(&mut task);
+            fake.insert_task(&tasklist, task, None).await.unwrap();
+        }
+
+        let titles = |tasks: Vec<Task>| -> Vec<String> {
+            let mut titles: Vec<_> = tasks.into_iter().filter_map(|t| t.title).collect();
+            titles.sort();
+            titles
+        };
+        let work = Filter {
+            tags: vec!["work".to_owned()],
+            ..Default::default()
+        };
+        let tasks = list(&fake, &tasklist, TaskOptions::default(), &work)
+            .await
+            .unwrap();
+        assert_eq!(titles(tasks), ["a", "b", "c"]);
+
+        let urgent = Filter {
+            min_priority: Some(Priority::Medium),
+            ..Default::default()
+        };
+        let tasks = list(&fake, &tasklist, TaskOptions::default(), &urgent)
+            .await
+            .unwrap();
+        assert_eq!(titles(tasks), ["a", "d"]);
+
+        let all = list(&fake, &tasklist, TaskOptions::default(), &Filter::default())
+            .await
+            .unwrap();
+        assert_eq!(filter(all.iter(), &work).len(), 3);
+    }
+}
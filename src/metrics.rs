@@ -0,0 +1,336 @@
+//! Metrics of the API usage, e.g. to watch the quota.
+//!
+//! [`Metrics`] is a middleware given to [`crate::Service::with_middleware`]. It counts the requests
+//! by operation and status, records the latency histograms, and counts the `304 Not Modified`
+//! responses to the requests with an ETag. Placed after a retry middleware, it counts every attempt.
+//!
+//! The metrics are rendered in the Prometheus text format by [`Metrics::prometheus`] and, with the
+//! `opentelemetry` feature, also recorded through the instruments of an OpenTelemetry meter.
+//!
+//! ```no_run
+//! # fn example() -> gtasks::Result<()> {
+//! use std::sync::Arc;
+//! use gtasks::{metrics::Metrics, Service};
+//!
+//! let metrics = Arc::new(Metrics::new());
+//! let service = Service::with_middleware(|| Ok("token".to_owned()), vec![metrics.clone()])?;
+//! // ... serve metrics.prometheus() on /metrics
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::Utc;
+use reqwest::header::IF_NONE_MATCH;
+use reqwest::{Method, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next, Result as MidWareResult};
+use task_local_extensions::Extensions;
+
+/// Upper bounds of the latency buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Status label of the requests which failed without a response.
+const ERROR_STATUS: &str = "error";
+
+/// Middleware recording the metrics of the requests.
+#[derive(Default)]
+pub struct Metrics {
+    state: Mutex<State>,
+    #[cfg(feature = "opentelemetry")]
+    instruments: Option<Instruments>,
+}
+
+#[derive(Default)]
+struct State {
+    requests: BTreeMap<(&'static str, String), u64>,
+    latencies: BTreeMap<&'static str, Histogram>,
+    cache_hits: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates metrics which are also recorded through the instruments of the meter:
+    /// the `gtasks.requests` and `gtasks.cache_hits` counters and the `gtasks.request.duration` histogram.
+    #[cfg(feature = "opentelemetry")]
+    pub fn with_meter(meter: &opentelemetry::metrics::Meter) -> Self {
+        Metrics {
+            state: Mutex::default(),
+            instruments: Some(Instruments::new(meter)),
+        }
+    }
+
+    /// Returns the number of requests of the operation, e.g. `tasks.list`, which got the status.
+    pub fn requests(&self, operation: &str, status: StatusCode) -> u64 {
+        let state = self.lock();
+        state
+            .requests
+            .iter()
+            .filter(|((op, s), _)| *op == operation && *s == status.as_str())
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Returns the number of requests of the operation answered with `304 Not Modified`.
+    pub fn cache_hits(&self, operation: &str) -> u64 {
+        self.lock().cache_hits.get(operation).copied().unwrap_or(0)
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn prometheus(&self) -> String {
+        let state = self.lock();
+        let mut out = String::new();
+
+        out.push_str("# HELP gtasks_requests_total Requests to the Google Tasks API.\n");
+        out.push_str("# TYPE gtasks_requests_total counter\n");
+        for ((operation, status), count) in state.requests.iter() {
+            let _ = writeln!(
+                out,
+                "gtasks_requests_total{{operation=\"{}\",status=\"{}\"}} {}",
+                operation, status, count
+            );
+        }
+
+        out.push_str("# HELP gtasks_request_duration_seconds Latency of the requests.\n");
+        out.push_str("# TYPE gtasks_request_duration_seconds histogram\n");
+        for (operation, histogram) in state.latencies.iter() {
+            for (count, bound) in histogram.buckets.iter().zip(BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "gtasks_request_duration_seconds_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                    operation, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "gtasks_request_duration_seconds_bucket{{operation=\"{}\",le=\"+Inf\"}} {}",
+                operation, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "gtasks_request_duration_seconds_sum{{operation=\"{}\"}} {}",
+                operation, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "gtasks_request_duration_seconds_count{{operation=\"{}\"}} {}",
+                operation, histogram.count
+            );
+        }
+
+        out.push_str("# HELP gtasks_cache_hits_total Requests with an ETag answered with 304 Not Modified.\n");
+        out.push_str("# TYPE gtasks_cache_hits_total counter\n");
+        for (operation, count) in state.cache_hits.iter() {
+            let _ = writeln!(
+                out,
+                "gtasks_cache_hits_total{{operation=\"{}\"}} {}",
+                operation, count
+            );
+        }
+
+        out
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn observe(&self, operation: &'static str, status: String, seconds: f64, cache_hit: bool) {
+        #[cfg(feature = "opentelemetry")]
+        if let Some(instruments) = self.instruments.as_ref() {
+            instruments.observe(operation, &status, seconds, cache_hit);
+        }
+
+        let mut state = self.lock();
+        *state.requests.entry((operation, status)).or_default() += 1;
+        state
+            .latencies
+            .entry(operation)
+            .or_default()
+            .observe(seconds);
+        if cache_hit {
+            *state.cache_hits.entry(operation).or_default() += 1;
+        }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl Middleware for Metrics {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MidWareResult<Response> {
+        let operation = operation(req.method(), req.url().path());
+        let conditional = req.headers().contains_key(IF_NONE_MATCH);
+
+        let started = Utc::now();
+        let result = next.run(req, extensions).await;
+        let elapsed = Utc::now() - started;
+        let seconds = elapsed.num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6;
+
+        let status = match result.as_ref() {
+            Ok(resp) => resp.status(),
+            Err(_) => {
+                self.observe(operation, ERROR_STATUS.to_owned(), seconds, false);
+                return result;
+            }
+        };
+        let cache_hit = conditional && status == StatusCode::NOT_MODIFIED;
+        self.observe(operation, status.as_str().to_owned(), seconds, cache_hit);
+        result
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+struct Instruments {
+    requests: opentelemetry::metrics::Counter<u64>,
+    duration: opentelemetry::metrics::Histogram<f64>,
+    cache_hits: opentelemetry::metrics::Counter<u64>,
+}
+
+#[cfg(feature = "opentelemetry")]
+impl Instruments {
+    fn new(meter: &opentelemetry::metrics::Meter) -> Self {
+        Instruments {
+            requests: meter
+                .u64_counter("gtasks.requests")
+                .with_description("Requests to the Google Tasks API.")
+                .build(),
+            duration: meter
+                .f64_histogram("gtasks.request.duration")
+                .with_description("Latency of the requests.")
+                .with_unit("s")
+                .with_boundaries(BUCKETS.to_vec())
+                .build(),
+            cache_hits: meter
+                .u64_counter("gtasks.cache_hits")
+                .with_description("Requests with an ETag answered with 304 Not Modified.")
+                .build(),
+        }
+    }
+
+    fn observe(&self, operation: &'static str, status: &str, seconds: f64, cache_hit: bool) {
+        use opentelemetry::KeyValue;
+
+        let attributes = [
+            KeyValue::new("operation", operation),
+            KeyValue::new("status", status.to_owned()),
+        ];
+        self.requests.add(1, &attributes);
+        self.duration.record(seconds, &attributes[..1]);
+        if cache_hit {
+            self.cache_hits.add(1, &attributes[..1]);
+        }
+    }
+}
+
+// Names the operation of the request after the functions of the crate, e.g. `tasks.list`.
+fn operation(method: &Method, path: &str) -> &'static str {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let (resource, collection) = match segments.as_slice() {
+        [.., "users", "@me", "lists"] => ("tasklists", true),
+        [.., "users", "@me", "lists", _] => ("tasklists", false),
+        [.., "lists", _, "tasks", _, "move"] if method == Method::POST => return "tasks.move",
+        [.., "lists", _, "clear"] if method == Method::POST => return "tasks.clear",
+        [.., "lists", _, "tasks"] => ("tasks", true),
+        [.., "lists", _, "tasks", _] => ("tasks", false),
+        _ => return "unknown",
+    };
+
+    match (resource, collection, method.as_str()) {
+        ("tasklists", true, "GET") => "tasklists.list",
+        ("tasklists", true, "POST") => "tasklists.insert",
+        ("tasklists", false, "GET") => "tasklists.get",
+        ("tasklists", false, "PUT") => "tasklists.update",
+        ("tasklists", false, "DELETE") => "tasklists.delete",
+        ("tasklists", false, "PATCH") => "tasklists.patch",
+        ("tasks", true, "GET") => "tasks.list",
+        ("tasks", true, "POST") => "tasks.insert",
+        ("tasks", false, "GET") => "tasks.get",
+        ("tasks", false, "PUT") => "tasks.update",
+        ("tasks", false, "DELETE") => "tasks.delete",
+        ("tasks", false, "PATCH") => "tasks.patch",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_operations() {
+        let cases = [
+            (Method::GET, "/tasks/v1/users/@me/lists", "tasklists.list"),
+            (
+                Method::PATCH,
+                "/tasks/v1/users/@me/lists/l1",
+                "tasklists.patch",
+            ),
+            (Method::GET, "/tasks/v1/lists/l1/tasks", "tasks.list"),
+            (Method::POST, "/tasks/v1/lists/l1/tasks", "tasks.insert"),
+            (Method::PUT, "/tasks/v1/lists/l1/tasks/t1", "tasks.update"),
+            (
+                Method::POST,
+                "/tasks/v1/lists/l1/tasks/t1/move",
+                "tasks.move",
+            ),
+            (Method::POST, "/tasks/v1/lists/l1/clear", "tasks.clear"),
+            (Method::GET, "/other", "unknown"),
+        ];
+        for (method, path, expected) in cases {
+            assert_eq!(operation(&method, path), expected, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let metrics = Metrics::new();
+        metrics.observe("tasks.list", "200".to_owned(), 0.02, false);
+        metrics.observe("tasks.list", "304".to_owned(), 0.3, true);
+
+        assert_eq!(metrics.requests("tasks.list", StatusCode::OK), 1);
+        assert_eq!(metrics.cache_hits("tasks.list"), 1);
+
+        let text = metrics.prometheus();
+        assert!(text.contains("gtasks_requests_total{operation=\"tasks.list\",status=\"304\"} 1"));
+        assert!(text.contains(
+            "gtasks_request_duration_seconds_bucket{operation=\"tasks.list\",le=\"0.025\"} 1"
+        ));
+        assert!(text.contains(
+            "gtasks_request_duration_seconds_bucket{operation=\"tasks.list\",le=\"+Inf\"} 2"
+        ));
+        assert!(text.contains("gtasks_request_duration_seconds_count{operation=\"tasks.list\"} 2"));
+        assert!(text.contains("gtasks_cache_hits_total{operation=\"tasks.list\"} 1"));
+    }
+}
@@ -0,0 +1,813 @@
+//! Two-way mirroring of two task lists, in different accounts or in the same one.
+//!
+//! A [`MirrorState`] pairs the tasks of the left list with the tasks of the right list and
+//! keeps, for each pair, the contents of the last sync and the `updated` time of both tasks.
+//! [`sync`] copies the changes made on either side since the last sync to the other side:
+//!
+//! - a task created on one side is created on the other, under the mirror of its parent;
+//! - the title, notes, due date and status are merged field by field; a field changed on both
+//!   sides takes the value of the task updated last, the left side winning a tie;
+//! - a task deleted on one side is deleted on the other, unless it was modified there since
+//!   the last sync, in which case it is created again on the side which deleted it.
+//!
+//! A task whose `updated` time is still the one recorded by the last sync counts as unchanged,
+//! so the writes of a sync are not copied back by the next one. Moves are not mirrored.
+//!
+//! The state is kept between syncs, e.g. in a JSON file:
+//!
+//! ```no_run
+//! # async fn example(work: &gtasks::Service, home: &gtasks::Service) -> gtasks::Result<()> {
+//! use std::fs::File;
+//! use gtasks::mirror::{self, MirrorState};
+//!
+//! let mut state = match File::open("mirror.json") {
+//!     Ok(file) => MirrorState::read_json(file)?,
+//!     Err(_) => MirrorState::new("work-list-id", "home-list-id"),
+//! };
+//! let changes = mirror::sync(work, home, &mut state).await;
+//! state.write_json(File::create("mirror.json")?)?;
+//! println!("{} changes", changes?.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::diff::Field;
+use crate::errors::{
+    Result,
+    TasksError::{InvalidArgument, ResponseError},
+};
+use crate::{tasks, Task, TaskInsertOptions, TaskOptions, TasksApi};
+
+/// Fields of the tasks merged by [`sync`].
+const FIELDS: [Field; 4] = [Field::Title, Field::Notes, Field::Due, Field::Status];
+
+/// Pairs of mirrored tasks of two task lists, kept between syncs.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MirrorState {
+    pub left_tasklist_id: String,
+    pub right_tasklist_id: String,
+
+    /// Time the last sync finished at.
+    pub synced_at: Option<DateTime<Utc>>,
+
+    pub pairs: Vec<Pair>,
+}
+
+/// Task of the left list and its mirror in the right list.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Pair {
+    pub left_id: String,
+    pub right_id: String,
+
+    /// `updated` time of the left task after the last sync.
+    pub left_updated: Option<DateTime<Utc>>,
+
+    /// `updated` time of the right task after the last sync.
+    pub right_updated: Option<DateTime<Utc>>,
+
+    /// Contents of both tasks after the last sync.
+    pub base: Task,
+}
+
+/// One of the two mirrored task lists.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Write made by [`sync`] to a side.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Change {
+    pub side: Side,
+    pub task_id: String,
+    pub kind: ChangeKind,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Inserted,
+    Updated(Vec<Field>),
+    Deleted,
+}
+
+impl MirrorState {
+    /// Creates the state of two task lists which were not synced yet.
+    /// The first sync copies all the tasks of each list to the other.
+    pub fn new(left_tasklist_id: &str, right_tasklist_id: &str) -> Self {
+        MirrorState {
+            left_tasklist_id: left_tasklist_id.to_owned(),
+            right_tasklist_id: right_tasklist_id.to_owned(),
+            synced_at: None,
+            pairs: Vec::new(),
+        }
+    }
+
+    /// Reads a state written by [`MirrorState::write_json`].
+    pub fn read_json<R: Read>(reader: R) -> Result<Self> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Writes the state as a JSON document.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> Result<()> {
+        serde_json::to_writer(&mut writer, self)?;
+        Ok(writer.flush()?)
+    }
+
+    /// Returns the id of the mirror of the task of the side.
+    pub fn mirror_of(&self, side: Side, task_id: &str) -> Option<&str> {
+        self.pairs.iter().find_map(|pair| match side {
+            Side::Left if pair.left_id == task_id => Some(pair.right_id.as_str()),
+            Side::Right if pair.right_id == task_id => Some(pair.left_id.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl Side {
+    fn other(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+impl Pair {
+    fn id(&self, side: Side) -> &str {
+        match side {
+            Side::Left => &self.left_id,
+            Side::Right => &self.right_id,
+        }
+    }
+
+    fn updated(&self, side: Side) -> Option<DateTime<Utc>> {
+        match side {
+            Side::Left => self.left_updated,
+            Side::Right => self.right_updated,
+        }
+    }
+
+    fn set(&mut self, side: Side, task: &Task) -> Result<()> {
+        let id = task
+            .id
+            .clone()
+            .ok_or_else(|| ResponseError("mirrored task has no id".to_owned()))?;
+        match side {
+            Side::Left => {
+                self.left_id = id;
+                self.left_updated = task.updated;
+            }
+            Side::Right => {
+                self.right_id = id;
+                self.right_updated = task.updated;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copies the changes of each task list since the last sync to the other, recording the pairs
+/// of tasks in the state. The state is updated after every write, so that it can be saved and
+/// the sync retried when a request fails.
+pub async fn sync(
+    left: &dyn TasksApi,
+    right: &dyn TasksApi,
+    state: &mut MirrorState,
+) -> Result<Vec<Change>> {
+    let same_service = std::ptr::eq(
+        left as *const dyn TasksApi as *const (),
+        right as *const dyn TasksApi as *const (),
+    );
+    if same_service && state.left_tasklist_id == state.right_tasklist_id {
+        return Err(InvalidArgument(
+            "cannot mirror a task list to itself".to_owned(),
+        ));
+    }
+
+    let left_list = state.left_tasklist_id.clone();
+    let right_list = state.right_tasklist_id.clone();
+    let mut mirror = Mirror {
+        sides: [
+            Endpoint::fetch(left, &left_list).await?,
+            Endpoint::fetch(right, &right_list).await?,
+        ],
+        changes: Vec::new(),
+    };
+
+    mirror.sync_pairs(state).await?;
+    mirror.insert_unpaired(state, Side::Left).await?;
+    mirror.insert_unpaired(state, Side::Right).await?;
+
+    state.synced_at = Some(Utc::now());
+    Ok(mirror.changes)
+}
+
+// Task list of a side with the tasks fetched at the start of the sync, in the order of the list.
+struct Endpoint<'a> {
+    api: &'a dyn TasksApi,
+    tasklist_id: &'a str,
+    tasks: HashMap<String, Task>,
+    order: Vec<String>,
+}
+
+impl<'a> Endpoint<'a> {
+    async fn fetch(api: &'a dyn TasksApi, tasklist_id: &'a str) -> Result<Endpoint<'a>> {
+        let opts = TaskOptions {
+            max_results: Some(100),
+            show_completed: Some(true),
+            show_hidden: Some(true),
+            ..Default::default()
+        };
+        let mut live: Vec<Task> = tasks::list_all(api, tasklist_id, opts)
+            .await?
+            .into_iter()
+            .filter(|task| task.deleted != Some(true) && task.id.is_some())
+            .collect();
+        live.sort_by(|a, b| a.position.cmp(&b.position));
+
+        let order = live.iter().filter_map(|task| task.id.clone()).collect();
+        let tasks = live
+            .into_iter()
+            .filter_map(|task| task.id.clone().map(|id| (id, task)))
+            .collect();
+        Ok(Endpoint {
+            api,
+            tasklist_id,
+            tasks,
+            order,
+        })
+    }
+
+    // The task and its ancestors, nearest first.
+    fn ancestry<'t>(&'t self, id: &str) -> impl Iterator<Item = &'t Task> {
+        std::iter::successors(self.tasks.get(id), |task| {
+            task.parent.as_deref().and_then(|p| self.tasks.get(p))
+        })
+        .take(self.tasks.len() + 1)
+    }
+
+    // Returns the id of the sibling right before the task.
+    fn previous_sibling(&self, task: &Task) -> Option<&str> {
+        self.order
+            .iter()
+            .take_while(|id| Some(id.as_str()) != task.id.as_deref())
+            .filter(|id| self.tasks[id.as_str()].parent == task.parent)
+            .last()
+            .map(String::as_str)
+    }
+
+    async fn insert(
+        &self,
+        task: &Task,
+        parent: Option<String>,
+        previous: Option<String>,
+    ) -> Result<Task> {
+        let copy = Task {
+            title: task.title.clone(),
+            notes: task.notes.clone(),
+            due: task.due,
+            status: task.status,
+            completed: task.completed,
+            ..Default::default()
+        };
+        let opts = TaskInsertOptions { parent, previous };
+        self.api
+            .insert_task(self.tasklist_id, copy, Some(opts))
+            .await
+    }
+}
+
+struct Mirror<'a> {
+    sides: [Endpoint<'a>; 2],
+    changes: Vec<Change>,
+}
+
+impl Mirror<'_> {
+    fn side(&self, side: Side) -> &Endpoint<'_> {
+        match side {
+            Side::Left => &self.sides[0],
+            Side::Right => &self.sides[1],
+        }
+    }
+
+    fn record(&mut self, side: Side, task_id: &str, kind: ChangeKind) {
+        self.changes.push(Change {
+            side,
+            task_id: task_id.to_owned(),
+            kind,
+        });
+    }
+
+    // Returns the id of the mirror of the task of `from` on the other side, if it exists there.
+    fn mirror_of(&self, state: &MirrorState, from: Side, task_id: &str) -> Option<String> {
+        state
+            .mirror_of(from, task_id)
+            .filter(|id| self.side(from.other()).tasks.contains_key(*id))
+            .map(str::to_owned)
+    }
+
+    // Merges the paired tasks, and mirrors the deletions.
+    async fn sync_pairs(&mut self, state: &mut MirrorState) -> Result<()> {
+        let mut deletes: Vec<(Side, String)> = Vec::new();
+        let mut i = 0;
+
+        while i < state.pairs.len() {
+            let pair = &state.pairs[i];
+            let left = self.sides[0].tasks.get(&pair.left_id).cloned();
+            let right = self.sides[1].tasks.get(&pair.right_id).cloned();
+
+            let (kept_side, kept) = match (left, right) {
+                (None, None) => {
+                    state.pairs.remove(i);
+                    continue;
+                }
+                (Some(left), Some(right)) => {
+                    self.merge(&mut state.pairs[i], left, right).await?;
+                    i += 1;
+                    continue;
+                }
+                (Some(left), None) => (Side::Left, left),
+                (None, Some(right)) => (Side::Right, right),
+            };
+
+            if kept.updated == state.pairs[i].updated(kept_side) {
+                deletes.push((kept_side, state.pairs[i].id(kept_side).to_owned()));
+            } else {
+                // a modification wins over the deletion: the task is created again
+                let parent = kept
+                    .parent
+                    .as_deref()
+                    .and_then(|p| self.mirror_of(state, kept_side, p));
+                let side = kept_side.other();
+                let created = self.side(side).insert(&kept, parent, None).await?;
+                let pair = &mut state.pairs[i];
+                pair.set(side, &created)?;
+                pair.set(kept_side, &kept)?;
+                pair.base = kept;
+                let id = pair.id(side).to_owned();
+                self.record(side, &id, ChangeKind::Inserted);
+            }
+            i += 1;
+        }
+
+        // deleting a task deletes its subtasks, which are then not deleted again
+        for side in [Side::Left, Side::Right] {
+            let ids: HashSet<String> = deletes
+                .iter()
+                .filter(|(s, _)| *s == side)
+                .map(|(_, id)| id.clone())
+                .collect();
+            let endpoint = self.side(side);
+            let deleted_ancestors = |id: &str| -> Vec<String> {
+                endpoint
+                    .ancestry(id)
+                    .filter_map(|t| t.id.clone())
+                    .filter(|a| ids.contains(a))
+                    .collect()
+            };
+            let mut removed = HashSet::new();
+            for id in endpoint.order.iter() {
+                let ancestors = deleted_ancestors(id);
+                if ancestors.len() == 1 && &ancestors[0] == id {
+                    endpoint.api.delete_task(endpoint.tasklist_id, id).await?;
+                }
+                if !ancestors.is_empty() {
+                    removed.insert(id.clone());
+                }
+            }
+
+            let endpoint = match side {
+                Side::Left => &mut self.sides[0],
+                Side::Right => &mut self.sides[1],
+            };
+            endpoint.order.retain(|id| !removed.contains(id));
+            endpoint.tasks.retain(|id, _| !removed.contains(id));
+            for id in removed.iter().filter(|id| ids.contains(*id)) {
+                self.record(side, id, ChangeKind::Deleted);
+            }
+            state.pairs.retain(|pair| !ids.contains(pair.id(side)));
+        }
+        Ok(())
+    }
+
+    // Copies the fields changed on each side since the last sync to the other side.
+    async fn merge(&mut self, pair: &mut Pair, left: Task, right: Task) -> Result<()> {
+        let left_changed = left.updated != pair.left_updated;
+        let right_changed = right.updated != pair.right_updated;
+        if !left_changed && !right_changed {
+            return Ok(());
+        }
+
+        let mut to_left = Vec::new();
+        let mut to_right = Vec::new();
+        for field in FIELDS {
+            if field.same(&left, &right) {
+                continue;
+            }
+            let on_left = left_changed && !field.same(&pair.base, &left);
+            let on_right = right_changed && !field.same(&pair.base, &right);
+            let left_wins = match (on_left, on_right) {
+                (true, false) => true,
+                (false, true) => false,
+                _ => left.updated >= right.updated,
+            };
+            if left_wins {
+                to_right.push(field);
+            } else {
+                to_left.push(field);
+            }
+        }
+
+        let mut base = left.clone();
+        for field in to_left.iter() {
+            field.copy(&right, &mut base);
+        }
+
+        // the pair is recorded once both sides are written: when a write fails, the side
+        // written before keeps its previous `updated`, so that a retry sees its changes again
+        // and copies those which the other side still lacks
+        let mut written = Vec::new();
+        for (side, fields, from, to) in [
+            (Side::Left, to_left, &right, &left),
+            (Side::Right, to_right, &left, &right),
+        ] {
+            if fields.is_empty() {
+                continue;
+            }
+            let mut task = to.clone();
+            for field in fields.iter() {
+                field.copy(from, &mut task);
+            }
+            let endpoint = self.side(side);
+            let updated = endpoint.api.update_task(endpoint.tasklist_id, task).await?;
+            let id = pair.id(side).to_owned();
+            self.record(side, &id, ChangeKind::Updated(fields));
+            written.push((side, updated));
+        }
+
+        pair.base = base;
+        pair.left_updated = left.updated;
+        pair.right_updated = right.updated;
+        for (side, updated) in written.iter() {
+            pair.set(*side, updated)?;
+        }
+        Ok(())
+    }
+
+    // Creates the tasks of the side which have no mirror yet on the other side,
+    // parents before their children and each after the mirror of its previous sibling.
+    async fn insert_unpaired(&mut self, state: &mut MirrorState, side: Side) -> Result<()> {
+        let other = side.other();
+        let paired: HashSet<&str> = state.pairs.iter().map(|p| p.id(side)).collect();
+        let endpoint = self.side(side);
+        let mut unpaired: Vec<String> = endpoint
+            .order
+            .iter()
+            .filter(|id| !paired.contains(id.as_str()))
+            .cloned()
+            .collect();
+        unpaired.sort_by_key(|id| endpoint.ancestry(id).count());
+
+        for id in unpaired {
+            let endpoint = self.side(side);
+            let task = endpoint.tasks[&id].clone();
+            let parent = task
+                .parent
+                .as_deref()
+                .and_then(|p| state.mirror_of(side, p))
+                .map(str::to_owned);
+            let previous = endpoint
+                .previous_sibling(&task)
+                .and_then(|p| state.mirror_of(side, p))
+                .map(str::to_owned);
+
+            let created = self.side(other).insert(&task, parent, previous).await?;
+            let mut pair = Pair {
+                left_id: String::new(),
+                right_id: String::new(),
+                left_updated: None,
+                right_updated: None,
+                base: task.clone(),
+            };
+            pair.set(side, &task)?;
+            pair.set(other, &created)?;
+            let created_id = pair.id(other).to_owned();
+            state.pairs.push(pair);
+            self.record(other, &created_id, ChangeKind::Inserted);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeTasks, TaskStatus, Tasklist, Tasklists, TasklistsOptions, Tasks};
+
+    async fn tasklist(fake: &FakeTasks, title: &str) -> String {
+        let tasklist = Tasklist {
+            title: Some(title.to_owned()),
+            ..Default::default()
+        };
+        fake.insert_tasklist(tasklist).await.unwrap().id.unwrap()
+    }
+
+    async fn insert(fake: &FakeTasks, list: &str, title: &str, parent: Option<&str>) -> Task {
+        let task = Task {
+            title: Some(title.to_owned()),
+            ..Default::default()
+        };
+        let opts = TaskInsertOptions {
+            parent: parent.map(str::to_owned),
+            previous: None,
+        };
+        fake.insert_task(list, task, Some(opts)).await.unwrap()
+    }
+
+    async fn titles(fake: &FakeTasks, list: &str) -> Vec<(String, Option<String>)> {
+        let opts = TaskOptions {
+            show_completed: Some(true),
+            show_hidden: Some(true),
+            ..Default::default()
+        };
+        let mut tasks = tasks::list_all(fake, list, opts).await.unwrap();
+        tasks.sort_by(|a, b| a.title.cmp(&b.title));
+        let by_id: HashMap<_, _> = tasks
+            .iter()
+            .map(|t| (t.id.clone().unwrap(), t.title.clone().unwrap()))
+            .collect();
+        tasks
+            .iter()
+            .map(|t| {
+                let parent = t.parent.as_ref().map(|p| by_id[p].clone());
+                (t.title.clone().unwrap(), parent)
+            })
+            .collect()
+    }
+
+    async fn get(fake: &FakeTasks, list: &str, id: &str) -> Task {
+        fake.get_task(list, id, None).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn mirrors_two_accounts() {
+        let (work, home) = (FakeTasks::new(), FakeTasks::new());
+        let (w, h) = (
+            tasklist(&work, "Shared").await,
+            tasklist(&home, "Shared").await,
+        );
+        let a = insert(&work, &w, "a", None).await;
+        let b = insert(&work, &w, "b", None).await;
+        let b1 = insert(&work, &w, "b1", b.id.as_deref()).await;
+        let c = insert(&home, &h, "c", None).await;
+
+        let mut state = MirrorState::new(&w, &h);
+        let changes = sync(&work, &home, &mut state).await.unwrap();
+        assert_eq!(changes.len(), 4);
+        let expected = vec![
+            ("a".to_owned(), None),
+            ("b".to_owned(), None),
+            ("b1".to_owned(), Some("b".to_owned())),
+            ("c".to_owned(), None),
+        ];
+        assert_eq!(titles(&work, &w).await, expected);
+        assert_eq!(titles(&home, &h).await, expected);
+
+        // the writes of the sync are not copied back
+        assert!(sync(&work, &home, &mut state).await.unwrap().is_empty());
+
+        let mirror = |side, task: &Task| {
+            let id = task.id.as_deref().unwrap();
+            state.mirror_of(side, id).unwrap().to_owned()
+        };
+        let (home_a, home_b, home_b1) = (
+            mirror(Side::Left, &a),
+            mirror(Side::Left, &b),
+            mirror(Side::Left, &b1),
+        );
+        let work_c = mirror(Side::Right, &c);
+
+        let mut done = get(&home, &h, &home_a).await;
+        done.status = Some(TaskStatus::Completed);
+        home.update_task(&h, done).await.unwrap();
+
+        let mut renamed = get(&work, &w, b.id.as_deref().unwrap()).await;
+        renamed.title = Some("b renamed".to_owned());
+        work.update_task(&w, renamed).await.unwrap();
+        let mut noted = get(&home, &h, &home_b).await;
+        noted.notes = Some("notes".to_owned());
+        home.update_task(&h, noted).await.unwrap();
+
+        let mut older = get(&home, &h, c.id.as_deref().unwrap()).await;
+        older.title = Some("c home".to_owned());
+        home.update_task(&h, older).await.unwrap();
+        let mut newer = get(&work, &w, &work_c).await;
+        newer.title = Some("c work".to_owned());
+        work.update_task(&w, newer).await.unwrap();
+
+        work.delete_task(&w, b1.id.as_deref().unwrap())
+            .await
+            .unwrap();
+
+        let changes = sync(&work, &home, &mut state).await.unwrap();
+        assert_eq!(changes.len(), 5);
+        let work_a = get(&work, &w, a.id.as_deref().unwrap()).await;
+        assert_eq!(work_a.status, Some(TaskStatus::Completed));
+        assert!(work_a.completed.is_some());
+        for (fake, list, id) in [
+            (&work, &w, b.id.clone().unwrap()),
+            (&home, &h, home_b.clone()),
+        ] {
+            let task = get(fake, list, &id).await;
+            assert_eq!(task.title.as_deref(), Some("b renamed"));
+            assert_eq!(task.notes.as_deref(), Some("notes"));
+        }
+        assert_eq!(
+            get(&home, &h, c.id.as_deref().unwrap()).await.title,
+            Some("c work".to_owned())
+        );
+        assert_eq!(get(&home, &h, &home_b1).await.deleted, Some(true));
+        assert!(sync(&work, &home, &mut state).await.unwrap().is_empty());
+
+        // a modification wins over a deletion
+        work.delete_task(&w, a.id.as_deref().unwrap())
+            .await
+            .unwrap();
+        let mut reopened = get(&home, &h, &home_a).await;
+        reopened.status = Some(TaskStatus::NeedsAction);
+        home.update_task(&h, reopened).await.unwrap();
+
+        let changes = sync(&work, &home, &mut state).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].side, Side::Left);
+        assert_eq!(changes[0].kind, ChangeKind::Inserted);
+        let restored = get(&work, &w, &changes[0].task_id).await;
+        assert_eq!(restored.title.as_deref(), Some("a"));
+        assert_eq!(restored.status, Some(TaskStatus::NeedsAction));
+
+        let json = serde_json::to_vec(&state).unwrap();
+        let read = MirrorState::read_json(json.as_slice()).unwrap();
+        assert_eq!(read.pairs.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn mirrors_lists_of_one_account() {
+        let fake = FakeTasks::new();
+        let (left, right) = (
+            tasklist(&fake, "Left").await,
+            tasklist(&fake, "Right").await,
+        );
+        insert(&fake, &left, "a", None).await;
+
+        let mut state = MirrorState::new(&left, &right);
+        sync(&fake, &fake, &mut state).await.unwrap();
+        assert_eq!(titles(&fake, &right).await, [("a".to_owned(), None)]);
+        assert!(sync(&fake, &fake, &mut state).await.unwrap().is_empty());
+
+        let mut itself = MirrorState::new(&left, &left);
+        assert!(sync(&fake, &fake, &mut itself).await.is_err());
+    }
+
+    // Fails the updates of tasks while `fail_updates` is set.
+    struct FailingUpdates {
+        inner: FakeTasks,
+        fail_updates: std::sync::atomic::AtomicBool,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl TasksApi for FailingUpdates {
+        async fn list_tasklists(&self, opt: Option<TasklistsOptions>) -> Result<Tasklists> {
+            self.inner.list_tasklists(opt).await
+        }
+
+        async fn get_tasklist(&self, id: &str) -> Result<Tasklist> {
+            self.inner.get_tasklist(id).await
+        }
+
+        async fn insert_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+            self.inner.insert_tasklist(v).await
+        }
+
+        async fn update_tasklist(&self, v: Tasklist) -> Result<Tasklist> {
+            self.inner.update_tasklist(v).await
+        }
+
+        async fn delete_tasklist(&self, id: &str) -> Result<()> {
+            self.inner.delete_tasklist(id).await
+        }
+
+        async fn patch_tasklist(&self, tasklist_id: &str, v: Tasklist) -> Result<Tasklist> {
+            self.inner.patch_tasklist(tasklist_id, v).await
+        }
+
+        async fn list_tasks(
+            &self,
+            tasklist_id: &str,
+            opt: Option<TaskOptions>,
+            etag: Option<String>,
+        ) -> Result<Option<Tasks>> {
+            self.inner.list_tasks(tasklist_id, opt, etag).await
+        }
+
+        async fn get_task(
+            &self,
+            tasklist_id: &str,
+            task_id: &str,
+            etag: Option<String>,
+        ) -> Result<Option<Task>> {
+            self.inner.get_task(tasklist_id, task_id, etag).await
+        }
+
+        async fn insert_task(
+            &self,
+            tasklist_id: &str,
+            v: Task,
+            opts: Option<TaskInsertOptions>,
+        ) -> Result<Task> {
+            self.inner.insert_task(tasklist_id, v, opts).await
+        }
+
+        async fn update_task(&self, tasklist_id: &str, v: Task) -> Result<Task> {
+            if self.fail_updates.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(ResponseError("backend error".to_owned()));
+            }
+            self.inner.update_task(tasklist_id, v).await
+        }
+
+        async fn delete_task(&self, tasklist_id: &str, task_id: &str) -> Result<()> {
+            self.inner.delete_task(tasklist_id, task_id).await
+        }
+
+        async fn clear_tasks(&self, tasklist_id: &str) -> Result<()> {
+            self.inner.clear_tasks(tasklist_id).await
+        }
+
+        async fn move_task(
+            &self,
+            tasklist_id: &str,
+            task_id: &str,
+            opts: TaskInsertOptions,
+        ) -> Result<Task> {
+            self.inner.move_task(tasklist_id, task_id, opts).await
+        }
+
+        async fn patch_task(&self, tasklist_id: &str, task_id: &str, v: Task) -> Result<Task> {
+            self.inner.patch_task(tasklist_id, task_id, v).await
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_a_half_written_merge() {
+        use std::sync::atomic::Ordering;
+
+        let left = FakeTasks::new();
+        let right = FailingUpdates {
+            inner: FakeTasks::new(),
+            fail_updates: Default::default(),
+        };
+        let (l, r) = (
+            tasklist(&left, "Shared").await,
+            tasklist(&right.inner, "Shared").await,
+        );
+        let a = insert(&left, &l, "a", None).await;
+        let mut state = MirrorState::new(&l, &r);
+        sync(&left, &right, &mut state).await.unwrap();
+        let right_a = state
+            .mirror_of(Side::Left, a.id.as_deref().unwrap())
+            .unwrap()
+            .to_owned();
+
+        // left renames, right adds notes; the write of the rename to the right fails
+        let mut renamed = get(&left, &l, a.id.as_deref().unwrap()).await;
+        renamed.title = Some("a renamed".to_owned());
+        left.update_task(&l, renamed).await.unwrap();
+        let mut noted = get(&right.inner, &r, &right_a).await;
+        noted.notes = Some("notes".to_owned());
+        right.inner.update_task(&r, noted).await.unwrap();
+
+        right.fail_updates.store(true, Ordering::Relaxed);
+        assert!(sync(&left, &right, &mut state).await.is_err());
+        let left_a = get(&left, &l, a.id.as_deref().unwrap()).await;
+        assert_eq!(left_a.notes.as_deref(), Some("notes"));
+
+        right.fail_updates.store(false, Ordering::Relaxed);
+        let changes = sync(&left, &right, &mut state).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].side, Side::Right);
+        assert_eq!(changes[0].kind, ChangeKind::Updated(vec![Field::Title]));
+        let right_a = get(&right.inner, &r, &right_a).await;
+        assert_eq!(right_a.title.as_deref(), Some("a renamed"));
+        assert_eq!(right_a.notes.as_deref(), Some("notes"));
+        assert!(sync(&left, &right, &mut state).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+//! Services of many accounts.
+//!
+//! An [`AccountPool`] holds a [`Service`] for each account, with its own token provider,
+//! request budget and ETag cache. The services share one reqwest client, and so one
+//! connection pool. [`AccountPool::run`] runs an operation across the accounts concurrently.
+//!
+//! ```no_run
+//! # async fn example() -> gtasks::Result<()> {
+//! use std::time::Duration;
+//! use gtasks::pool::{AccountPool, Budget};
+//!
+//! let mut pool = AccountPool::new()?
+//!     .with_concurrency(4)
+//!     .with_budget(Budget { requests: 10, per: Duration::from_secs(1) });
+//! pool.insert("alice", || Ok("Bearer alice-token".to_owned()));
+//! pool.insert("bob", || Ok("Bearer bob-token".to_owned()));
+//!
+//! let tasklists = pool.run(|_, service| service.list_tasklists(None)).await;
+//! for (account, result) in tasklists {
+//!     println!("{}: {} task lists", account, result?.items.len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures::stream::{self, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, ETAG, IF_NONE_MATCH};
+use reqwest::{Method, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next, Result as MidWareResult};
+use task_local_extensions::Extensions;
+use tokio::time::{self, Instant};
+
+use crate::errors::Result;
+use crate::http::{self, TokenProvider};
+use crate::Service;
+
+/// Number of accounts [`AccountPool::run`] handles at the same time, unless set otherwise.
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Number of requests an account may send in a period.
+/// The budget refills continuously, and requests beyond it wait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+/// Services of many accounts, sharing one connection pool.
+pub struct AccountPool {
+    client: reqwest::Client,
+    services: BTreeMap<String, Service>,
+    concurrency: usize,
+    budget: Option<Budget>,
+    base_url: Option<String>,
+}
+
+impl AccountPool {
+    /// Creates an empty pool.
+    pub fn new() -> Result<Self> {
+        Ok(AccountPool {
+            client: http::reqwest_client()?,
+            services: BTreeMap::new(),
+            concurrency: DEFAULT_CONCURRENCY,
+            budget: None,
+            base_url: None,
+        })
+    }
+
+    /// Sets how many accounts [`AccountPool::run`] handles at the same time.
+    pub fn with_concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Sets the budget of the accounts inserted afterwards, which are unlimited by default.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Sets the root URL of the API for the accounts inserted afterwards, e.g. of a local test server.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.to_owned());
+        self
+    }
+
+    /// Adds the account with the budget of the pool, replacing an account of the same name.
+    pub fn insert<P>(&mut self, account: &str, token_provider: P)
+    where
+        P: TokenProvider,
+    {
+        self.insert_with_budget(account, token_provider, self.budget)
+    }
+
+    /// Adds the account with its own budget, `None` for unlimited requests.
+    pub fn insert_with_budget<P>(
+        &mut self,
+        account: &str,
+        token_provider: P,
+        budget: Option<Budget>,
+    ) where
+        P: TokenProvider,
+    {
+        let mut middleware: Vec<Arc<dyn Middleware>> = Vec::new();
+        if let Some(budget) = budget {
+            middleware.push(Arc::new(RateLimit::new(budget)));
+        }
+        middleware.push(Arc::new(EtagCache::default()));
+
+        let mut service = Service::with_client(self.client.clone(), token_provider, middleware);
+        if let Some(base_url) = self.base_url.as_deref() {
+            service = service.with_base_url(base_url);
+        }
+        self.services.insert(account.to_owned(), service);
+    }
+
+    /// Removes the account, returning its service.
+    pub fn remove(&mut self, account: &str) -> Option<Service> {
+        self.services.remove(account)
+    }
+
+    /// Returns the service of the account.
+    pub fn get(&self, account: &str) -> Option<&Service> {
+        self.services.get(account)
+    }
+
+    /// Returns the names of the accounts, in order.
+    pub fn accounts(&self) -> impl Iterator<Item = &str> {
+        self.services.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.services.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.services.is_empty()
+    }
+
+    /// Runs the operation for every account, at most as many at the same time as the concurrency
+    /// limit. Returns the result of each account, also when the operation failed for others.
+    pub async fn run<'a, F, Fut, T>(&'a self, op: F) -> BTreeMap<String, Result<T>>
+    where
+        F: Fn(&'a str, &'a Service) -> Fut,
+        Fut: Future<Output = Result<T>> + 'a,
+    {
+        let op = &op;
+        stream::iter(self.services.iter())
+            .map(|(account, service)| async move {
+                (account.clone(), op(account.as_str(), service).await)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await
+    }
+}
+
+// Token bucket holding the requests an account may send right away.
+struct RateLimit {
+    budget: Budget,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimit {
+    fn new(budget: Budget) -> Self {
+        RateLimit {
+            budget,
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(budget.requests),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    // Takes a token, returning how long to wait for one when the bucket is empty.
+    fn take(&self) -> Option<Duration> {
+        let capacity = f64::from(self.budget.requests.max(1));
+        let rate = capacity / self.budget.per.as_secs_f64().max(f64::EPSILON);
+
+        let mut bucket = self.bucket.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return None;
+        }
+        Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RateLimit {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MidWareResult<Response> {
+        while let Some(wait) = self.take() {
+            time::sleep(wait).await;
+        }
+        next.run(req, extensions).await
+    }
+}
+
+// Cache of the GET responses, revalidated with their ETag: a 304 response is answered from
+// the cache. Requests which already have an If-None-Match header are left to the caller.
+#[derive(Default)]
+struct EtagCache {
+    entries: Mutex<HashMap<String, Cached>>,
+}
+
+struct Cached {
+    etag: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl EtagCache {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Cached>> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for EtagCache {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MidWareResult<Response> {
+        if req.method() != Method::GET || req.headers().contains_key(IF_NONE_MATCH) {
+            return next.run(req, extensions).await;
+        }
+
+        let url = req.url().to_string();
+        let etag = self.lock().get(&url).map(|cached| cached.etag.clone());
+        if let Some(etag) = etag {
+            let value = HeaderValue::from_str(&etag).map_err(|err| anyhow!(err))?;
+            req.headers_mut().insert(IF_NONE_MATCH, value);
+        }
+
+        let resp = next.run(req, extensions).await?;
+        match resp.status() {
+            StatusCode::NOT_MODIFIED => match self.lock().get(&url) {
+                Some(cached) => {
+                    response(StatusCode::OK, cached.headers.clone(), cached.body.clone())
+                }
+                None => Ok(resp),
+            },
+            StatusCode::OK => {
+                let headers = resp.headers().clone();
+                let body = resp.bytes().await?.to_vec();
+                if let Some(etag) = etag_of(&headers, &body) {
+                    let cached = Cached {
+                        etag,
+                        headers: headers.clone(),
+                        body: body.clone(),
+                    };
+                    self.lock().insert(url, cached);
+                }
+                response(StatusCode::OK, headers, body)
+            }
+            _ => Ok(resp),
+        }
+    }
+}
+
+// Returns the ETag header, or the etag of the resource in the body.
+fn etag_of(headers: &HeaderMap, body: &[u8]) -> Option<String> {
+    if let Some(etag) = headers.get(ETAG).and_then(|v| v.to_str().ok()) {
+        return Some(etag.to_owned());
+    }
+    let resource: serde_json::Value = serde_json::from_slice(body).ok()?;
+    resource["etag"].as_str().map(str::to_owned)
+}
+
+fn response(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> MidWareResult<Response> {
+    let mut builder = ::http::Response::builder().status(status);
+    if let Some(response_headers) = builder.headers_mut() {
+        *response_headers = headers;
+    }
+    let resp = builder.body(body).map_err(|err| anyhow!(err))?;
+    Ok(Response::from(resp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_refills_over_time() {
+        let limit = RateLimit::new(Budget {
+            requests: 2,
+            per: Duration::from_secs(1),
+        });
+        assert_eq!(limit.take(), None);
+        assert_eq!(limit.take(), None);
+        assert_eq!(limit.take(), Some(Duration::from_millis(500)));
+
+        time::advance(Duration::from_millis(500)).await;
+        assert_eq!(limit.take(), None);
+        assert!(limit.take().is_some());
+    }
+}
@@ -0,0 +1,603 @@
+//! Query language over tasks, e.g. `status:open due<today tag:work title~"deploy" list:Inbox`.
+//!
+//! A query is a list of terms separated by spaces, which a task must all match:
+//!
+//! | Term | Matches |
+//! |------|---------|
+//! | `status:open`, `status:completed` | tasks which need action, completed tasks |
+//! | `due<DATE`, `due<=`, `due>`, `due>=`, `due:DATE`, `due:none` | the due date, or no due date |
+//! | `completed<DATE`, ..., `updated>=DATE`, ... | the completion or modification date |
+//! | `tag:NAME` | tasks with the tag, see [`crate::metadata`] |
+//! | `priority:LEVEL`, `priority>=LEVEL`, ... | the priority, `high` above `medium` above `low` |
+//! | `title:TEXT`, `notes:TEXT` | the whole title or notes, ignoring the case |
+//! | `title~TEXT`, `notes~TEXT` | a part of the title or notes, ignoring the case |
+//! | `list:NAME` | the task lists resolved by [`crate::resolve`]; several are all searched |
+//! | `TEXT` | a part of the title or the notes |
+//!
+//! A date is `today`, `tomorrow`, `yesterday`, a number of days or weeks from today such as
+//! `+3d` or `-2w`, or `YYYY-MM-DD`. Values with spaces are quoted, e.g. `title:"a b"`.
+//! A term starting with `-` matches the tasks which do not match the term.
+//!
+//! [`Query::options`] compiles the terms the API can filter by into [`TaskOptions`]. They only
+//! narrow the listing: every term is checked on the client, so the results do not depend on
+//! how the server treats the bounds.
+
+use std::cell::OnceCell;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use crate::errors::{Result, TasksError};
+use crate::footer;
+use crate::metadata::{Metadata, Priority};
+use crate::snapshot::TasklistSnapshot;
+use crate::{resolve, tasklists, tasks, Task, TaskOptions, TaskStatus, TasksApi};
+
+/// Number of task lists searched at the same time.
+const CONCURRENCY: usize = 4;
+
+/// Parsed query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    /// Names of the task lists to search, all of them when empty.
+    pub lists: Vec<String>,
+
+    /// Terms the tasks must all match.
+    pub terms: Vec<Term>,
+}
+
+/// Condition on a task, negated when `negated` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub negated: bool,
+    pub condition: Condition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Status(TaskStatus),
+    Due(Comparison<NaiveDate>),
+    NoDue,
+    Completed(Comparison<NaiveDate>),
+    Updated(Comparison<NaiveDate>),
+    Tag(String),
+    Priority(Comparison<Priority>),
+    Title(Text),
+    Notes(Text),
+    /// Part of the title or the notes.
+    Contains(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison<T> {
+    Eq(T),
+    Lt(T),
+    Le(T),
+    Gt(T),
+    Ge(T),
+}
+
+/// Text compared ignoring the case, in lower case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Text {
+    Equals(String),
+    Contains(String),
+}
+
+impl Query {
+    /// Parses the query, reckoning the relative dates from the current day in UTC.
+    pub fn parse(input: &str) -> Result<Query> {
+        Query::parse_at(input, Utc::now().date_naive())
+    }
+
+    /// Parses the query, reckoning the relative dates from the given day.
+    pub fn parse_at(input: &str, today: NaiveDate) -> Result<Query> {
+        let mut query = Query {
+            lists: Vec::new(),
+            terms: Vec::new(),
+        };
+
+        for token in tokenize(input)? {
+            let invalid = || invalid_term(&token.text);
+            let (key, op, value) = match token.op {
+                Some((key, op, value)) => (key, op, value),
+                None => {
+                    query.terms.push(Term {
+                        negated: token.negated,
+                        condition: Condition::Contains(token.text.to_lowercase()),
+                    });
+                    continue;
+                }
+            };
+
+            let condition = match (key.to_ascii_lowercase().as_str(), op.as_str()) {
+                ("list", ":") if !token.negated => {
+                    query.lists.push(value);
+                    continue;
+                }
+                ("status", ":") => Condition::Status(parse_status(&value).ok_or_else(invalid)?),
+                ("due", ":") if value.eq_ignore_ascii_case("none") => Condition::NoDue,
+                ("due", _) => {
+                    Condition::Due(compare(&op, parse_date(&value, today)).ok_or_else(invalid)?)
+                }
+                ("completed", _) => Condition::Completed(
+                    compare(&op, parse_date(&value, today)).ok_or_else(invalid)?,
+                ),
+                ("updated", _) => {
+                    Condition::Updated(compare(&op, parse_date(&value, today)).ok_or_else(invalid)?)
+                }
+                ("tag", ":") => Condition::Tag(value),
+                ("priority", _) => {
+                    Condition::Priority(compare(&op, value.parse().ok()).ok_or_else(invalid)?)
+                }
+                ("title", ":") => Condition::Title(Text::Equals(value.to_lowercase())),
+                ("title", "~") => Condition::Title(Text::Contains(value.to_lowercase())),
+                ("notes", ":") => Condition::Notes(Text::Equals(value.to_lowercase())),
+                ("notes", "~") => Condition::Notes(Text::Contains(value.to_lowercase())),
+                _ => return Err(invalid()),
+            };
+            query.terms.push(Term {
+                negated: token.negated,
+                condition,
+            });
+        }
+
+        Ok(query)
+    }
+
+    /// Returns the options which let the API filter out tasks that cannot match.
+    pub fn options(&self) -> TaskOptions {
+        let mut opts = TaskOptions {
+            max_results: Some(100),
+            ..Default::default()
+        };
+        let (mut completed_min, mut completed_max) = (None, None);
+
+        for term in self.terms.iter().filter(|term| !term.negated) {
+            match &term.condition {
+                Condition::Status(TaskStatus::NeedsAction) => opts.show_completed = Some(false),
+                Condition::Status(TaskStatus::Completed) => {
+                    opts.show_completed = Some(true);
+                    opts.show_hidden = Some(true);
+                }
+                Condition::Due(comparison) => {
+                    let (min, max) = bounds(comparison);
+                    opts.due_min = later(opts.due_min, min);
+                    opts.due_max = earlier(opts.due_max, max);
+                }
+                Condition::Completed(comparison) => {
+                    let (min, max) = bounds(comparison);
+                    completed_min = later(completed_min, min);
+                    completed_max = earlier(completed_max, max);
+                    opts.show_completed = Some(true);
+                    opts.show_hidden = Some(true);
+                }
+                Condition::Updated(comparison) => {
+                    let (min, _) = bounds(comparison);
+                    opts.updated_min = later(opts.updated_min, min);
+                }
+                _ => {}
+            }
+        }
+
+        opts.completed_min = completed_min.map(|t| t.to_rfc3339());
+        opts.completed_max = completed_max.map(|t| t.to_rfc3339());
+        opts
+    }
+
+    /// Whether the task matches all the terms.
+    pub fn matches(&self, task: &Task) -> bool {
+        let metadata = OnceCell::new();
+        self.terms
+            .iter()
+            .all(|term| term.condition.matches(task, &metadata) != term.negated)
+    }
+}
+
+impl Condition {
+    // The metadata is read from the notes the first time a condition needs it.
+    fn matches(&self, task: &Task, metadata: &OnceCell<Metadata>) -> bool {
+        let metadata = || metadata.get_or_init(|| Metadata::of(task).unwrap_or_default());
+        let date = |value: Option<DateTime<Utc>>| value.map(|v| v.date_naive());
+        let title = task.title.as_deref();
+        let notes = task.notes.as_deref().map(|notes| footer::split(notes).0);
+        match self {
+            Condition::Status(status) => task.status.unwrap_or(TaskStatus::NeedsAction) == *status,
+            Condition::Due(comparison) => date(task.due).is_some_and(|d| comparison.holds(&d)),
+            Condition::NoDue => task.due.is_none(),
+            Condition::Completed(comparison) => {
+                date(task.completed).is_some_and(|d| comparison.holds(&d))
+            }
+            Condition::Updated(comparison) => {
+                date(task.updated).is_some_and(|d| comparison.holds(&d))
+            }
+            Condition::Tag(tag) => metadata().has_tag(tag),
+            // a more urgent priority is a lower one in the order of `Priority`
+            Condition::Priority(comparison) => metadata()
+                .priority
+                .is_some_and(|p| comparison.reversed().holds(&p)),
+            Condition::Title(text) => text.matches(title),
+            Condition::Notes(text) => text.matches(notes),
+            Condition::Contains(part) => [title, notes]
+                .into_iter()
+                .flatten()
+                .any(|text| text.to_lowercase().contains(part.as_str())),
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy> Comparison<T> {
+    fn holds(&self, value: &T) -> bool {
+        match self {
+            Comparison::Eq(v) => value == v,
+            Comparison::Lt(v) => value < v,
+            Comparison::Le(v) => value <= v,
+            Comparison::Gt(v) => value > v,
+            Comparison::Ge(v) => value >= v,
+        }
+    }
+
+    fn reversed(&self) -> Comparison<T> {
+        match *self {
+            Comparison::Eq(v) => Comparison::Eq(v),
+            Comparison::Lt(v) => Comparison::Gt(v),
+            Comparison::Le(v) => Comparison::Ge(v),
+            Comparison::Gt(v) => Comparison::Lt(v),
+            Comparison::Ge(v) => Comparison::Le(v),
+        }
+    }
+}
+
+impl Text {
+    fn matches(&self, value: Option<&str>) -> bool {
+        let value = value.unwrap_or_default().trim().to_lowercase();
+        match self {
+            Text::Equals(text) => value == *text,
+            Text::Contains(text) => value.contains(text.as_str()),
+        }
+    }
+}
+
+/// Searches the task lists of the query, all of them when it names none, several at the
+/// same time. Returns the task lists with the matching tasks, in the order of the API.
+pub async fn run<A: TasksApi + ?Sized>(api: &A, query: &Query) -> Result<Vec<TasklistSnapshot>> {
+    let all = tasklists::list_all(api).await?;
+    let mut selected = Vec::new();
+    if query.lists.is_empty() {
+        selected = all.iter().collect();
+    } else {
+        for name in query.lists.iter() {
+            let tasklist = resolve::resolve(&all, name)?;
+            if !selected.iter().any(|t| std::ptr::eq(*t, tasklist)) {
+                selected.push(tasklist);
+            }
+        }
+    }
+
+    let opts = query.options();
+    stream::iter(selected)
+        .map(|tasklist| {
+            let opts = opts.clone();
+            async move {
+                let id = tasklist.id.as_deref().unwrap_or_default();
+                let mut tasks = tasks::list_all(api, id, opts).await?;
+                tasks.retain(|task| query.matches(task));
+                Ok::<_, TasksError>(TasklistSnapshot {
+                    tasklist: tasklist.clone(),
+                    tasks,
+                })
+            }
+        })
+        .buffered(CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+struct Token {
+    text: String,
+    negated: bool,
+    /// Key, operator and value of a `key:value` term.
+    op: Option<(String, String, String)>,
+}
+
+// Splits the input at the spaces outside quotes, and each term at its first operator
+// outside quotes.
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(tokens);
+        }
+
+        let negated = chars.next_if_eq(&'-').is_some();
+        let (mut text, mut key, mut op) = (String::new(), None, String::new());
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' if quoted => text.extend(chars.next()),
+                c if c.is_whitespace() && !quoted => break,
+                ':' | '~' | '<' | '>' | '=' if !quoted && key.is_none() && !text.is_empty() => {
+                    op.push(c);
+                    if matches!(c, '<' | '>') {
+                        op.extend(chars.next_if_eq(&'='));
+                    }
+                    key = Some(std::mem::take(&mut text));
+                }
+                c => text.push(c),
+            }
+        }
+        if quoted {
+            return Err(invalid_term(input));
+        }
+
+        let op = key.map(|key| {
+            let op = if op == "=" { ":".to_owned() } else { op };
+            (key, op, text.clone())
+        });
+        let text = match op.as_ref() {
+            Some((key, op, value)) => format!("{}{}{}", key, op, value),
+            None => text,
+        };
+        if text.is_empty() {
+            return Err(invalid_term(input));
+        }
+        tokens.push(Token { text, negated, op });
+    }
+}
+
+fn invalid_term(term: &str) -> TasksError {
+    TasksError::ParseError(format!("invalid query term: {}", term))
+}
+
+fn parse_status(value: &str) -> Option<TaskStatus> {
+    match value.to_ascii_lowercase().as_str() {
+        "open" | "todo" | "needsaction" => Some(TaskStatus::NeedsAction),
+        "completed" | "done" => Some(TaskStatus::Completed),
+        _ => None,
+    }
+}
+
+fn parse_date(value: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match value.to_ascii_lowercase().as_str() {
+        "today" => return Some(today),
+        "tomorrow" => return today.checked_add_days(Days::new(1)),
+        "yesterday" => return today.checked_sub_days(Days::new(1)),
+        _ => {}
+    }
+
+    if let Some((sign, offset)) = value
+        .strip_prefix('+')
+        .map(|o| (1, o))
+        .or_else(|| value.strip_prefix('-').map(|o| (-1, o)))
+    {
+        let (number, unit) = match offset.strip_suffix('w') {
+            Some(number) => (number, 7),
+            None => (offset.strip_suffix('d')?, 1),
+        };
+        let days = Days::new(number.parse::<u64>().ok()?.checked_mul(unit)?);
+        return match sign {
+            1 => today.checked_add_days(days),
+            _ => today.checked_sub_days(days),
+        };
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+fn compare<T>(op: &str, value: Option<T>) -> Option<Comparison<T>> {
+    let value = value?;
+    match op {
+        ":" => Some(Comparison::Eq(value)),
+        "<" => Some(Comparison::Lt(value)),
+        "<=" => Some(Comparison::Le(value)),
+        ">" => Some(Comparison::Gt(value)),
+        ">=" => Some(Comparison::Ge(value)),
+        _ => None,
+    }
+}
+
+// Bounds of the times on the days matching the comparison, inclusive as the API treats them.
+fn bounds(comparison: &Comparison<NaiveDate>) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let start = |date: &NaiveDate| Some(date.and_time(NaiveTime::MIN).and_utc());
+    let next = |date: &NaiveDate| date.checked_add_days(Days::new(1)).and_then(|d| start(&d));
+    match comparison {
+        Comparison::Eq(d) => (start(d), next(d)),
+        Comparison::Lt(d) => (None, start(d)),
+        Comparison::Le(d) => (None, next(d)),
+        Comparison::Gt(d) => (next(d), None),
+        Comparison::Ge(d) => (start(d), None),
+    }
+}
+
+fn later(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    a.max(b)
+}
+
+fn earlier(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Metadata;
+    use crate::{FakeTasks, Tasklist};
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn today() -> NaiveDate {
+        date("2026-10-18")
+    }
+
+    fn midnight(s: &str) -> DateTime<Utc> {
+        date(s).and_time(NaiveTime::MIN).and_utc()
+    }
+
+    #[test]
+    fn parses_terms() {
+        let query = Query::parse_at(
+            r#"status:open due<today tag:work title~"Deploy now" list:Inbox -priority>=medium fix"#,
+            today(),
+        )
+        .unwrap();
+        assert_eq!(query.lists, ["Inbox"]);
+        let conditions: Vec<_> = query.terms.iter().map(|t| &t.condition).collect();
+        assert_eq!(
+            conditions,
+            [
+                &Condition::Status(TaskStatus::NeedsAction),
+                &Condition::Due(Comparison::Lt(today())),
+                &Condition::Tag("work".to_owned()),
+                &Condition::Title(Text::Contains("deploy now".to_owned())),
+                &Condition::Priority(Comparison::Ge(Priority::Medium)),
+                &Condition::Contains("fix".to_owned()),
+            ]
+        );
+        assert!(query.terms[4].negated);
+
+        let dates =
+            Query::parse_at("due>=+1w completed:yesterday updated>2026-01-31", today()).unwrap();
+        assert_eq!(
+            dates.terms[0].condition,
+            Condition::Due(Comparison::Ge(date("2026-10-25")))
+        );
+        assert_eq!(
+            dates.terms[1].condition,
+            Condition::Completed(Comparison::Eq(date("2026-10-17")))
+        );
+        assert_eq!(
+            dates.terms[2].condition,
+            Condition::Updated(Comparison::Gt(date("2026-01-31")))
+        );
+
+        for invalid in [
+            "status:maybe",
+            "due<soon",
+            "color:red",
+            "title<a",
+            "-list:Inbox",
+            "title:\"open",
+            "due<+3x",
+        ] {
+            assert!(Query::parse_at(invalid, today()).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn compiles_server_options() {
+        let query = Query::parse_at("status:open due>=2026-10-01 due<today", today()).unwrap();
+        let opts = query.options();
+        assert_eq!(opts.show_completed, Some(false));
+        assert_eq!(opts.due_min, Some(midnight("2026-10-01")));
+        assert_eq!(opts.due_max, Some(midnight("2026-10-18")));
+
+        let done = Query::parse_at("completed:today -due:none tag:x", today()).unwrap();
+        let opts = done.options();
+        assert_eq!(
+            (opts.show_completed, opts.show_hidden),
+            (Some(true), Some(true))
+        );
+        assert_eq!(
+            opts.completed_min,
+            Some(midnight("2026-10-18").to_rfc3339())
+        );
+        assert_eq!(
+            opts.completed_max,
+            Some(midnight("2026-10-19").to_rfc3339())
+        );
+        assert_eq!((opts.due_min, opts.due_max), (None, None));
+    }
+
+    #[test]
+    fn matches_on_the_client() {
+        let mut task = Task {
+            title: Some("Deploy the release".to_owned()),
+            notes: Some("after the review".to_owned()),
+            due: Some(midnight("2026-10-17")),
+            status: Some(TaskStatus::NeedsAction),
+            ..Default::default()
+        };
+        Metadata {
+            tags: vec!["Work".to_owned()],
+            priority: Some(crate::metadata::Priority::High),
+            estimate: None,
+        }
+        .apply(&mut task);
+
+        let matches = |query: &str| Query::parse_at(query, today()).unwrap().matches(&task);
+        assert!(matches(r#"status:open due<today tag:work title~"deploy""#));
+        assert!(matches("priority>=medium review -tag:home"));
+        assert!(matches("notes:\"after the review\""));
+        assert!(!matches("status:completed"));
+        assert!(!matches("due:none"));
+        assert!(!matches("priority<medium"));
+        assert!(!matches("-title~release"));
+        assert!(!matches("gtasks"), "the footer is not text");
+    }
+
+    #[tokio::test]
+    async fn runs_across_lists() {
+        let fake = FakeTasks::new();
+        for (list, titles) in [
+            ("Inbox", ["deploy api", "buy milk"]),
+            ("Work", ["deploy web", "review"]),
+        ] {
+            let tasklist = Tasklist {
+                title: Some(list.to_owned()),
+                ..Default::default()
+            };
+            let id = fake.insert_tasklist(tasklist).await.unwrap().id.unwrap();
+            for title in titles {
+                let task = Task {
+                    title: Some(title.to_owned()),
+                    ..Default::default()
+                };
+                fake.insert_task(&id, task, None).await.unwrap();
+            }
+        }
+
+        let titles = |lists: Vec<TasklistSnapshot>| -> Vec<(String, Vec<String>)> {
+            lists
+                .into_iter()
+                .map(|l| {
+                    let tasks = l.tasks.into_iter().filter_map(|t| t.title).collect();
+                    (l.tasklist.title.unwrap(), tasks)
+                })
+                .collect()
+        };
+
+        let all = run(&fake, &Query::parse("title~deploy").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            titles(all),
+            [
+                ("My Tasks".to_owned(), vec![]),
+                ("Inbox".to_owned(), vec!["deploy api".to_owned()]),
+                ("Work".to_owned(), vec!["deploy web".to_owned()]),
+            ]
+        );
+
+        let named = Query::parse("deploy list:work list:Work list:inbox").unwrap();
+        let named = run(&fake, &named).await.unwrap();
+        assert_eq!(
+            titles(named),
+            [
+                ("Work".to_owned(), vec!["deploy web".to_owned()]),
+                ("Inbox".to_owned(), vec!["deploy api".to_owned()]),
+            ]
+        );
+
+        let missing = Query::parse("list:Home").unwrap();
+        assert!(run(&fake, &missing).await.is_err());
+    }
+}
@@ -0,0 +1,709 @@
+//! Recurring tasks, which the Google Tasks API does not support.
+//!
+//! The recurrence of a task is kept in a footer at the end of its notes, after a `-- gtasks`
+//! line, as iCalendar (RFC 5545) properties:
+//!
+//! ```text
+//! Water the plants
+//!
+//! -- gtasks
+//! RRULE:FREQ=WEEKLY;BYDAY=MO,TH
+//! DTSTART:20261019
+//! X-GTASKS-SERIES:task1
+//! ```
+//!
+//! [`schedule`] creates the next occurrence of every completed recurring task of a task list.
+//! The occurrences of a recurrence share the `X-GTASKS-SERIES` id, and a completed occurrence
+//! records the id of the next one in `X-GTASKS-NEXT`, so that it is created only once.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveTime, Utc, Weekday};
+
+use crate::errors::{
+    Result,
+    TasksError::{ParseError, ResponseError},
+};
+use crate::footer::{self, Footer};
+use crate::{tasks, Task, TaskInsertOptions, TaskOptions, TaskStatus, TasksApi};
+
+const SERIES_PROP: &str = "X-GTASKS-SERIES";
+const NEXT_PROP: &str = "X-GTASKS-NEXT";
+
+/// Longest run of days without an occurrence, per unit of the interval, before the rule
+/// is considered to have no more occurrences, e.g. 8 years between two February 29ths.
+const MAX_GAP_DAYS: u64 = 8 * 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Day of the week of a `BYDAY` rule part, with the optional ordinal of the day in the month
+/// of a monthly or yearly rule, e.g. `-1FR` for the last Friday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekdayNum {
+    pub ordinal: Option<i32>,
+    pub weekday: Weekday,
+}
+
+/// Recurrence rule (RRULE), with the `FREQ`, `INTERVAL`, `COUNT`, `UNTIL`, `BYDAY`,
+/// `BYMONTHDAY` and `BYMONTH` rule parts. The times are ignored, as the due dates of tasks
+/// have no time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDate>,
+    pub by_day: Vec<WeekdayNum>,
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>,
+}
+
+/// Recurrence of a task, kept in the footer of its notes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub rule: Rule,
+
+    /// First day of the recurrence, from which the interval and the count are reckoned.
+    pub start: NaiveDate,
+
+    /// Id shared by the occurrences, the id of the first one.
+    pub series: Option<String>,
+
+    /// Id of the next occurrence, once created.
+    pub next: Option<String>,
+}
+
+impl Rule {
+    pub fn new(frequency: Frequency) -> Self {
+        Rule {
+            frequency,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+        }
+    }
+
+    /// Returns the first occurrence after the date, of the recurrence starting at `start`.
+    pub fn next_after(&self, start: NaiveDate, date: NaiveDate) -> Option<NaiveDate> {
+        self.occurrences(start).find(|d| *d > date)
+    }
+
+    /// Returns the occurrences of the recurrence starting at `start`, in order.
+    pub fn occurrences(&self, start: NaiveDate) -> impl Iterator<Item = NaiveDate> + '_ {
+        let max_gap = MAX_GAP_DAYS * u64::from(self.interval.max(1));
+        let mut day = Some(start);
+        let mut last = start;
+
+        let dates = std::iter::from_fn(move || {
+            while let Some(date) = day {
+                if self.until.is_some_and(|until| date > until)
+                    || (date - last).num_days() as u64 > max_gap
+                {
+                    return None;
+                }
+                day = date.checked_add_days(Days::new(1));
+                if self.matches(start, date) {
+                    last = date;
+                    return Some(date);
+                }
+            }
+            None
+        });
+        dates.take(self.count.map_or(usize::MAX, |count| count as usize))
+    }
+
+    // Whether the date is in a period of the interval and matches the rule parts.
+    fn matches(&self, start: NaiveDate, date: NaiveDate) -> bool {
+        let interval = i64::from(self.interval.max(1));
+        let periods = match self.frequency {
+            Frequency::Daily => (date - start).num_days(),
+            Frequency::Weekly => (week_start(date) - week_start(start)).num_days() / 7,
+            Frequency::Monthly => months(date) - months(start),
+            Frequency::Yearly => i64::from(date.year() - start.year()),
+        };
+        if periods % interval != 0 {
+            return false;
+        }
+
+        if !self.by_month.is_empty() && !self.by_month.contains(&date.month()) {
+            return false;
+        }
+        if !self.by_month_day.is_empty()
+            && !self.by_month_day.iter().any(|d| is_month_day(date, *d))
+        {
+            return false;
+        }
+        if !self.by_day.is_empty() {
+            let in_year = self.frequency == Frequency::Yearly && self.by_month.is_empty();
+            let ordinals = matches!(self.frequency, Frequency::Monthly | Frequency::Yearly);
+            return self.by_day.iter().any(|day| {
+                day.weekday == date.weekday()
+                    && (!ordinals || day.ordinal.is_none_or(|n| is_nth(date, n, in_year)))
+            });
+        }
+
+        // without a day of the rule, the day is taken from the start
+        if !self.by_month_day.is_empty() {
+            return true;
+        }
+        match self.frequency {
+            Frequency::Daily => true,
+            Frequency::Weekly => date.weekday() == start.weekday(),
+            Frequency::Monthly => date.day() == start.day(),
+            Frequency::Yearly => {
+                date.day() == start.day()
+                    && (!self.by_month.is_empty() || date.month() == start.month())
+            }
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = crate::TasksError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix("RRULE:").unwrap_or(s);
+        let invalid = |part: &str| ParseError(format!("invalid RRULE part: {}", part));
+
+        let mut frequency = None;
+        let mut rule = Rule::new(Frequency::Daily);
+        for part in s.split(';').filter(|p| !p.is_empty()) {
+            let (name, value) = part.split_once('=').ok_or_else(|| invalid(part))?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid(part)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value.parse().map_err(|_| invalid(part))?;
+                    if rule.interval == 0 {
+                        return Err(invalid(part));
+                    }
+                }
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| invalid(part))?),
+                "UNTIL" => {
+                    let date = value.get(..8).ok_or_else(|| invalid(part))?;
+                    rule.until = Some(parse_date(date).map_err(|_| invalid(part))?);
+                }
+                "BYDAY" => {
+                    rule.by_day = value
+                        .split(',')
+                        .map(|day| parse_weekday_num(day).ok_or_else(|| invalid(part)))
+                        .collect::<Result<_>>()?
+                }
+                "BYMONTHDAY" => {
+                    rule.by_month_day = value
+                        .split(',')
+                        .map(|day| match day.parse::<i32>() {
+                            Ok(d) if d != 0 && (-31..=31).contains(&d) => Ok(d),
+                            _ => Err(invalid(part)),
+                        })
+                        .collect::<Result<_>>()?
+                }
+                "BYMONTH" => {
+                    rule.by_month = value
+                        .split(',')
+                        .map(|month| match month.parse::<u32>() {
+                            Ok(m) if (1..=12).contains(&m) => Ok(m),
+                            _ => Err(invalid(part)),
+                        })
+                        .collect::<Result<_>>()?
+                }
+                "WKST" => {}
+                _ => return Err(invalid(part)),
+            }
+        }
+
+        rule.frequency =
+            frequency.ok_or_else(|| ParseError(format!("RRULE without FREQ: {}", s)))?;
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval > 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%d"))?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|day| match day.ordinal {
+                    Some(n) => format!("{}{}", n, weekday_code(day.weekday)),
+                    None => weekday_code(day.weekday).to_owned(),
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(i32::to_string).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+        if !self.by_month.is_empty() {
+            let months: Vec<String> = self.by_month.iter().map(u32::to_string).collect();
+            write!(f, ";BYMONTH={}", months.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl Recurrence {
+    pub fn new(rule: Rule, start: NaiveDate) -> Self {
+        Recurrence {
+            rule,
+            start,
+            series: None,
+            next: None,
+        }
+    }
+
+    /// Reads the recurrence from the footer of the notes, `None` if the footer has no `RRULE`.
+    pub fn parse(notes: &str) -> Result<Option<Recurrence>> {
+        let footer = Footer::parse(Some(notes));
+        let series = footer.get(SERIES_PROP).map(str::to_owned);
+        let next = footer.get(NEXT_PROP).map(str::to_owned);
+        let rule = match footer.get("RRULE") {
+            Some(rule) => rule.parse()?,
+            None if footer.get("DTSTART").is_some() || series.is_some() => {
+                return Err(ParseError("recurrence without RRULE".to_owned()))
+            }
+            None => return Ok(None),
+        };
+        let start = match footer.get("DTSTART") {
+            Some(start) => parse_date(start.get(..8).unwrap_or(start))?,
+            None => return Err(ParseError("recurrence without DTSTART".to_owned())),
+        };
+
+        Ok(Some(Recurrence {
+            rule,
+            start,
+            series,
+            next,
+        }))
+    }
+
+    /// Reads the recurrence of the task, `None` if the task does not recur.
+    pub fn of(task: &Task) -> Result<Option<Recurrence>> {
+        match task.notes.as_deref() {
+            Some(notes) => Recurrence::parse(notes),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the recurrence to the footer of the notes of the task, replacing a previous one
+    /// and keeping the other properties of the footer.
+    pub fn apply(&self, task: &mut Task) {
+        let mut footer = Footer::parse(task.notes.as_deref());
+        footer.set("RRULE", Some(&self.rule.to_string()));
+        footer.set("DTSTART", Some(&self.start.format("%Y%m%d").to_string()));
+        footer.set(SERIES_PROP, self.series.as_deref());
+        footer.set(NEXT_PROP, self.next.as_deref());
+        task.notes = footer.notes();
+    }
+
+    /// Returns the date of the occurrence after the one of the task: after its due date or,
+    /// without one, after the day it was completed.
+    pub fn next_date(&self, task: &Task) -> Option<NaiveDate> {
+        let date = task
+            .due
+            .or(task.completed)
+            .map(|d| d.date_naive())
+            .unwrap_or_else(|| Utc::now().date_naive());
+        self.rule.next_after(self.start, date)
+    }
+}
+
+/// Returns the notes without the footer.
+pub fn strip_footer(notes: &str) -> &str {
+    footer::split(notes).0
+}
+
+/// Creates the next occurrence of each completed recurring task of the task list which has
+/// none yet, with the title, notes and parent of the completed one and the next due date.
+/// Returns the created tasks.
+///
+/// An occurrence is not created again when the completed task already records it, nor when
+/// a task of the same series with the same due date exists, e.g. when a previous run failed
+/// before recording it.
+pub async fn schedule<A: TasksApi + ?Sized>(api: &A, tasklist_id: &str) -> Result<Vec<Task>> {
+    let opts = TaskOptions {
+        max_results: Some(100),
+        show_completed: Some(true),
+        show_deleted: Some(true),
+        show_hidden: Some(true),
+        ..Default::default()
+    };
+    let tasks = tasks::list_all(api, tasklist_id, opts).await?;
+
+    // due dates of the occurrences of each series, with their ids
+    let mut occurrences: HashMap<(String, NaiveDate), String> = HashMap::new();
+    for task in tasks.iter() {
+        let (id, due) = match (task.id.as_ref(), task.due) {
+            (Some(id), Some(due)) => (id, due),
+            _ => continue,
+        };
+        if let Some(series) = Recurrence::of(task).ok().flatten().and_then(|r| r.series) {
+            occurrences.insert((series, due.date_naive()), id.clone());
+        }
+    }
+
+    let mut created = Vec::new();
+    let mut handled = HashSet::new();
+    for task in tasks.iter() {
+        if task.status != Some(TaskStatus::Completed) || task.deleted == Some(true) {
+            continue;
+        }
+        let (id, mut recurrence) = match (task.id.as_ref(), Recurrence::of(task)) {
+            (Some(id), Ok(Some(recurrence))) if recurrence.next.is_none() => (id, recurrence),
+            _ => continue,
+        };
+        let next_date = match recurrence.next_date(task) {
+            Some(date) => date,
+            None => continue,
+        };
+
+        let series = recurrence.series.get_or_insert_with(|| id.clone()).clone();
+        if !handled.insert((series.clone(), next_date)) {
+            continue;
+        }
+        let next_id = match occurrences.get(&(series.clone(), next_date)) {
+            Some(next_id) => next_id.clone(),
+            None => {
+                let mut next = Task {
+                    title: task.title.clone(),
+                    notes: task.notes.clone(),
+                    status: Some(TaskStatus::NeedsAction),
+                    due: Some(next_date.and_time(NaiveTime::MIN).and_utc()),
+                    ..Default::default()
+                };
+                recurrence.apply(&mut next);
+                let opts = TaskInsertOptions {
+                    parent: task.parent.clone(),
+                    previous: None,
+                };
+                let next = api.insert_task(tasklist_id, next, Some(opts)).await?;
+                let next_id = next
+                    .id
+                    .clone()
+                    .ok_or_else(|| ResponseError("created task has no id".to_owned()))?;
+                created.push(next);
+                next_id
+            }
+        };
+
+        recurrence.next = Some(next_id);
+        let mut done = Task {
+            notes: task.notes.clone(),
+            ..Default::default()
+        };
+        recurrence.apply(&mut done);
+        api.patch_task(tasklist_id, id, done).await?;
+    }
+
+    Ok(created)
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y%m%d")
+        .map_err(|err| ParseError(format!("invalid date {}: {}", value, err)))
+}
+
+fn parse_weekday_num(value: &str) -> Option<WeekdayNum> {
+    let value = value.trim();
+    let split = value.len().checked_sub(2)?;
+    let (ordinal, code) = (value.get(..split)?, value.get(split..)?);
+    let weekday = match code.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let ordinal = match ordinal {
+        "" => None,
+        n => Some(n.parse::<i32>().ok().filter(|n| *n != 0)?),
+    };
+    Some(WeekdayNum { ordinal, weekday })
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+// Monday of the week of the date.
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Days::new(u64::from(date.weekday().num_days_from_monday()))
+}
+
+// Number of months since the year 0.
+fn months(date: NaiveDate) -> i64 {
+    i64::from(date.year()) * 12 + i64::from(date.month0())
+}
+
+// Whether the date is the day of the month, counted from the end when negative.
+fn is_month_day(date: NaiveDate, day: i32) -> bool {
+    if day > 0 {
+        return date.day() == day as u32;
+    }
+    i64::from(last_of_month(date).day()) + i64::from(day) + 1 == i64::from(date.day())
+}
+
+// Whether the date is the nth of its weekday in its month, or in its year, counted from the
+// end when negative.
+fn is_nth(date: NaiveDate, n: i32, in_year: bool) -> bool {
+    let (before, after) = if in_year {
+        let last = NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap_or(date);
+        (date.ordinal0(), last.ordinal0() - date.ordinal0())
+    } else {
+        (date.day0(), last_of_month(date).day() - date.day())
+    };
+    if n > 0 {
+        before / 7 + 1 == n as u32
+    } else {
+        after / 7 + 1 == n.unsigned_abs()
+    }
+}
+
+fn last_of_month(date: NaiveDate) -> NaiveDate {
+    let first = date.with_day(1).unwrap_or(date);
+    (first + Months::new(1)).pred_opt().unwrap_or(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeTasks, Tasklist};
+
+    fn date(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    fn dates(rule: &str, start: &str, n: usize) -> Vec<String> {
+        let rule: Rule = rule.parse().unwrap();
+        rule.occurrences(date(start))
+            .take(n)
+            .map(|d| d.format("%Y%m%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parses_and_formats_rules() {
+        let rule: Rule = "RRULE:FREQ=MONTHLY;INTERVAL=2;COUNT=5;BYDAY=-1FR,2MO"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.frequency, Frequency::Monthly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(
+            rule.by_day[0],
+            WeekdayNum {
+                ordinal: Some(-1),
+                weekday: Weekday::Fri
+            }
+        );
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=MONTHLY;INTERVAL=2;COUNT=5;BYDAY=-1FR,2MO"
+        );
+
+        let until: Rule = "FREQ=DAILY;UNTIL=20261231T235959Z".parse().unwrap();
+        assert_eq!(until.until, Some(date("20261231")));
+
+        for invalid in [
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=WEEKLY;BYDAY=XX",
+        ] {
+            assert!(invalid.parse::<Rule>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn computes_occurrences() {
+        assert_eq!(
+            dates("FREQ=WEEKLY;BYDAY=MO,TH", "20261019", 4),
+            ["20261019", "20261022", "20261026", "20261029"]
+        );
+        assert_eq!(
+            dates("FREQ=WEEKLY;INTERVAL=2", "20261021", 3),
+            ["20261021", "20261104", "20261118"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=-1FR", "20261001", 3),
+            ["20261030", "20261127", "20261225"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY", "20260131", 3),
+            ["20260131", "20260331", "20260531"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=-1", "20260115", 2),
+            ["20260131", "20260228"]
+        );
+        assert_eq!(
+            dates("FREQ=YEARLY", "20240229", 2),
+            ["20240229", "20280229"]
+        );
+        assert_eq!(
+            dates("FREQ=YEARLY;BYMONTH=11;BYDAY=4TH", "20260101", 2),
+            ["20261126", "20271125"]
+        );
+        assert_eq!(dates("FREQ=DAILY;COUNT=2", "20261018", 5).len(), 2);
+        assert_eq!(dates("FREQ=DAILY;UNTIL=20261019", "20261018", 5).len(), 2);
+        assert!(dates("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30", "20260101", 1).is_empty());
+
+        let rule: Rule = "FREQ=DAILY;INTERVAL=3".parse().unwrap();
+        assert_eq!(
+            rule.next_after(date("20261001"), date("20261018")),
+            Some(date("20261019"))
+        );
+    }
+
+    #[test]
+    fn keeps_recurrence_in_notes_footer() {
+        let mut task = Task {
+            notes: Some("Water the plants\n".to_owned()),
+            ..Default::default()
+        };
+        let mut recurrence = Recurrence::new("FREQ=WEEKLY".parse().unwrap(), date("20261019"));
+        recurrence.apply(&mut task);
+        assert_eq!(
+            task.notes.as_deref(),
+            Some("Water the plants\n\n-- gtasks\nRRULE:FREQ=WEEKLY\nDTSTART:20261019")
+        );
+
+        recurrence.series = Some("task1".to_owned());
+        recurrence.apply(&mut task);
+        let parsed = Recurrence::of(&task).unwrap().unwrap();
+        assert_eq!(parsed, recurrence);
+        assert_eq!(
+            strip_footer(task.notes.as_deref().unwrap()),
+            "Water the plants"
+        );
+
+        assert_eq!(Recurrence::parse("no footer").unwrap(), None);
+        assert!(Recurrence::parse("-- gtasks\nDTSTART:20261019").is_err());
+        assert!(Recurrence::parse("-- gtasks\nX-GTASKS-SERIES:task1").is_err());
+    }
+
+    #[test]
+    fn shares_the_footer_with_other_properties() {
+        let mut task = Task {
+            notes: Some("Water the plants\n\n-- gtasks\nPRIORITY:1".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(Recurrence::of(&task).unwrap(), None);
+
+        let recurrence = Recurrence::new("FREQ=WEEKLY".parse().unwrap(), date("20261019"));
+        recurrence.apply(&mut task);
+        assert_eq!(
+            task.notes.as_deref(),
+            Some("Water the plants\n\n-- gtasks\nPRIORITY:1\nRRULE:FREQ=WEEKLY\nDTSTART:20261019")
+        );
+        assert_eq!(Recurrence::of(&task).unwrap(), Some(recurrence));
+    }
+
+    #[tokio::test]
+    async fn schedules_each_occurrence_once() {
+        let fake = FakeTasks::new();
+        let list = fake
+            .insert_tasklist(Tasklist {
+                title: Some("Home".to_owned()),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+
+        let mut task = Task {
+            title: Some("Water the plants".to_owned()),
+            due: Some(date("20261019").and_time(NaiveTime::MIN).and_utc()),
+            ..Default::default()
+        };
+        let rule = "FREQ=WEEKLY;BYDAY=MO,TH".parse().unwrap();
+        Recurrence::new(rule, date("20261019")).apply(&mut task);
+        let first = fake.insert_task(&list, task, None).await.unwrap();
+        let first_id = first.id.clone().unwrap();
+        assert!(schedule(&fake, &list).await.unwrap().is_empty());
+
+        let mut done = first.clone();
+        done.status = Some(TaskStatus::Completed);
+        fake.update_task(&list, done).await.unwrap();
+
+        let created = schedule(&fake, &list).await.unwrap();
+        assert_eq!(created.len(), 1);
+        let second = &created[0];
+        assert_eq!(second.title.as_deref(), Some("Water the plants"));
+        assert_eq!(second.status, Some(TaskStatus::NeedsAction));
+        assert_eq!(second.due.unwrap().date_naive(), date("20261022"));
+        let recurrence = Recurrence::of(second).unwrap().unwrap();
+        assert_eq!(recurrence.series.as_deref(), Some(first_id.as_str()));
+        assert_eq!(recurrence.next, None);
+
+        let first = fake
+            .get_task(&list, &first_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        let recorded = Recurrence::of(&first).unwrap().unwrap();
+        assert_eq!(recorded.next, second.id);
+        assert!(schedule(&fake, &list).await.unwrap().is_empty());
+
+        // the occurrence exists, but was not recorded on the completed task
+        let mut unrecorded = recorded.clone();
+        unrecorded.next = None;
+        let mut patch = first.clone();
+        unrecorded.apply(&mut patch);
+        fake.update_task(&list, patch).await.unwrap();
+        assert!(schedule(&fake, &list).await.unwrap().is_empty());
+        let first = fake
+            .get_task(&list, &first_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Recurrence::of(&first).unwrap().unwrap().next, second.id);
+    }
+}
@@ -0,0 +1,159 @@
+//! Resolution of human-readable task list and task names to identifiers.
+//!
+//! A query is matched against the candidates in the following order,
+//! the first rule with any matches decides:
+//!
+//! 1. identifier
+//! 2. exact title
+//! 3. case-insensitive title
+//! 4. case-insensitive title prefix
+//! 5. case-insensitive title substring
+//! 6. fuzzy match, the query characters appear in the title in order
+//!
+//! A rule matching more than one candidate yields [`crate::TasksError::AmbiguousName`].
+
+use crate::errors::{
+    Result,
+    TasksError::{AmbiguousName, NameNotFound},
+};
+use crate::{tasklists, tasks, Task, TaskOptions, Tasklist, TasksApi};
+
+/// Resource with an identifier and a title.
+pub trait Named {
+    fn id(&self) -> Option<&str>;
+    fn title(&self) -> Option<&str>;
+}
+
+impl Named for Tasklist {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+}
+
+impl Named for Task {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+}
+
+/// Returns the candidate matching the query.
+pub fn resolve<'a, T: Named>(candidates: &'a [T], query: &str) -> Result<&'a T> {
+    let lower = query.to_lowercase();
+    let rules: [&dyn Fn(&T) -> bool; 6] = [
+        &|c| c.id() == Some(query),
+        &|c| c.title() == Some(query),
+        &|c| title_lower(c).is_some_and(|t| t == lower),
+        &|c| title_lower(c).is_some_and(|t| t.starts_with(&lower)),
+        &|c| title_lower(c).is_some_and(|t| t.contains(&lower)),
+        &|c| title_lower(c).is_some_and(|t| is_subsequence(&lower, &t)),
+    ];
+
+    for rule in rules.iter() {
+        let matches: Vec<&T> = candidates.iter().filter(|c| rule(c)).collect();
+        match matches.as_slice() {
+            [] => continue,
+            [single] => return Ok(single),
+            _ => {
+                let names = matches
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{} ({})",
+                            c.title().unwrap_or_default(),
+                            c.id().unwrap_or_default()
+                        )
+                    })
+                    .collect();
+                return Err(AmbiguousName(query.to_owned(), names));
+            }
+        }
+    }
+
+    Err(NameNotFound(query.to_owned()))
+}
+
+/// Returns the identifier of the task list matching the query.
+pub async fn tasklist_id<A: TasksApi + ?Sized>(service: &A, query: &str) -> Result<String> {
+    let tasklists = tasklists::list_all(service).await?;
+    let tasklist = resolve(&tasklists, query)?;
+    Ok(tasklist.id.clone().unwrap_or_default())
+}
+
+/// Returns the identifier of the task matching the query, including completed and hidden tasks.
+pub async fn task_id<A: TasksApi + ?Sized>(
+    service: &A,
+    tasklist_id: &str,
+    query: &str,
+) -> Result<String> {
+    let opts = TaskOptions {
+        max_results: Some(100),
+        show_completed: Some(true),
+        show_hidden: Some(true),
+        ..Default::default()
+    };
+
+    let tasks = tasks::list_all(service, tasklist_id, opts).await?;
+    let task = resolve(&tasks, query)?;
+    Ok(task.id.clone().unwrap_or_default())
+}
+
+fn title_lower<T: Named>(candidate: &T) -> Option<String> {
+    candidate.title().map(str::to_lowercase)
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(id: &str, title: &str) -> Tasklist {
+        Tasklist {
+            id: Some(id.to_owned()),
+            title: Some(title.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resolve_by_rules() {
+        let lists = vec![
+            list("a1", "Work"),
+            list("b2", "work"),
+            list("c3", "Groceries"),
+            list("d4", "Garden"),
+        ];
+
+        assert_eq!(resolve(&lists, "c3").unwrap().id(), Some("c3"));
+        assert_eq!(resolve(&lists, "work").unwrap().id(), Some("b2"));
+        assert_eq!(resolve(&lists, "gro").unwrap().id(), Some("c3"));
+        assert_eq!(resolve(&lists, "rden").unwrap().id(), Some("d4"));
+        assert_eq!(resolve(&lists, "grcs").unwrap().id(), Some("c3"));
+    }
+
+    #[test]
+    fn resolve_reports_ambiguity() {
+        let lists = vec![list("a1", "Work"), list("b2", "WORK"), list("c3", "Garden")];
+
+        match resolve(&lists, "wor") {
+            Err(AmbiguousName(query, names)) => {
+                assert_eq!(query, "wor");
+                assert_eq!(names, vec!["Work (a1)", "WORK (b2)"]);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|l| l.id())),
+        }
+
+        assert!(matches!(resolve(&lists, "xyz"), Err(NameNotFound(_))));
+    }
+}
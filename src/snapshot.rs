@@ -0,0 +1,143 @@
+//! Snapshot of all the task lists and tasks of an account.
+//!
+//! Unlike [`crate::backup`], which fetches the task lists one after another, a snapshot
+//! fetches the tasks of several task lists at the same time, e.g. to load a dashboard.
+
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::{Result, TasksError};
+use crate::{tasklists, tasks, Task, TaskOptions, Tasklist, TasksApi};
+
+/// Number of task lists fetched at the same time, unless set otherwise.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// All the task lists of an account with their tasks.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AccountSnapshot {
+    /// Time the snapshot was started at.
+    pub taken_at: DateTime<Utc>,
+
+    /// Task lists in the order of the API.
+    pub tasklists: Vec<TasklistSnapshot>,
+}
+
+/// Task list with its tasks, in the order of the API.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TasklistSnapshot {
+    pub tasklist: Tasklist,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotOptions {
+    /// Maximum number of task lists whose tasks are fetched at the same time. The default is 4.
+    pub concurrency: usize,
+
+    /// Flag indicating whether completed tasks are included. The default is True.
+    pub show_completed: bool,
+
+    /// Flag indicating whether hidden tasks, completed before the task list was last cleared, are included.
+    /// The default is False.
+    pub show_hidden: bool,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        SnapshotOptions {
+            concurrency: DEFAULT_CONCURRENCY,
+            show_completed: true,
+            show_hidden: false,
+        }
+    }
+}
+
+/// Fetches all the task lists, then their tasks concurrently.
+pub async fn snapshot<A: TasksApi + ?Sized>(
+    service: &A,
+    opts: SnapshotOptions,
+) -> Result<AccountSnapshot> {
+    let taken_at = Utc::now();
+    let task_opts = TaskOptions {
+        max_results: Some(100),
+        show_completed: Some(opts.show_completed),
+        show_hidden: Some(opts.show_hidden),
+        ..Default::default()
+    };
+
+    let tasklists = stream::iter(tasklists::list_all(service).await?)
+        .map(|tasklist| {
+            let task_opts = task_opts.clone();
+            async move {
+                let id = tasklist.id.clone().unwrap_or_default();
+                let tasks = tasks::list_all(service, &id, task_opts).await?;
+                Ok::<_, TasksError>(TasklistSnapshot { tasklist, tasks })
+            }
+        })
+        .buffered(opts.concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    Ok(AccountSnapshot {
+        taken_at,
+        tasklists,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeTasks, TaskStatus};
+
+    fn task(title: &str, status: TaskStatus) -> Task {
+        Task {
+            title: Some(title.to_owned()),
+            status: Some(status),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_every_tasklist() {
+        let fake = FakeTasks::new();
+        for title in ["Work", "Home", "Shopping"] {
+            let tasklist = Tasklist {
+                title: Some(title.to_owned()),
+                ..Default::default()
+            };
+            let id = fake.insert_tasklist(tasklist).await.unwrap().id.unwrap();
+            for i in 0..3 {
+                let status = match i {
+                    0 => TaskStatus::Completed,
+                    _ => TaskStatus::NeedsAction,
+                };
+                let title = format!("{} {}", title, i);
+                fake.insert_task(&id, task(&title, status), None)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let all = snapshot(&fake, SnapshotOptions::default()).await.unwrap();
+        let titles: Vec<_> = all
+            .tasklists
+            .iter()
+            .map(|t| t.tasklist.title.clone().unwrap())
+            .collect();
+        assert_eq!(titles, ["My Tasks", "Work", "Home", "Shopping"]);
+        assert_eq!(all.tasklists[1].tasks.len(), 3);
+
+        let opts = SnapshotOptions {
+            concurrency: 1,
+            show_completed: false,
+            ..Default::default()
+        };
+        let open = snapshot(&fake, opts).await.unwrap();
+        assert_eq!(open.tasklists[3].tasks.len(), 2);
+
+        let json = serde_json::to_string(&open).unwrap();
+        let parsed: AccountSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.tasklists.len(), 4);
+    }
+}
@@ -2,11 +2,14 @@ use chrono::{DateTime, Utc};
 use reqwest::Response;
 use reqwest_middleware::ClientWithMiddleware as HttpClient;
 use serde_derive::{Deserialize, Serialize};
+#[cfg(feature = "tracing")]
+use tracing::field::Empty;
 
-use super::{ensure_status_success, Result, BASE_URL};
+use super::{ensure_status_success, parse_json, Result};
 use crate::errors::TasksError::InvalidArgument;
+use crate::TasksApi;
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Tasklists {
     /// Type of the resource. This is always "tasks#taskLists".
@@ -16,13 +19,14 @@ pub struct Tasklists {
     pub etag: String,
 
     /// Token that can be used to request the next page of this result.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub next_page_token: Option<String>,
 
     /// Collection of task lists.
     pub items: Vec<Tasklist>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Tasklist {
     /// Type of the resource. This is always "tasks#taskList".
@@ -62,8 +66,20 @@ pub struct ListOptions {
 }
 
 // Returns all the authenticated user's task lists.
-pub(crate) async fn list(client: &HttpClient, opt: Option<ListOptions>) -> Result<Tasklists> {
-    let url = format!("{base_url}/users/@me/lists", base_url = BASE_URL);
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasklists.list",
+        skip_all,
+        fields(status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
+pub(crate) async fn list(
+    client: &HttpClient,
+    base_url: &str,
+    opt: Option<ListOptions>,
+) -> Result<Tasklists> {
+    let url = format!("{base_url}/users/@me/lists", base_url = base_url);
     let mut builder = client.get(url.as_str());
 
     if let Some(query_params) = opt {
@@ -73,14 +89,41 @@ pub(crate) async fn list(client: &HttpClient, opt: Option<ListOptions>) -> Resul
     let resp = builder.send().await?;
 
     let resp = ensure_status_success(resp).await?;
-    Ok(resp.json::<Tasklists>().await?)
+    parse_json(resp).await
+}
+
+// Returns all the authenticated user's task lists, following the page tokens.
+pub(crate) async fn list_all<A: TasksApi + ?Sized>(api: &A) -> Result<Vec<Tasklist>> {
+    let mut tasklists = Vec::new();
+    let mut opt = ListOptions {
+        max_results: Some(100),
+        page_token: None,
+    };
+
+    loop {
+        let page = api.list_tasklists(Some(opt.clone())).await?;
+        tasklists.extend(page.items);
+
+        match page.next_page_token {
+            Some(token) => opt.page_token = Some(token),
+            None => return Ok(tasklists),
+        }
+    }
 }
 
 // Returns the authenticated user's specified task list.
-pub(crate) async fn get(client: &HttpClient, id: &str) -> Result<Tasklist> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasklists.get",
+        skip_all,
+        fields(tasklist_id = %id, status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
+pub(crate) async fn get(client: &HttpClient, base_url: &str, id: &str) -> Result<Tasklist> {
     let url = format!(
         "{base_url}/users/@me/lists/{tasklist_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = id
     );
     let resp = client.get(url.as_str()).send().await?;
@@ -88,8 +131,16 @@ pub(crate) async fn get(client: &HttpClient, id: &str) -> Result<Tasklist> {
 }
 
 // Creates a new task list and adds it to the authenticated user's task lists.
-pub(crate) async fn insert(client: &HttpClient, b: Tasklist) -> Result<Tasklist> {
-    let url = format!("{base_url}/users/@me/lists", base_url = BASE_URL);
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasklists.insert",
+        skip_all,
+        fields(status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
+pub(crate) async fn insert(client: &HttpClient, base_url: &str, b: Tasklist) -> Result<Tasklist> {
+    let url = format!("{base_url}/users/@me/lists", base_url = base_url);
     let resp = client
         .post(url.as_str())
         .body(serde_json::to_vec(&b)?)
@@ -100,7 +151,20 @@ pub(crate) async fn insert(client: &HttpClient, b: Tasklist) -> Result<Tasklist>
 }
 
 // Updates the authenticated user's specified task list.
-pub(crate) async fn update(client: &HttpClient, v: Tasklist) -> Result<Tasklist> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasklists.update",
+        skip_all,
+        fields(
+            tasklist_id = v.id.as_deref().unwrap_or_default(),
+            status = Empty,
+            retries = Empty,
+            latency_ms = Empty,
+        )
+    )
+)]
+pub(crate) async fn update(client: &HttpClient, base_url: &str, v: Tasklist) -> Result<Tasklist> {
     let tasklist_id = match v.id.as_ref() {
         Some(id) => id,
         None => return Err(InvalidArgument("tasklist id cannot be None".to_owned())),
@@ -108,7 +172,7 @@ pub(crate) async fn update(client: &HttpClient, v: Tasklist) -> Result<Tasklist>
 
     let url = format!(
         "{base_url}/users/@me/lists/{tasklist_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id
     );
     let resp = client
@@ -121,10 +185,18 @@ pub(crate) async fn update(client: &HttpClient, v: Tasklist) -> Result<Tasklist>
 }
 
 // Deletes the authenticated user's specified task list.
-pub(crate) async fn delete(client: &HttpClient, id: &str) -> Result<()> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasklists.delete",
+        skip_all,
+        fields(tasklist_id = %id, status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
+pub(crate) async fn delete(client: &HttpClient, base_url: &str, id: &str) -> Result<()> {
     let url = format!(
         "{base_url}/users/@me/lists/{tasklist_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = id
     );
     let resp = client.delete(url.as_str()).send().await?;
@@ -134,10 +206,23 @@ pub(crate) async fn delete(client: &HttpClient, id: &str) -> Result<()> {
 }
 
 // Updates the authenticated user's specified task list. This method supports patch semantics.
-pub(crate) async fn patch(client: &HttpClient, tasklist_id: &str, v: Tasklist) -> Result<Tasklist> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasklists.patch",
+        skip_all,
+        fields(tasklist_id = %tasklist_id, status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
+pub(crate) async fn patch(
+    client: &HttpClient,
+    base_url: &str,
+    tasklist_id: &str,
+    v: Tasklist,
+) -> Result<Tasklist> {
     let url = format!(
         "{base_url}/users/@me/lists/{tasklist_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id
     );
     let resp = client
@@ -151,5 +236,5 @@ pub(crate) async fn patch(client: &HttpClient, tasklist_id: &str, v: Tasklist) -
 
 async fn handle_response_tasklist(resp: Response) -> Result<Tasklist> {
     let resp = ensure_status_success(resp).await?;
-    Ok(resp.json::<Tasklist>().await?)
+    parse_json(resp).await
 }
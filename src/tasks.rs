@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
 use reqwest::{
     header::{CONTENT_LENGTH, IF_NONE_MATCH},
@@ -5,11 +7,14 @@ use reqwest::{
 };
 use reqwest_middleware::ClientWithMiddleware as HttpClient;
 use serde_derive::{Deserialize, Serialize};
+#[cfg(feature = "tracing")]
+use tracing::field::Empty;
 
-use super::{ensure_status_success, Result, BASE_URL};
+use super::{ensure_status_success, parse_json, Result};
 use crate::errors::TasksError::InvalidArgument;
+use crate::TasksApi;
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Tasks {
     /// Type of the resource. This is always "tasks#tasks".
@@ -19,13 +24,15 @@ pub struct Tasks {
     pub etag: String,
 
     /// Token used to access the next page of this result.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub next_page_token: Option<String>,
 
     /// Collection of tasks.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub items: Option<Vec<Task>>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Task {
     /// Type of the resource. This is always "tasks#task".
@@ -114,7 +121,7 @@ pub struct TaskLink {
     pub link: String,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum TaskStatus {
     NeedsAction,
@@ -165,15 +172,24 @@ pub struct ListOptions {
 }
 
 // Returns all tasks in the specified task list.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.list",
+        skip_all,
+        fields(tasklist_id = %tasklist_id, status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
 pub async fn list(
     client: &HttpClient,
+    base_url: &str,
     tasklist_id: &str,
     opt: Option<ListOptions>,
     etag: Option<String>,
 ) -> Result<Option<Tasks>> {
     let url = format!(
         "{base_url}/lists/{tasklist_id}/tasks",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id
     );
     let mut builder = client.get(url.as_str());
@@ -192,20 +208,57 @@ pub async fn list(
         Ok(None)
     } else {
         let resp = ensure_status_success(resp).await?;
-        Ok(Some(resp.json::<Tasks>().await?))
+        Ok(Some(parse_json(resp).await?))
+    }
+}
+
+// Returns all tasks in the specified task list, following the page tokens.
+pub(crate) async fn list_all<A: TasksApi + ?Sized>(
+    api: &A,
+    tasklist_id: &str,
+    mut opt: ListOptions,
+) -> Result<Vec<Task>> {
+    let mut tasks = Vec::new();
+
+    loop {
+        let page = match api.list_tasks(tasklist_id, Some(opt.clone()), None).await? {
+            Some(page) => page,
+            None => return Ok(tasks),
+        };
+        tasks.extend(page.items.unwrap_or_default());
+
+        match page.next_page_token {
+            Some(token) => opt.page_token = Some(token),
+            None => return Ok(tasks),
+        }
     }
 }
 
 // Returns the specified task.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.get",
+        skip_all,
+        fields(
+            tasklist_id = %tasklist_id,
+            task_id = %task_id,
+            status = Empty,
+            retries = Empty,
+            latency_ms = Empty,
+        )
+    )
+)]
 pub async fn get(
     client: &HttpClient,
+    base_url: &str,
     tasklist_id: &str,
     task_id: &str,
     etag: Option<String>,
 ) -> Result<Option<Task>> {
     let url = format!(
         "{base_url}/lists/{tasklist_id}/tasks/{task_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id,
         task_id = task_id
     );
@@ -221,7 +274,7 @@ pub async fn get(
         Ok(None)
     } else {
         let resp = ensure_status_success(resp).await?;
-        Ok(Some(resp.json::<Task>().await?))
+        Ok(Some(parse_json(resp).await?))
     }
 }
 
@@ -238,15 +291,24 @@ pub struct InsertOptions {
 }
 
 // Creates a new task on the specified task list.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.insert",
+        skip_all,
+        fields(tasklist_id = %tasklist_id, status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
 pub async fn insert(
     client: &HttpClient,
+    base_url: &str,
     tasklist_id: &str,
     v: Task,
     opts: Option<InsertOptions>,
 ) -> Result<Task> {
     let url = format!(
         "{base_url}/lists/{tasklist_id}/tasks",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id,
     );
 
@@ -259,11 +321,89 @@ pub async fn insert(
     let resp = builder.send().await?;
 
     let resp = ensure_status_success(resp).await?;
-    Ok(resp.json::<Task>().await?)
+    parse_json(resp).await
+}
+
+// Creates the tasks so that every task is inserted under its parent and after its previous sibling.
+// Each node holds the task and the index of its parent node, siblings are created in the order of the nodes.
+// Returns the created tasks in the order of the nodes.
+pub(crate) async fn insert_tree<A: TasksApi + ?Sized>(
+    api: &A,
+    tasklist_id: &str,
+    nodes: Vec<(Task, Option<usize>)>,
+) -> Result<Vec<Task>> {
+    let parents: Vec<Option<usize>> = nodes
+        .iter()
+        .map(|(_, parent)| parent.filter(|p| *p < nodes.len()))
+        .collect();
+
+    let mut children: HashMap<Option<usize>, Vec<usize>> = HashMap::new();
+    for i in 0..parents.len() {
+        // a node which is its own ancestor is created at the top level
+        let in_cycle = std::iter::successors(parents[i], |p| parents[*p])
+            .take(parents.len())
+            .any(|ancestor| ancestor == i);
+        let parent = parents[i].filter(|_| !in_cycle);
+        children.entry(parent).or_default().push(i);
+    }
+
+    let mut tasks: Vec<Option<Task>> = nodes.iter().map(|_| None).collect();
+    let mut nodes: Vec<Option<Task>> = nodes.into_iter().map(|(task, _)| Some(task)).collect();
+
+    // depth-first walk, so that every parent is created before its children;
+    // each level holds the parent node, the remaining siblings and the previous sibling id
+    let roots = children.remove(&None).unwrap_or_default();
+    let mut levels = vec![(None, roots.into_iter(), None)];
+
+    while let Some((parent, siblings, previous)) = levels.last_mut() {
+        let i = match siblings.next() {
+            Some(i) => i,
+            None => {
+                levels.pop();
+                continue;
+            }
+        };
+
+        let opts = InsertOptions {
+            parent: parent.and_then(|p: usize| tasks[p].as_ref().and_then(|t| t.id.clone())),
+            previous: previous.clone(),
+        };
+
+        let task = api
+            .insert_task(tasklist_id, nodes[i].take().unwrap_or_default(), Some(opts))
+            .await?;
+        *previous = task.id.clone();
+        tasks[i] = Some(task);
+
+        if let Some(nested) = children.remove(&Some(i)) {
+            levels.push((Some(i), nested.into_iter(), None));
+        }
+    }
+
+    Ok(tasks.into_iter().flatten().collect())
 }
 
 // Updates the specified task.
-pub async fn update(client: &HttpClient, tasklist_id: &str, mut v: Task) -> Result<Task> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.update",
+        skip_all,
+        fields(
+            tasklist_id = %tasklist_id,
+            task_id = v.id.as_deref().unwrap_or_default(),
+            status = Empty,
+            retries = Empty,
+            latency_ms = Empty,
+        )
+    )
+)]
+pub async fn update(
+    client: &HttpClient,
+    base_url: &str,
+    tasklist_id: &str,
+    mut v: Task,
+) -> Result<Task> {
     let task_id = match v.id.as_ref() {
         Some(id) => id,
         None => return Err(InvalidArgument("task id cannot be None".to_owned())),
@@ -271,7 +411,7 @@ pub async fn update(client: &HttpClient, tasklist_id: &str, mut v: Task) -> Resu
 
     let url = format!(
         "{base_url}/lists/{tasklist_id}/tasks/{task_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id,
         task_id = task_id.as_str()
     );
@@ -285,14 +425,33 @@ pub async fn update(client: &HttpClient, tasklist_id: &str, mut v: Task) -> Resu
         .await?;
 
     let resp = ensure_status_success(resp).await?;
-    Ok(resp.json::<Task>().await?)
+    parse_json(resp).await
 }
 
 // Deletes the specified task from the task list.
-pub async fn delete(client: &HttpClient, tasklist_id: &str, task_id: &str) -> Result<()> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.delete",
+        skip_all,
+        fields(
+            tasklist_id = %tasklist_id,
+            task_id = %task_id,
+            status = Empty,
+            retries = Empty,
+            latency_ms = Empty,
+        )
+    )
+)]
+pub async fn delete(
+    client: &HttpClient,
+    base_url: &str,
+    tasklist_id: &str,
+    task_id: &str,
+) -> Result<()> {
     let url = format!(
         "{base_url}/lists/{tasklist_id}/tasks/{task_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id,
         task_id = task_id,
     );
@@ -305,10 +464,18 @@ pub async fn delete(client: &HttpClient, tasklist_id: &str, task_id: &str) -> Re
 
 // Clears all completed tasks from the specified task list.
 // The affected tasks will be marked as 'hidden' and no longer be returned by default when retrieving all tasks for a task list.
-pub async fn clear(client: &HttpClient, tasklist_id: &str) -> Result<()> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.clear",
+        skip_all,
+        fields(tasklist_id = %tasklist_id, status = Empty, retries = Empty, latency_ms = Empty)
+    )
+)]
+pub async fn clear(client: &HttpClient, base_url: &str, tasklist_id: &str) -> Result<()> {
     let url = format!(
         "{base_url}/lists/{tasklist_id}/clear",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id,
     );
 
@@ -324,15 +491,30 @@ pub async fn clear(client: &HttpClient, tasklist_id: &str) -> Result<()> {
 
 // Moves the specified task to another position in the task list.
 // This can include putting it as a child task under a new parent and/or move it to a different position among its sibling tasks.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.move",
+        skip_all,
+        fields(
+            tasklist_id = %tasklist_id,
+            task_id = %task_id,
+            status = Empty,
+            retries = Empty,
+            latency_ms = Empty,
+        )
+    )
+)]
 pub async fn move_task(
     client: &HttpClient,
+    base_url: &str,
     tasklist_id: &str,
     task_id: &str,
     opts: InsertOptions,
 ) -> Result<Task> {
     let url = format!(
         "{base_url}/lists/{tasklist_id}/tasks/{task_id}/move",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id,
         task_id = task_id
     );
@@ -345,14 +527,34 @@ pub async fn move_task(
         .await?;
 
     let resp = ensure_status_success(resp).await?;
-    Ok(resp.json::<Task>().await?)
+    parse_json(resp).await
 }
 
 // Updates the specified task. This method supports patch semantics.
-pub async fn patch(client: &HttpClient, tasklist_id: &str, task_id: &str, v: Task) -> Result<Task> {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "tasks.patch",
+        skip_all,
+        fields(
+            tasklist_id = %tasklist_id,
+            task_id = %task_id,
+            status = Empty,
+            retries = Empty,
+            latency_ms = Empty,
+        )
+    )
+)]
+pub async fn patch(
+    client: &HttpClient,
+    base_url: &str,
+    tasklist_id: &str,
+    task_id: &str,
+    v: Task,
+) -> Result<Task> {
     let url = format!(
         "{base_url}/lists/{tasklist_id}/tasks/{task_id}",
-        base_url = BASE_URL,
+        base_url = base_url,
         tasklist_id = tasklist_id,
         task_id = task_id
     );
@@ -364,5 +566,5 @@ pub async fn patch(client: &HttpClient, tasklist_id: &str, task_id: &str, v: Tas
         .await?;
 
     let resp = ensure_status_success(resp).await?;
-    Ok(resp.json::<Task>().await?)
+    parse_json(resp).await
 }
@@ -0,0 +1,427 @@
+//! Local HTTP server implementing the Tasks v1 REST API, for end-to-end tests of [`Service`].
+//!
+//! The server is backed by [`FakeTasks`] and supports the task list and task
+//! resources including `move` and `clear`, `If-None-Match` (304) and `If-Match` (412)
+//! preconditions, pagination tokens, the `show*` and date filters and the JSON error bodies of the API.
+//!
+//! ```no_run
+//! # async fn example() -> gtasks::Result<()> {
+//! let server = gtasks::testing::FakeServer::start()?;
+//! let service = server.service()?;
+//! let tasklists = service.list_tasklists(None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, IF_MATCH, IF_NONE_MATCH};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::{oneshot, Mutex};
+
+use crate::errors::{Result, TasksError};
+use crate::fake::error_body;
+use crate::{
+    FakeTasks, Service, Task, TaskInsertOptions, TaskOptions, Tasklist, TasklistsOptions, TasksApi,
+};
+
+const BASE_PATH: &str = "/tasks/v1";
+const FAKE_TOKEN: &str = "Bearer fake-token";
+
+/// Fake Google Tasks server listening on a free local port, stopped when dropped.
+pub struct FakeServer {
+    addr: SocketAddr,
+    tasks: Arc<FakeTasks>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+struct Shared {
+    tasks: Arc<FakeTasks>,
+
+    // serializes the requests, so that preconditions are checked and applied at once
+    lock: Mutex<()>,
+}
+
+// Error response with the status and the JSON error body.
+struct HttpError(StatusCode, String);
+
+type HttpResult<T = Response<Body>> = std::result::Result<T, HttpError>;
+
+impl FakeServer {
+    /// Starts a server with an empty account, must be called within a Tokio runtime.
+    pub fn start() -> Result<Self> {
+        Self::with_tasks(FakeTasks::new())
+    }
+
+    /// Starts a server serving the given store, must be called within a Tokio runtime.
+    pub fn with_tasks(tasks: FakeTasks) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let tasks = Arc::new(tasks);
+        let shared = Arc::new(Shared {
+            tasks: tasks.clone(),
+            lock: Mutex::new(()),
+        });
+
+        let make_service = make_service_fn(move |_| {
+            let shared = shared.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let shared = shared.clone();
+                    async move { Ok::<_, Infallible>(handle(&shared, req).await) }
+                }))
+            }
+        });
+
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let server = Server::from_tcp(listener)
+            .map_err(std::io::Error::other)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                stopped.await.ok();
+            });
+        tokio::spawn(server);
+
+        Ok(FakeServer {
+            addr,
+            tasks,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Returns the root URL of the API, to be given to [`Service::with_base_url`].
+    pub fn base_url(&self) -> String {
+        format!("http://{}{}", self.addr, BASE_PATH)
+    }
+
+    /// Returns a service talking to this server.
+    pub fn service(&self) -> Result<Service> {
+        Ok(Service::with_token(FAKE_TOKEN)?.with_base_url(&self.base_url()))
+    }
+
+    /// Returns the store behind the server, to seed or inspect its state directly.
+    pub fn tasks(&self) -> &FakeTasks {
+        &self.tasks
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn handle(shared: &Shared, req: Request<Body>) -> Response<Body> {
+    let _guard = shared.lock.lock().await;
+    route(&shared.tasks, req)
+        .await
+        .unwrap_or_else(|HttpError(status, body)| {
+            let mut resp = Response::new(Body::from(body));
+            *resp.status_mut() = status;
+            resp.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            resp
+        })
+}
+
+async fn route(api: &FakeTasks, req: Request<Body>) -> HttpResult {
+    if !req.headers().contains_key(AUTHORIZATION) {
+        let message = "Request is missing required authentication credential.";
+        return Err(error(StatusCode::UNAUTHORIZED, "required", message));
+    }
+
+    let path = req.uri().path().to_owned();
+    let segments: Vec<String> = path
+        .strip_prefix(BASE_PATH)
+        .unwrap_or_default()
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    let query = query(req.uri().query());
+    let if_match = header(&req, IF_MATCH);
+    let if_none_match = header(&req, IF_NONE_MATCH);
+    let method = req.method().clone();
+    let body = hyper::body::to_bytes(req.into_body())
+        .await
+        .map_err(|err| error(StatusCode::BAD_REQUEST, "invalid", &err.to_string()))?;
+
+    match (&method, segments.as_slice()) {
+        (&Method::GET, ["users", "@me", "lists"]) => {
+            let opts = TasklistsOptions {
+                max_results: param(&query, "maxResults")?,
+                page_token: query.get("pageToken").cloned(),
+            };
+            json(&api.list_tasklists(Some(opts)).await.map_err(api_error)?)
+        }
+        (&Method::POST, ["users", "@me", "lists"]) => {
+            let tasklist: Tasklist = parse_body(&body)?;
+            json(&api.insert_tasklist(tasklist).await.map_err(api_error)?)
+        }
+        (&Method::GET, ["users", "@me", "lists", id]) => {
+            let tasklist = api.get_tasklist(id).await.map_err(api_error)?;
+            if if_none_match.is_some() && if_none_match == tasklist.etag {
+                return Ok(empty(StatusCode::NOT_MODIFIED));
+            }
+            json(&tasklist)
+        }
+        (method, ["users", "@me", "lists", id]) => {
+            if let Ok(current) = api.get_tasklist(id).await {
+                precondition(&if_match, &current.etag)?;
+            }
+
+            match *method {
+                Method::PUT => {
+                    let tasklist = Tasklist {
+                        id: Some(id.to_string()),
+                        ..parse_body(&body)?
+                    };
+                    json(&api.update_tasklist(tasklist).await.map_err(api_error)?)
+                }
+                Method::PATCH => {
+                    let tasklist = parse_body(&body)?;
+                    json(&api.patch_tasklist(id, tasklist).await.map_err(api_error)?)
+                }
+                Method::DELETE => no_content(api.delete_tasklist(id).await),
+                _ => Err(not_found(&path)),
+            }
+        }
+        (&Method::GET, ["lists", list, "tasks"]) => {
+            let tasks = api
+                .list_tasks(list, Some(task_options(&query)?), if_none_match)
+                .await
+                .map_err(api_error)?;
+            match tasks {
+                Some(tasks) => json(&tasks),
+                None => Ok(empty(StatusCode::NOT_MODIFIED)),
+            }
+        }
+        (&Method::POST, ["lists", list, "tasks"]) => {
+            let task: Task = parse_body(&body)?;
+            let opts = insert_options(&query);
+            json(
+                &api.insert_task(list, task, Some(opts))
+                    .await
+                    .map_err(api_error)?,
+            )
+        }
+        (&Method::POST, ["lists", list, "tasks", id, "move"]) => {
+            let opts = insert_options(&query);
+            json(&api.move_task(list, id, opts).await.map_err(api_error)?)
+        }
+        (&Method::POST, ["lists", list, "clear"]) => no_content(api.clear_tasks(list).await),
+        (&Method::GET, ["lists", list, "tasks", id]) => {
+            match api
+                .get_task(list, id, if_none_match)
+                .await
+                .map_err(api_error)?
+            {
+                Some(task) => json(&task),
+                None => Ok(empty(StatusCode::NOT_MODIFIED)),
+            }
+        }
+        (method, ["lists", list, "tasks", id]) => {
+            if let Ok(Some(current)) = api.get_task(list, id, None).await {
+                precondition(&if_match, &current.etag)?;
+            }
+
+            match *method {
+                Method::PUT => {
+                    let task = Task {
+                        id: Some(id.to_string()),
+                        ..parse_body(&body)?
+                    };
+                    json(&api.update_task(list, task).await.map_err(api_error)?)
+                }
+                Method::PATCH => {
+                    let task = parse_body(&body)?;
+                    json(&api.patch_task(list, id, task).await.map_err(api_error)?)
+                }
+                Method::DELETE => no_content(api.delete_task(list, id).await),
+                _ => Err(not_found(&path)),
+            }
+        }
+        _ => Err(not_found(&path)),
+    }
+}
+
+fn precondition(if_match: &Option<String>, etag: &Option<String>) -> HttpResult<()> {
+    match if_match {
+        Some(expected) if expected != "*" && Some(expected) != etag.as_ref() => Err(error(
+            StatusCode::PRECONDITION_FAILED,
+            "conditionNotMet",
+            "Precondition Failed",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn task_options(query: &HashMap<String, String>) -> HttpResult<TaskOptions> {
+    Ok(TaskOptions {
+        completed_max: query.get("completedMax").cloned(),
+        completed_min: query.get("completedMin").cloned(),
+        due_max: time_param(query, "dueMax")?,
+        due_min: time_param(query, "dueMin")?,
+        max_results: param(query, "maxResults")?,
+        page_token: query.get("pageToken").cloned(),
+        show_completed: param(query, "showCompleted")?,
+        show_deleted: param(query, "showDeleted")?,
+        show_hidden: param(query, "showHidden")?,
+        updated_min: time_param(query, "updatedMin")?,
+    })
+}
+
+fn insert_options(query: &HashMap<String, String>) -> TaskInsertOptions {
+    TaskInsertOptions {
+        parent: query.get("parent").cloned(),
+        previous: query.get("previous").cloned(),
+    }
+}
+
+fn param<T: std::str::FromStr>(
+    query: &HashMap<String, String>,
+    name: &str,
+) -> HttpResult<Option<T>> {
+    query
+        .get(name)
+        .map(|value| value.parse().map_err(|_| invalid_param(name, value)))
+        .transpose()
+}
+
+fn time_param(query: &HashMap<String, String>, name: &str) -> HttpResult<Option<DateTime<Utc>>> {
+    query
+        .get(name)
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|_| invalid_param(name, value))
+        })
+        .transpose()
+}
+
+fn invalid_param(name: &str, value: &str) -> HttpError {
+    let message = format!("Invalid value for {}: {}", name, value);
+    error(StatusCode::BAD_REQUEST, "invalid", &message)
+}
+
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> HttpResult<T> {
+    serde_json::from_slice(body)
+        .map_err(|err| error(StatusCode::BAD_REQUEST, "parseError", &err.to_string()))
+}
+
+fn json<T: Serialize>(value: &T) -> HttpResult {
+    let body = serde_json::to_vec(value).map_err(|err| {
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "backendError",
+            &err.to_string(),
+        )
+    })?;
+
+    let mut resp = Response::new(Body::from(body));
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(resp)
+}
+
+fn no_content(result: Result<()>) -> HttpResult {
+    result.map_err(api_error)?;
+    Ok(empty(StatusCode::NO_CONTENT))
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = status;
+    resp
+}
+
+// Responds with the error body of the fake, or with a generic one for the other errors.
+fn api_error(err: TasksError) -> HttpError {
+    match err {
+        TasksError::ResponseError(body) => {
+            let code = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v["error"]["code"].as_u64())
+                .and_then(|code| StatusCode::from_u16(code as u16).ok())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            HttpError(code, body)
+        }
+        TasksError::InvalidArgument(message) => error(StatusCode::BAD_REQUEST, "invalid", &message),
+        err => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "backendError",
+            &err.to_string(),
+        ),
+    }
+}
+
+fn error(status: StatusCode, reason: &str, message: &str) -> HttpError {
+    HttpError(status, error_body(status.as_u16(), reason, message))
+}
+
+fn not_found(path: &str) -> HttpError {
+    error(
+        StatusCode::NOT_FOUND,
+        "notFound",
+        &format!("Not Found: {}", path),
+    )
+}
+
+fn header(req: &Request<Body>, name: hyper::header::HeaderName) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+}
+
+fn query(query: Option<&str>) -> HashMap<String, String> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+// Decodes the percent-encoded form value.
+fn decode(value: &str) -> String {
+    let value = value.replace('+', " ");
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
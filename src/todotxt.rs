@@ -0,0 +1,517 @@
+//! Conversion between Google Tasks and the [todo.txt](https://github.com/todotxt/todo.txt) format.
+//!
+//! ```text
+//! x 2024-03-02 Book flights +Travel id:MTIzNDU2
+//! Pack the bags +Travel due:2024-03-05 id:Nzg5MDEy
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::errors::{
+    Result,
+    TasksError::{ParseError, ResponseError},
+};
+use crate::{tasklists, tasks, Task, TaskOptions, TaskStatus, Tasklist, Tasks, TasksApi};
+
+const DEFAULT_TASKLIST: &str = "@default";
+const DUE_TAG: &str = "due:";
+const ID_TAG: &str = "id:";
+
+/// Single todo.txt line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub completed: bool,
+    pub completion_date: Option<NaiveDate>,
+    pub priority: Option<char>,
+
+    /// Description without the projects and the `due:` and `id:` tags.
+    /// A `due:` tag which is not a `YYYY-MM-DD` date is kept in the description.
+    pub title: String,
+
+    /// Projects without the leading `+`.
+    pub projects: Vec<String>,
+
+    pub due: Option<NaiveDate>,
+
+    /// Google task identifier from the `id:` tag.
+    pub id: Option<String>,
+}
+
+/// Result of a [`sync`].
+#[derive(Debug, Clone, Default)]
+pub struct SyncOutcome {
+    /// New content of the todo.txt file, with an `id:` tag on every line.
+    pub content: String,
+
+    pub created: Vec<Task>,
+    pub updated: Vec<Task>,
+
+    /// Identifiers of the deleted tasks.
+    pub deleted: Vec<String>,
+}
+
+impl Entry {
+    /// Builds the entry of the task, using the task list title as the project.
+    pub fn from_task(task: &Task, tasklist: Option<&Tasklist>) -> Self {
+        let completed = task.status == Some(TaskStatus::Completed);
+
+        Entry {
+            completed,
+            completion_date: task
+                .completed
+                .filter(|_| completed)
+                .map(|dt| dt.date_naive()),
+            priority: None,
+            title: task.title.as_deref().unwrap_or_default().replace('\n', " "),
+            projects: tasklist
+                .and_then(|tasklist| tasklist.title.as_deref())
+                .map(project_name)
+                .into_iter()
+                .collect(),
+            due: task.due.map(|dt| dt.date_naive()),
+            id: task.id.clone(),
+        }
+    }
+
+    /// Builds a task with the title, status, completed and due fields of the entry.
+    pub fn to_task(&self) -> Task {
+        let status = match self.completed {
+            true => TaskStatus::Completed,
+            false => TaskStatus::NeedsAction,
+        };
+
+        Task {
+            title: Some(self.title.clone()),
+            status: Some(status),
+            completed: self
+                .completion_date
+                .map(midnight)
+                .filter(|_| self.completed),
+            due: self.due.map(midnight),
+            ..Default::default()
+        }
+    }
+
+    /// Parses a single todo.txt line. Returns None for blank lines.
+    pub fn parse(line: &str) -> Result<Option<Self>> {
+        let mut words = line.split_whitespace().peekable();
+        let mut entry = Entry::default();
+
+        if words.peek().is_none() {
+            return Ok(None);
+        }
+
+        if words.peek() == Some(&"x") {
+            words.next();
+            entry.completed = true;
+            if let Some(date) = words.peek().and_then(|w| parse_date(w).ok()) {
+                words.next();
+                entry.completion_date = Some(date);
+            }
+        }
+
+        if let Some(priority) = words.peek().and_then(|w| parse_priority(w)) {
+            words.next();
+            entry.priority = Some(priority);
+        }
+
+        // creation date, which has no counterpart in Google Tasks
+        if words.peek().is_some_and(|w| parse_date(w).is_ok()) {
+            words.next();
+        }
+
+        let mut title = Vec::new();
+        for word in words {
+            if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+                entry.projects.push(project.to_owned());
+            } else if let Some(due) = word.strip_prefix(DUE_TAG).and_then(|d| parse_date(d).ok()) {
+                entry.due = Some(due);
+            } else if let Some(id) = word.strip_prefix(ID_TAG).filter(|id| !id.is_empty()) {
+                entry.id = Some(id.to_owned());
+            } else {
+                title.push(word);
+            }
+        }
+
+        entry.title = title.join(" ");
+        Ok(Some(entry))
+    }
+
+    /// Renders the entry as a todo.txt line.
+    pub fn to_line(&self) -> String {
+        let mut words = Vec::new();
+
+        if self.completed {
+            words.push("x".to_owned());
+            words.extend(self.completion_date.map(|d| d.to_string()));
+        }
+        words.extend(self.priority.map(|p| format!("({})", p)));
+        if !self.title.is_empty() {
+            words.push(self.title.clone());
+        }
+        words.extend(self.projects.iter().map(|p| format!("+{}", p)));
+        words.extend(self.due.map(|d| format!("{}{}", DUE_TAG, d)));
+        words.extend(self.id.as_ref().map(|id| format!("{}{}", ID_TAG, id)));
+
+        words.join(" ")
+    }
+}
+
+/// Renders the tasks of the task list as todo.txt lines.
+pub fn export(tasklist: &Tasklist, tasks: &Tasks) -> String {
+    tasks
+        .items
+        .iter()
+        .flatten()
+        .filter(|task| !task.deleted.unwrap_or(false))
+        .map(|task| Entry::from_task(task, Some(tasklist)).to_line() + "\n")
+        .collect()
+}
+
+/// Parses all the entries of a todo.txt file.
+pub fn parse(input: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for line in input.lines() {
+        entries.extend(Entry::parse(line)?);
+    }
+    Ok(entries)
+}
+
+/// Parses the todo.txt file and creates its entries as tasks.
+/// Every entry goes to the task list named after its first project,
+/// a missing task list is created. Entries without a project go to the default task list.
+pub async fn import<A: TasksApi + ?Sized>(service: &A, input: &str) -> Result<Vec<Task>> {
+    let entries = parse(input)?;
+    let mut tasklist_ids: HashMap<String, String> = HashMap::new();
+
+    for tasklist in tasklists::list_all(service).await? {
+        if let (Some(id), Some(title)) = (tasklist.id, tasklist.title) {
+            tasklist_ids.entry(project_name(&title)).or_insert(id);
+        }
+    }
+
+    let mut created = Vec::with_capacity(entries.len());
+    for entry in entries.iter() {
+        let tasklist_id = match entry.projects.first() {
+            None => DEFAULT_TASKLIST.to_owned(),
+            Some(project) => match tasklist_ids.get(project) {
+                Some(id) => id.clone(),
+                None => {
+                    let tasklist = Tasklist {
+                        title: Some(project.replace('_', " ")),
+                        ..Default::default()
+                    };
+                    let tasklist = service.insert_tasklist(tasklist).await?;
+                    let id = tasklist
+                        .id
+                        .ok_or_else(|| ResponseError("created task list has no id".to_owned()))?;
+                    tasklist_ids.insert(project.clone(), id.clone());
+                    id
+                }
+            },
+        };
+
+        created.push(
+            service
+                .insert_task(&tasklist_id, entry.to_task(), None)
+                .await?,
+        );
+    }
+
+    Ok(created)
+}
+
+/// Synchronizes the todo.txt file with the task list, matching the lines to tasks by the `id:` tag.
+///
+/// Lines without an id are created as tasks. For a line matched to a task,
+/// the task wins if it has been updated after `last_sync`, otherwise the line is written to the task.
+/// Tasks without a line are added to the file if they have been updated after `last_sync`,
+/// otherwise the line is considered removed and the task is deleted.
+/// Lines whose task no longer exists are dropped.
+pub async fn sync<A: TasksApi + ?Sized>(
+    service: &A,
+    tasklist_id: &str,
+    input: &str,
+    last_sync: Option<DateTime<Utc>>,
+) -> Result<SyncOutcome> {
+    let entries = parse(input)?;
+    let tasklist = service.get_tasklist(tasklist_id).await?;
+
+    let opts = TaskOptions {
+        max_results: Some(100),
+        show_completed: Some(true),
+        show_hidden: Some(true),
+        ..Default::default()
+    };
+    let tasks: Vec<Task> = tasks::list_all(service, tasklist_id, opts)
+        .await?
+        .into_iter()
+        .filter(|task| !task.deleted.unwrap_or(false))
+        .collect();
+
+    let changed_remotely = |task: &Task| match (last_sync, task.updated) {
+        (Some(last_sync), Some(updated)) => updated > last_sync,
+        _ => true,
+    };
+
+    let by_id: HashMap<&str, &Task> = tasks
+        .iter()
+        .filter_map(|task| task.id.as_deref().map(|id| (id, task)))
+        .collect();
+
+    let mut outcome = SyncOutcome::default();
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut lines = Vec::with_capacity(entries.len());
+
+    for mut entry in entries.into_iter() {
+        let task = match entry.id.as_deref() {
+            None => {
+                let task = service
+                    .insert_task(tasklist_id, entry.to_task(), None)
+                    .await?;
+                entry.id = task.id.clone();
+                outcome.created.push(task);
+                lines.push(entry.to_line());
+                continue;
+            }
+            Some(id) => match by_id.get(id) {
+                Some(task) => *task,
+                None => continue,
+            },
+        };
+
+        seen.extend(task.id.as_deref());
+        let remote = Entry::from_task(task, Some(&tasklist));
+
+        if same_content(&entry, &remote) {
+            lines.push(entry.to_line());
+        } else if changed_remotely(task) {
+            lines.push(merge_remote(entry, remote).to_line());
+        } else {
+            // the whole task is written, so that a removed due date is cleared
+            let local = entry.to_task();
+            let merged = Task {
+                title: local.title,
+                status: local.status,
+                completed: local.completed,
+                due: local.due,
+                ..task.clone()
+            };
+            let task = service.update_task(tasklist_id, merged).await?;
+            outcome.updated.push(task);
+            lines.push(entry.to_line());
+        }
+    }
+
+    for task in tasks.iter() {
+        let id = match task.id.as_deref() {
+            Some(id) if !seen.contains(id) => id,
+            _ => continue,
+        };
+
+        if changed_remotely(task) {
+            lines.push(Entry::from_task(task, Some(&tasklist)).to_line());
+        } else {
+            service.delete_task(tasklist_id, id).await?;
+            outcome.deleted.push(id.to_owned());
+        }
+    }
+
+    outcome.content = lines.into_iter().map(|line| line + "\n").collect();
+    Ok(outcome)
+}
+
+fn same_content(local: &Entry, remote: &Entry) -> bool {
+    local.completed == remote.completed && local.title == remote.title && local.due == remote.due
+}
+
+// Takes the task fields from the remote entry, keeping the local-only priority and projects.
+fn merge_remote(local: Entry, remote: Entry) -> Entry {
+    Entry {
+        priority: local.priority,
+        projects: local.projects,
+        ..remote
+    }
+}
+
+fn project_name(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|err| ParseError(format!("invalid date {}: {}", s, err)))
+}
+
+fn parse_priority(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('('), Some(p), Some(')'), None) if p.is_ascii_uppercase() => Some(p),
+        _ => None,
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_completed_line() {
+        let entry = Entry::parse("x 2024-03-02 2024-02-01 Book flights +Travel @phone id:abc")
+            .unwrap()
+            .unwrap();
+
+        assert!(entry.completed);
+        assert_eq!(entry.completion_date, NaiveDate::from_ymd_opt(2024, 3, 2));
+        assert_eq!(entry.title, "Book flights @phone");
+        assert_eq!(entry.projects, vec!["Travel".to_owned()]);
+        assert_eq!(entry.id.as_deref(), Some("abc"));
+        assert_eq!(
+            entry.to_line(),
+            "x 2024-03-02 Book flights @phone +Travel id:abc"
+        );
+    }
+
+    #[test]
+    fn parse_open_line() {
+        let entry = Entry::parse("(A) Pack the bags due:2024-03-05 +Travel")
+            .unwrap()
+            .unwrap();
+
+        assert!(!entry.completed);
+        assert_eq!(entry.priority, Some('A'));
+        assert_eq!(entry.due, NaiveDate::from_ymd_opt(2024, 3, 5));
+        assert_eq!(entry.to_line(), "(A) Pack the bags +Travel due:2024-03-05");
+        assert!(Entry::parse("   ").unwrap().is_none());
+
+        let entry = Entry::parse("Pack due:tomorrow").unwrap().unwrap();
+        assert_eq!(entry.title, "Pack due:tomorrow");
+        assert_eq!(entry.due, None);
+    }
+
+    #[test]
+    fn task_round_trip() {
+        let tasklist = Tasklist {
+            title: Some("Summer Travel".to_owned()),
+            ..Default::default()
+        };
+        let task = Task {
+            id: Some("t1".to_owned()),
+            title: Some("Book flights".to_owned()),
+            status: Some(TaskStatus::Completed),
+            completed: Some("2024-03-02T10:00:00Z".parse().unwrap()),
+            due: Some("2024-03-05T00:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let entry = Entry::from_task(&task, Some(&tasklist));
+        assert_eq!(
+            entry.to_line(),
+            "x 2024-03-02 Book flights +Summer_Travel due:2024-03-05 id:t1"
+        );
+
+        let back = entry.to_task();
+        assert_eq!(back.status, Some(TaskStatus::Completed));
+        assert_eq!(back.due, task.due);
+        assert_eq!(
+            back.completed,
+            Some("2024-03-02T00:00:00Z".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_merges_both_sides() {
+        let fake = crate::FakeTasks::new();
+        let mut ids = HashMap::new();
+        for title in ["Both", "Local", "Remote", "Removed"] {
+            let task = Task {
+                title: Some(title.to_owned()),
+                ..Default::default()
+            };
+            let task = fake.insert_task("@default", task, None).await.unwrap();
+            ids.insert(title, task.id.unwrap());
+        }
+
+        let due = Task {
+            title: Some("Due".to_owned()),
+            due: Some("2024-03-01T00:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+        let due = fake.insert_task("@default", due, None).await.unwrap();
+        ids.insert("Due", due.id.unwrap());
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let last_sync = Utc::now();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+
+        for (title, done) in [("Both", "Both remotely"), ("Remote", "Remote")] {
+            let patch = Task {
+                title: Some(done.to_owned()),
+                status: Some(TaskStatus::Completed),
+                ..Default::default()
+            };
+            fake.patch_task("@default", &ids[title], patch)
+                .await
+                .unwrap();
+        }
+
+        // "Removed" has no line and was not changed remotely, so it is deleted
+        let input = format!(
+            "(A) Both locally id:{}\nx 2024-03-02 Local id:{}\nRemote id:{}\nDue id:{}\nNew\n",
+            ids["Both"], ids["Local"], ids["Remote"], ids["Due"]
+        );
+        let outcome = sync(&fake, "@default", &input, Some(last_sync))
+            .await
+            .unwrap();
+
+        let lines: Vec<Entry> = parse(&outcome.content).unwrap();
+        let titles: Vec<(&str, bool)> = lines
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.completed))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                ("Both remotely", true),
+                ("Local", true),
+                ("Remote", true),
+                ("Due", false),
+                ("New", false)
+            ]
+        );
+        assert_eq!(lines[0].priority, Some('A'));
+
+        assert_eq!(outcome.updated.len(), 2);
+        assert_eq!(outcome.updated[0].id.as_ref(), Some(&ids["Local"]));
+        assert_eq!(outcome.updated[0].status, Some(TaskStatus::Completed));
+        assert_eq!(outcome.updated[1].id.as_ref(), Some(&ids["Due"]));
+        assert_eq!(outcome.updated[1].due, None);
+        assert_eq!(outcome.created.len(), 1);
+        assert_eq!(outcome.deleted, [ids["Removed"].clone()]);
+
+        let local = fake
+            .get_task("@default", &ids["Local"], None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(local.status, Some(TaskStatus::Completed));
+        let both = fake
+            .get_task("@default", &ids["Both"], None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(both.title.as_deref(), Some("Both remotely"));
+        let due = fake
+            .get_task("@default", &ids["Due"], None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(due.due, None);
+    }
+}
@@ -0,0 +1,189 @@
+//! Tracing of the API calls.
+//!
+//! Every call of `tasks::*` and `tasklists::*` runs in a span named after the operation,
+//! e.g. `tasks.list`, with the task list and task ids. [`TraceMiddleware`], the innermost
+//! middleware of the client, records the HTTP status, the number of retries and the latency
+//! on that span, and logs the request and response bodies if body logging is enabled.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result as MidWareResult};
+use serde_json::Value;
+use task_local_extensions::Extensions;
+use tracing::Span;
+
+const REDACTED: &str = "REDACTED";
+
+/// Appends the trace middleware, returning the switch of its body logging.
+pub(crate) fn with_trace_middleware(
+    mut middleware: Vec<Arc<dyn Middleware>>,
+) -> (Vec<Arc<dyn Middleware>>, Arc<AtomicBool>) {
+    let body_logging = Arc::new(AtomicBool::new(false));
+    middleware.push(Arc::new(TraceMiddleware {
+        body_logging: body_logging.clone(),
+    }));
+    (middleware, body_logging)
+}
+
+pub(crate) struct TraceMiddleware {
+    body_logging: Arc<AtomicBool>,
+}
+
+// Attempts of a request, shared by the retries through the extensions.
+struct Attempts {
+    started: DateTime<Utc>,
+    count: u32,
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl Middleware for TraceMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MidWareResult<Response> {
+        let span = Span::current();
+        if extensions.get::<Attempts>().is_none() {
+            extensions.insert(Attempts {
+                started: Utc::now(),
+                count: 0,
+            });
+        }
+        let started = match extensions.get_mut::<Attempts>() {
+            Some(attempts) => {
+                span.record("retries", attempts.count);
+                attempts.count += 1;
+                attempts.started
+            }
+            None => Utc::now(),
+        };
+
+        let log_bodies = self.body_logging.load(Ordering::Relaxed);
+        if log_bodies {
+            tracing::debug!(
+                method = %req.method(),
+                url = %req.url(),
+                headers = ?redacted_headers(req.headers()),
+                body = %req.body().and_then(|b| b.as_bytes()).map(redacted_body).unwrap_or_default(),
+                "request"
+            );
+        }
+
+        let result = next.run(req, extensions).await;
+        span.record("latency_ms", (Utc::now() - started).num_milliseconds());
+
+        let resp = match result {
+            Ok(resp) => resp,
+            Err(err) => {
+                tracing::debug!(error = %err, "request failed");
+                return Err(err);
+            }
+        };
+        span.record("status", resp.status().as_u16());
+
+        match log_bodies {
+            true => log_response(resp).await,
+            false => Ok(resp),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn log_response(resp: Response) -> MidWareResult<Response> {
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let body = resp.bytes().await?;
+    tracing::debug!(
+        status = status.as_u16(),
+        headers = ?redacted_headers(&headers),
+        body = %redacted_body(&body),
+        "response"
+    );
+
+    let mut builder = http::Response::builder().status(status);
+    if let Some(response_headers) = builder.headers_mut() {
+        *response_headers = headers;
+    }
+    let resp = builder.body(body).map_err(|err| anyhow::anyhow!(err))?;
+    Ok(Response::from(resp))
+}
+
+// The response of the fetch API cannot be rebuilt, so only its status and headers are logged.
+#[cfg(target_arch = "wasm32")]
+async fn log_response(resp: Response) -> MidWareResult<Response> {
+    tracing::debug!(
+        status = resp.status().as_u16(),
+        headers = ?redacted_headers(resp.headers()),
+        "response"
+    );
+    Ok(resp)
+}
+
+fn redacted_headers(headers: &HeaderMap<HeaderValue>) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match name == AUTHORIZATION {
+                true => REDACTED.to_owned(),
+                false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            };
+            (name.as_str().to_owned(), value)
+        })
+        .collect()
+}
+
+// Redacts the notes of the tasks in a JSON body, other bodies are logged as they are.
+fn redacted_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            redact_notes(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+fn redact_notes(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                match name.as_str() {
+                    "notes" => *field = Value::String(REDACTED.to_owned()),
+                    _ => redact_notes(field),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_notes),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_notes_and_authorization() {
+        let body = br#"{"items":[{"id":"1","title":"call","notes":"pin 1234"}],"notes":"x"}"#;
+        let redacted: Value = serde_json::from_str(&redacted_body(body)).unwrap();
+        assert_eq!(redacted["items"][0]["notes"], REDACTED);
+        assert_eq!(redacted["items"][0]["title"], "call");
+        assert_eq!(redacted["notes"], REDACTED);
+
+        assert_eq!(redacted_body(b"not json"), "not json");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.insert("if-none-match", HeaderValue::from_static("\"1\""));
+        let redacted = redacted_headers(&headers);
+        assert_eq!(redacted["authorization"], REDACTED);
+        assert_eq!(redacted["if-none-match"], "\"1\"");
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_derive::Serialize;
+use tokio::sync::mpsc::Sender;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::errors::{Result, TasksError};
+use crate::{Service, Task, TaskOptions, TaskStatus, Tasklist, TasklistsOptions};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+const PAGE_SIZE: u64 = 100;
+
+/// Change detected by the [`Watcher`] between two polls.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A task appeared in the watched task list.
+    TaskCreated { tasklist_id: String, task: Task },
+
+    /// A task was modified, but not completed.
+    TaskUpdated { tasklist_id: String, task: Task },
+
+    /// A task changed its status to completed.
+    TaskCompleted { tasklist_id: String, task: Task },
+
+    /// A task was deleted or is no longer returned by the API.
+    /// The task holds the last known state.
+    TaskDeleted { tasklist_id: String, task: Task },
+
+    /// A watched task list changed its title.
+    TasklistRenamed {
+        tasklist_id: String,
+        old_title: Option<String>,
+        tasklist: Tasklist,
+    },
+}
+
+#[derive(Default)]
+struct ListState {
+    etag: Option<String>,
+    title: Option<String>,
+    tasks: HashMap<String, Task>,
+    initialized: bool,
+}
+
+/// Watcher polls the chosen task lists and reports the changes as [`Event`]s.
+///
+/// Task lists are requested with the `If-None-Match` header,
+/// so a list which has not changed since the previous poll is not downloaded again.
+/// The first poll only records the current state and does not emit any events.
+pub struct Watcher {
+    service: Arc<Service>,
+    interval: Duration,
+    lists: Vec<(String, ListState)>,
+}
+
+impl Watcher {
+    /// Creates a new watcher for the given task list ids.
+    pub fn new<I, S>(service: Arc<Service>, tasklist_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let lists = tasklist_ids
+            .into_iter()
+            .map(|id| (id.into(), ListState::default()))
+            .collect();
+
+        Watcher {
+            service,
+            interval: DEFAULT_INTERVAL,
+            lists,
+        }
+    }
+
+    /// Sets the polling interval used by [`Watcher::run`]. The default is 60 seconds.
+    ///
+    /// Fails if the interval is zero.
+    pub fn interval(mut self, interval: Duration) -> Result<Self> {
+        if interval.is_zero() {
+            return Err(TasksError::InvalidArgument(
+                "the polling interval must not be zero".to_owned(),
+            ));
+        }
+        self.interval = interval;
+        Ok(self)
+    }
+
+    /// Polls the task lists once and returns the changes since the previous poll.
+    pub async fn poll(&mut self) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        let tasklists = self.fetch_tasklists().await?;
+
+        for (tasklist_id, state) in self.lists.iter_mut() {
+            if let Some(tasklist) = tasklists.get(tasklist_id.as_str()) {
+                if state.initialized && state.title != tasklist.title {
+                    events.push(Event::TasklistRenamed {
+                        tasklist_id: tasklist_id.clone(),
+                        old_title: state.title.clone(),
+                        tasklist: tasklist.clone(),
+                    });
+                }
+                state.title = tasklist.title.clone();
+            }
+
+            let (etag, tasks) = match fetch_tasks(&self.service, tasklist_id, &state.etag).await? {
+                Some(fetched) => fetched,
+                None => continue,
+            };
+
+            if state.initialized {
+                diff_tasks(tasklist_id, &state.tasks, &tasks, &mut events);
+            }
+
+            state.etag = Some(etag);
+            state.tasks = tasks;
+            state.initialized = true;
+        }
+
+        Ok(events)
+    }
+
+    /// Polls the task lists at the configured interval and sends the events to the channel.
+    /// Returns when the receiving half of the channel is closed or a request fails.
+    pub async fn run(mut self, tx: Sender<Event>) -> Result<()> {
+        let mut ticker = interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            for event in self.poll().await? {
+                if tx.send(event).await.is_err() {
+                    return Ok(());
+                }
+            }
+
+            if tx.is_closed() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn fetch_tasklists(&self) -> Result<HashMap<String, Tasklist>> {
+        let mut tasklists = HashMap::new();
+        let mut page_token = None;
+
+        loop {
+            let opts = TasklistsOptions {
+                max_results: Some(PAGE_SIZE),
+                page_token,
+            };
+
+            let page = self.service.list_tasklists(Some(opts)).await?;
+            for tasklist in page.items {
+                if let Some(id) = tasklist.id.clone() {
+                    tasklists.insert(id, tasklist);
+                }
+            }
+
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                return Ok(tasklists);
+            }
+        }
+    }
+}
+
+// Returns None if the task list has not changed since the given etag.
+async fn fetch_tasks(
+    service: &Service,
+    tasklist_id: &str,
+    etag: &Option<String>,
+) -> Result<Option<(String, HashMap<String, Task>)>> {
+    let mut tasks = HashMap::new();
+    let mut page_token = None;
+    let mut if_none_match = etag.clone();
+    let mut first_etag = None;
+
+    loop {
+        let opts = TaskOptions {
+            max_results: Some(PAGE_SIZE),
+            page_token,
+            show_completed: Some(true),
+            show_deleted: Some(true),
+            show_hidden: Some(true),
+            ..Default::default()
+        };
+
+        let page = match service
+            .list_tasks(tasklist_id, Some(opts), if_none_match.take())
+            .await?
+        {
+            Some(page) => page,
+            None => return Ok(None),
+        };
+
+        first_etag.get_or_insert(page.etag);
+        for task in page.items.unwrap_or_default() {
+            if let Some(id) = task.id.clone() {
+                tasks.insert(id, task);
+            }
+        }
+
+        page_token = page.next_page_token;
+        if page_token.is_none() {
+            return Ok(first_etag.map(|etag| (etag, tasks)));
+        }
+    }
+}
+
+fn diff_tasks(
+    tasklist_id: &str,
+    old: &HashMap<String, Task>,
+    new: &HashMap<String, Task>,
+    events: &mut Vec<Event>,
+) {
+    for (id, task) in new.iter() {
+        let prev = old.get(id).filter(|prev| !is_deleted(prev));
+
+        let event: fn(String, Task) -> Event = match prev {
+            None if is_deleted(task) => continue,
+            None => |tasklist_id, task| Event::TaskCreated { tasklist_id, task },
+            Some(_) if is_deleted(task) => {
+                |tasklist_id, task| Event::TaskDeleted { tasklist_id, task }
+            }
+            Some(prev) if prev.etag == task.etag => continue,
+            Some(prev)
+                if prev.status != Some(TaskStatus::Completed)
+                    && task.status == Some(TaskStatus::Completed) =>
+            {
+                |tasklist_id, task| Event::TaskCompleted { tasklist_id, task }
+            }
+            Some(_) => |tasklist_id, task| Event::TaskUpdated { tasklist_id, task },
+        };
+
+        events.push(event(tasklist_id.to_owned(), task.clone()));
+    }
+
+    for (id, task) in old.iter() {
+        if !new.contains_key(id) && !is_deleted(task) {
+            events.push(Event::TaskDeleted {
+                tasklist_id: tasklist_id.to_owned(),
+                task: task.clone(),
+            });
+        }
+    }
+}
+
+fn is_deleted(task: &Task) -> bool {
+    task.deleted.unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, etag: &str) -> Task {
+        Task {
+            id: Some(id.to_owned()),
+            etag: Some(etag.to_owned()),
+            title: Some(id.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn tasks<const N: usize>(items: [Task; N]) -> HashMap<String, Task> {
+        items
+            .into_iter()
+            .map(|task| (task.id.clone().unwrap(), task))
+            .collect()
+    }
+
+    fn kinds(events: &[Event]) -> Vec<(&'static str, String)> {
+        let mut kinds: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                Event::TaskCreated { task, .. } => ("created", task.id.clone().unwrap()),
+                Event::TaskUpdated { task, .. } => ("updated", task.id.clone().unwrap()),
+                Event::TaskCompleted { task, .. } => ("completed", task.id.clone().unwrap()),
+                Event::TaskDeleted { task, .. } => ("deleted", task.id.clone().unwrap()),
+                Event::TasklistRenamed { tasklist_id, .. } => ("renamed", tasklist_id.clone()),
+            })
+            .collect();
+        kinds.sort();
+        kinds
+    }
+
+    #[test]
+    fn diff_tasks_reports_changes() {
+        let old = tasks([
+            task("same", "1"),
+            task("edited", "1"),
+            task("done", "1"),
+            task("removed", "1"),
+            task("gone", "1"),
+        ]);
+
+        let mut done = task("done", "2");
+        done.status = Some(TaskStatus::Completed);
+        let mut removed = task("removed", "2");
+        removed.deleted = Some(true);
+        let mut deleted_new = task("deleted_new", "1");
+        deleted_new.deleted = Some(true);
+        let new = tasks([
+            task("same", "1"),
+            task("edited", "2"),
+            done,
+            removed,
+            task("added", "1"),
+            deleted_new,
+        ]);
+
+        let mut events = Vec::new();
+        diff_tasks("list", &old, &new, &mut events);
+        assert_eq!(
+            kinds(&events),
+            [
+                ("completed", "done".to_owned()),
+                ("created", "added".to_owned()),
+                ("deleted", "gone".to_owned()),
+                ("deleted", "removed".to_owned()),
+                ("updated", "edited".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_tasks_reports_completed_tasks_as_updated() {
+        let mut before = task("a", "1");
+        before.status = Some(TaskStatus::Completed);
+        let mut after = task("a", "2");
+        after.status = Some(TaskStatus::Completed);
+
+        let mut events = Vec::new();
+        diff_tasks("list", &tasks([before]), &tasks([after]), &mut events);
+        assert_eq!(kinds(&events), [("updated", "a".to_owned())]);
+    }
+
+    #[test]
+    fn rejects_zero_interval() {
+        let service = Arc::new(Service::with_token("token").unwrap());
+        let watcher = Watcher::new(service, ["list"]);
+        assert!(matches!(
+            watcher.interval(Duration::ZERO),
+            Err(TasksError::InvalidArgument(_))
+        ));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn poll_skips_unchanged_lists() {
+        use crate::testing::FakeServer;
+        use crate::{TaskInsertOptions, TasksApi};
+
+        let server = FakeServer::start().unwrap();
+        let service = Arc::new(server.service().unwrap());
+        let mut watcher = Watcher::new(service.clone(), ["@default"]);
+
+        let first = Task {
+            title: Some("first".to_owned()),
+            ..Default::default()
+        };
+        let first = server
+            .tasks()
+            .insert_task("@default", first, None)
+            .await
+            .unwrap();
+        assert!(watcher.poll().await.unwrap().is_empty());
+
+        // the etag of the previous poll gets a 304 Not Modified
+        let etag = watcher.lists[0].1.etag.clone();
+        assert!(etag.is_some());
+        let fetched = fetch_tasks(&service, "@default", &etag).await.unwrap();
+        assert!(fetched.is_none());
+        assert!(watcher.poll().await.unwrap().is_empty());
+        assert_eq!(watcher.lists[0].1.etag, etag);
+
+        let second = Task {
+            title: Some("second".to_owned()),
+            ..Default::default()
+        };
+        let after_first = TaskInsertOptions {
+            parent: None,
+            previous: first.id,
+        };
+        server
+            .tasks()
+            .insert_task("@default", second, Some(after_first))
+            .await
+            .unwrap();
+        let events = watcher.poll().await.unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [Event::TaskCreated { task, .. }] if task.title.as_deref() == Some("second")
+        ));
+        assert_ne!(watcher.lists[0].1.etag, etag);
+    }
+}
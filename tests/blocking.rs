@@ -0,0 +1,53 @@
+use std::sync::mpsc;
+use std::thread;
+
+use gtasks::blocking::Service;
+use gtasks::testing::FakeServer;
+use gtasks::{Task, TaskOptions, TaskStatus};
+
+// Runs the fake server on a runtime of its own, the blocking client must be called outside of it.
+fn start_server() -> String {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let server = FakeServer::start().unwrap();
+            tx.send(server.base_url()).unwrap();
+            std::future::pending::<()>().await;
+        });
+    });
+    rx.recv().unwrap()
+}
+
+#[test]
+fn blocking_service() {
+    let service = Service::with_token("Bearer token")
+        .unwrap()
+        .with_base_url(&start_server());
+
+    let tasklists = service.list_tasklists(None).unwrap();
+    assert_eq!(tasklists.items.len(), 1);
+
+    let task = Task {
+        title: Some("sync".to_owned()),
+        ..Default::default()
+    };
+    let task = service.insert_task("@default", task, None).unwrap();
+    let done = Task {
+        status: Some(TaskStatus::Completed),
+        ..Default::default()
+    };
+    service
+        .patch_task("@default", task.id.as_deref().unwrap(), done)
+        .unwrap();
+
+    let open = TaskOptions {
+        show_completed: Some(false),
+        ..Default::default()
+    };
+    let tasks = service.list_tasks("@default", Some(open), None).unwrap();
+    assert!(tasks.unwrap().items.is_none());
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use gtasks::cassette::Cassette;
+use gtasks::testing::FakeServer;
+use gtasks::{Service, Task, TasksError};
+
+#[tokio::test]
+async fn records_and_replays() {
+    let path = std::env::temp_dir().join(format!("gtasks-cassette-{}.json", std::process::id()));
+    let server = FakeServer::start().unwrap();
+    let base_url = server.base_url();
+
+    let recorder = Arc::new(Cassette::record(&path));
+    let service = Service::with_middleware(|| Ok("Bearer secret".to_owned()), vec![recorder])
+        .unwrap()
+        .with_base_url(&base_url);
+    let task = Task {
+        title: Some("recorded".to_owned()),
+        ..Default::default()
+    };
+    let created = service
+        .insert_task("@default", task.clone(), None)
+        .await
+        .unwrap();
+    drop(server);
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!content.contains("secret"));
+
+    let player = Arc::new(Cassette::replay(&path).unwrap());
+    let service = Service::with_middleware(|| Ok("Bearer other".to_owned()), vec![player])
+        .unwrap()
+        .with_base_url(&base_url);
+    let replayed = service.insert_task("@default", task, None).await.unwrap();
+    assert_eq!(replayed.id, created.id);
+
+    let unknown = service.get_task("@default", "unknown", None).await;
+    assert!(matches!(unknown, Err(TasksError::MiddlewareError(_))));
+
+    std::fs::remove_file(&path).unwrap();
+}
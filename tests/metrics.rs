@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use gtasks::metrics::Metrics;
+use gtasks::testing::FakeServer;
+use gtasks::Service;
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn counts_requests_and_cache_hits() {
+    let server = FakeServer::start().unwrap();
+    let metrics = Arc::new(Metrics::new());
+    let service = Service::with_middleware(|| Ok("Bearer token".to_owned()), vec![metrics.clone()])
+        .unwrap()
+        .with_base_url(&server.base_url());
+
+    let tasks = service
+        .list_tasks("@default", None, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let unchanged = service
+        .list_tasks("@default", None, Some(tasks.etag))
+        .await
+        .unwrap();
+    assert!(unchanged.is_none());
+    assert!(service.get_tasklist("missing").await.is_err());
+
+    assert_eq!(metrics.requests("tasks.list", StatusCode::OK), 1);
+    assert_eq!(metrics.requests("tasks.list", StatusCode::NOT_MODIFIED), 1);
+    assert_eq!(metrics.requests("tasklists.get", StatusCode::NOT_FOUND), 1);
+    assert_eq!(metrics.cache_hits("tasks.list"), 1);
+
+    let text = metrics.prometheus();
+    assert!(text.contains("gtasks_requests_total{operation=\"tasklists.get\",status=\"404\"} 1"));
+    assert!(text.contains("gtasks_request_duration_seconds_count{operation=\"tasks.list\"} 2"));
+}
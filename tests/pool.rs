@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use gtasks::pool::{AccountPool, Budget};
+use gtasks::testing::FakeServer;
+use gtasks::{Task, Tasklist, TasksApi};
+
+#[tokio::test]
+async fn runs_across_accounts() {
+    let alice = FakeServer::start().unwrap();
+    let bob = FakeServer::start().unwrap();
+    let work = Tasklist {
+        title: Some("Work".to_owned()),
+        ..Default::default()
+    };
+    bob.tasks().insert_tasklist(work).await.unwrap();
+
+    let mut pool = AccountPool::new()
+        .unwrap()
+        .with_concurrency(1)
+        .with_base_url(&alice.base_url());
+    pool.insert("alice", || Ok("Bearer alice".to_owned()));
+    let mut pool = pool.with_base_url(&bob.base_url());
+    let budget = Budget {
+        requests: 1,
+        per: Duration::from_millis(50),
+    };
+    pool.insert_with_budget("bob", || Ok("Bearer bob".to_owned()), Some(budget));
+    pool.insert("carol", || Err(anyhow::anyhow!("token expired")));
+    assert_eq!(
+        pool.accounts().collect::<Vec<_>>(),
+        ["alice", "bob", "carol"]
+    );
+
+    let counts = pool
+        .run(|_, service| async move {
+            let tasklists = service.list_tasklists(None).await?;
+            service.list_tasklists(None).await?;
+            Ok(tasklists.items.len())
+        })
+        .await;
+    assert_eq!(counts["alice"].as_ref().unwrap(), &1);
+    assert_eq!(counts["bob"].as_ref().unwrap(), &2);
+    assert!(counts["carol"].is_err());
+}
+
+#[tokio::test]
+async fn revalidates_cached_responses() {
+    let server = FakeServer::start().unwrap();
+    let mut pool = AccountPool::new()
+        .unwrap()
+        .with_base_url(&server.base_url());
+    pool.insert("alice", || Ok("Bearer alice".to_owned()));
+    let service = pool.get("alice").unwrap();
+
+    let task = Task {
+        title: Some("a".to_owned()),
+        ..Default::default()
+    };
+    let task = service.insert_task("@default", task, None).await.unwrap();
+    let id = task.id.as_deref().unwrap();
+
+    let first = service
+        .get_task("@default", id, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let cached = service
+        .get_task("@default", id, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cached.etag, first.etag);
+
+    let renamed = Task {
+        title: Some("b".to_owned()),
+        ..Default::default()
+    };
+    service.patch_task("@default", id, renamed).await.unwrap();
+    let changed = service
+        .get_task("@default", id, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(changed.title.as_deref(), Some("b"));
+
+    // an ETag of the caller is answered by the server, not by the cache
+    let unchanged = service
+        .get_task("@default", id, changed.etag.clone())
+        .await
+        .unwrap();
+    assert!(unchanged.is_none());
+}
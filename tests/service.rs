@@ -0,0 +1,279 @@
+use gtasks::testing::FakeServer;
+use gtasks::{
+    Service, Task, TaskInsertOptions, TaskOptions, TaskStatus, Tasklist, TasklistsOptions,
+    TasksError,
+};
+
+fn task(title: &str) -> Task {
+    Task {
+        title: Some(title.to_owned()),
+        ..Default::default()
+    }
+}
+
+async fn titles(service: &Service, tasklist_id: &str, opts: TaskOptions) -> Vec<String> {
+    let tasks = service
+        .list_tasks(tasklist_id, Some(opts), None)
+        .await
+        .unwrap()
+        .unwrap();
+    tasks
+        .items
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|t| t.title)
+        .collect()
+}
+
+#[tokio::test]
+async fn tasklists_crud() {
+    let server = FakeServer::start().unwrap();
+    let service = server.service().unwrap();
+
+    let work = Tasklist {
+        title: Some("Work".to_owned()),
+        ..Default::default()
+    };
+    let work = service.insert_tasklist(work).await.unwrap();
+    let id = work.id.clone().unwrap();
+
+    let renamed = Tasklist {
+        title: Some("Office".to_owned()),
+        ..Default::default()
+    };
+    let renamed = service.patch_tasklist(&id, renamed).await.unwrap();
+    assert_eq!(renamed.title.as_deref(), Some("Office"));
+    assert_ne!(renamed.etag, work.etag);
+
+    let updated = Tasklist {
+        title: Some("Job".to_owned()),
+        ..renamed
+    };
+    service.update_tasklist(updated).await.unwrap();
+    let fetched = service.get_tasklist(&id).await.unwrap();
+    assert_eq!(fetched.title.as_deref(), Some("Job"));
+
+    let opts = TasklistsOptions {
+        max_results: Some(1),
+        page_token: None,
+    };
+    let first = service.list_tasklists(Some(opts)).await.unwrap();
+    assert_eq!(first.items.len(), 1);
+    let opts = TasklistsOptions {
+        max_results: Some(1),
+        page_token: first.next_page_token,
+    };
+    let second = service.list_tasklists(Some(opts)).await.unwrap();
+    assert_eq!(second.items[0].id.as_deref(), Some(id.as_str()));
+    assert!(second.next_page_token.is_none());
+
+    service.delete_tasklist(&id).await.unwrap();
+    assert!(matches!(
+        service.get_tasklist(&id).await,
+        Err(TasksError::ResponseError(_))
+    ));
+}
+
+#[tokio::test]
+async fn tasks_lifecycle() {
+    let server = FakeServer::start().unwrap();
+    let service = server.service().unwrap();
+
+    let a = service
+        .insert_task("@default", task("a"), None)
+        .await
+        .unwrap();
+    let opts = TaskInsertOptions {
+        parent: None,
+        previous: a.id.clone(),
+    };
+    let b = service
+        .insert_task("@default", task("b"), Some(opts))
+        .await
+        .unwrap();
+    let opts = TaskInsertOptions {
+        parent: a.id.clone(),
+        previous: None,
+    };
+    let a1 = service
+        .insert_task("@default", task("a1"), Some(opts))
+        .await
+        .unwrap();
+    assert_eq!(a1.parent, a.id);
+    assert_eq!(
+        titles(&service, "@default", TaskOptions::default()).await,
+        ["a", "a1", "b"]
+    );
+
+    let opts = TaskInsertOptions {
+        parent: None,
+        previous: b.id.clone(),
+    };
+    let moved = service
+        .move_task("@default", a1.id.as_deref().unwrap(), opts)
+        .await
+        .unwrap();
+    assert_eq!(moved.parent, None);
+    assert_eq!(
+        titles(&service, "@default", TaskOptions::default()).await,
+        ["a", "b", "a1"]
+    );
+
+    let done = Task {
+        status: Some(TaskStatus::Completed),
+        ..Default::default()
+    };
+    let done = service
+        .patch_task("@default", b.id.as_deref().unwrap(), done)
+        .await
+        .unwrap();
+    assert!(done.completed.is_some());
+
+    let open = TaskOptions {
+        show_completed: Some(false),
+        ..Default::default()
+    };
+    assert_eq!(titles(&service, "@default", open).await, ["a", "a1"]);
+
+    service.clear_tasks("@default").await.unwrap();
+    assert_eq!(
+        titles(&service, "@default", TaskOptions::default()).await,
+        ["a", "a1"]
+    );
+    let hidden = TaskOptions {
+        show_hidden: Some(true),
+        ..Default::default()
+    };
+    assert_eq!(titles(&service, "@default", hidden).await, ["a", "b", "a1"]);
+
+    let renamed = Task {
+        title: Some("A".to_owned()),
+        ..a.clone()
+    };
+    let renamed = service.update_task("@default", renamed).await.unwrap();
+    assert_eq!(renamed.title.as_deref(), Some("A"));
+
+    service
+        .delete_task("@default", a.id.as_deref().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        titles(&service, "@default", TaskOptions::default()).await,
+        ["a1"]
+    );
+    let deleted = TaskOptions {
+        show_deleted: Some(true),
+        ..Default::default()
+    };
+    assert_eq!(titles(&service, "@default", deleted).await, ["A", "a1"]);
+}
+
+#[tokio::test]
+async fn pagination_and_etags() {
+    let server = FakeServer::start().unwrap();
+    let service = server.service().unwrap();
+
+    for title in ["a", "b", "c", "d", "e"] {
+        service
+            .insert_task("@default", task(title), None)
+            .await
+            .unwrap();
+    }
+
+    let mut opts = TaskOptions {
+        max_results: Some(2),
+        ..Default::default()
+    };
+    let mut pages = Vec::new();
+    loop {
+        let page = service
+            .list_tasks("@default", Some(opts.clone()), None)
+            .await
+            .unwrap()
+            .unwrap();
+        pages.push(page.items.unwrap_or_default().len());
+        match page.next_page_token {
+            Some(token) => opts.page_token = Some(token),
+            None => break,
+        }
+    }
+    assert_eq!(pages, [2, 2, 1]);
+
+    let tasks = service
+        .list_tasks("@default", None, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let unchanged = service
+        .list_tasks("@default", None, Some(tasks.etag.clone()))
+        .await
+        .unwrap();
+    assert!(unchanged.is_none());
+
+    let first = tasks.items.unwrap().remove(0);
+    let id = first.id.as_deref().unwrap();
+    let same = service
+        .get_task("@default", id, first.etag.clone())
+        .await
+        .unwrap();
+    assert!(same.is_none());
+
+    service
+        .patch_task("@default", id, task("changed"))
+        .await
+        .unwrap();
+    let changed = service
+        .list_tasks("@default", None, Some(tasks.etag))
+        .await
+        .unwrap();
+    assert!(changed.is_some());
+}
+
+#[tokio::test]
+async fn error_bodies_and_preconditions() {
+    let server = FakeServer::start().unwrap();
+    let service = server.service().unwrap();
+
+    match service.get_task("@default", "missing", None).await {
+        Err(TasksError::ResponseError(body)) => {
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(body["error"]["code"], 404);
+            assert_eq!(body["error"]["errors"][0]["reason"], "notFound");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let created = service
+        .insert_task("@default", task("a"), None)
+        .await
+        .unwrap();
+    let url = format!(
+        "{}/lists/@default/tasks/{}",
+        server.base_url(),
+        created.id.as_deref().unwrap()
+    );
+    let client = reqwest::Client::new();
+
+    let stale = client
+        .patch(&url)
+        .header("Authorization", "Bearer token")
+        .header("If-Match", "\"stale\"")
+        .body(r#"{"title":"b"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stale.status(), 412);
+
+    let current = client
+        .patch(&url)
+        .header("Authorization", "Bearer token")
+        .header("If-Match", created.etag.as_deref().unwrap())
+        .body(r#"{"title":"b"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(current.status(), 200);
+
+    let unauthorized = client.get(&url).send().await.unwrap();
+    assert_eq!(unauthorized.status(), 401);
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use gtasks::testing::FakeServer;
+use gtasks::Task;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+type Fields = HashMap<String, String>;
+
+// Subscriber keeping the fields of the spans and of the events.
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<(&'static Metadata<'static>, Fields)>>>,
+    events: Arc<Mutex<Vec<Fields>>>,
+    entered: Arc<Mutex<Vec<Id>>>,
+}
+
+struct Visitor<'a>(&'a mut Fields);
+
+impl Visit for Visitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.to_owned());
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::new();
+        span.record(&mut Visitor(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push((span.metadata(), fields));
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+        values.record(&mut Visitor(fields));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut Visitor(&mut fields));
+        if !event.metadata().target().starts_with("gtasks") {
+            return;
+        }
+        self.events.lock().unwrap().push(fields);
+    }
+
+    fn enter(&self, span: &Id) {
+        self.entered.lock().unwrap().push(span.clone());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.entered.lock().unwrap().pop();
+    }
+
+    fn current_span(&self) -> Current {
+        let spans = self.spans.lock().unwrap();
+        match self.entered.lock().unwrap().last() {
+            Some(id) => Current::new(id.clone(), spans[id.into_u64() as usize - 1].0),
+            None => Current::none(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn records_spans_and_redacted_bodies() {
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let server = FakeServer::start().unwrap();
+    let service = server.service().unwrap().with_body_logging(true);
+
+    let task = Task {
+        title: Some("call".to_owned()),
+        notes: Some("pin 1234".to_owned()),
+        ..Default::default()
+    };
+    let task = service.insert_task("@default", task, None).await.unwrap();
+    assert_eq!(task.notes.as_deref(), Some("pin 1234"));
+    assert!(service.get_task("@default", "missing", None).await.is_err());
+
+    let spans: Vec<_> = recorder
+        .spans
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(metadata, _)| metadata.target().starts_with("gtasks"))
+        .cloned()
+        .collect();
+    let (metadata, insert) = &spans[0];
+    assert_eq!(metadata.name(), "tasks.insert");
+    assert_eq!(insert["tasklist_id"], "@default");
+    assert_eq!(insert["status"], "200");
+    assert_eq!(insert["retries"], "0");
+    assert!(insert.contains_key("latency_ms"));
+
+    let (metadata, get) = &spans[1];
+    assert_eq!(metadata.name(), "tasks.get");
+    assert_eq!(get["task_id"], "missing");
+    assert_eq!(get["status"], "404");
+
+    let events = recorder.events.lock().unwrap().clone();
+    let logged: Vec<&String> = events.iter().flat_map(|e| e.values()).collect();
+    assert!(logged.iter().any(|v| v.contains("REDACTED")));
+    assert!(!logged.iter().any(|v| v.contains("pin 1234")));
+    assert!(!logged.iter().any(|v| v.contains("Bearer")));
+}
@@ -0,0 +1,90 @@
+//! Runs the client against a mocked `fetch`, with `cargo test --target wasm32-unknown-unknown`
+//! and `wasm-bindgen-test-runner` as the runner.
+
+#![cfg(target_arch = "wasm32")]
+
+use gtasks::{Service, TaskOptions, TasksError};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen(inline_js = r#"
+export function mock_fetch(status, body) {
+    globalThis.gtasksRequests = [];
+    globalThis.fetch = async (input, init) => {
+        const req = input instanceof Request ? input : new Request(input, init);
+        globalThis.gtasksRequests.push({
+            method: req.method,
+            url: req.url,
+            authorization: req.headers.get("authorization"),
+        });
+        const resp = new Response(status === 304 ? null : body, { status });
+        // Responses built by hand have no URL, which reqwest expects.
+        Object.defineProperty(resp, "url", { value: req.url });
+        return resp;
+    };
+}
+
+export function last_request() {
+    const requests = globalThis.gtasksRequests;
+    return JSON.stringify(requests[requests.length - 1]);
+}
+"#)]
+extern "C" {
+    fn mock_fetch(status: u16, body: &str);
+    fn last_request() -> String;
+}
+
+fn last_request_json() -> serde_json::Value {
+    serde_json::from_str(&last_request()).unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn lists_tasks() {
+    mock_fetch(
+        200,
+        r#"{"kind":"tasks#tasks","etag":"\"1\"","items":[{"id":"t1","title":"wasm"}]}"#,
+    );
+
+    let service = Service::with_token("Bearer token").unwrap();
+    let opts = TaskOptions {
+        max_results: Some(5),
+        ..Default::default()
+    };
+    let tasks = service
+        .list_tasks("@default", Some(opts), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(tasks.items.unwrap()[0].title.as_deref(), Some("wasm"));
+
+    let request = last_request_json();
+    assert_eq!(request["method"], "GET");
+    assert_eq!(
+        request["url"],
+        "https://www.googleapis.com/tasks/v1/lists/@default/tasks?maxResults=5"
+    );
+    assert_eq!(request["authorization"], "Bearer token");
+}
+
+#[wasm_bindgen_test]
+async fn not_modified() {
+    mock_fetch(304, "");
+
+    let service = Service::with_token("Bearer token").unwrap();
+    let tasks = service
+        .list_tasks("@default", None, Some("\"1\"".to_owned()))
+        .await
+        .unwrap();
+    assert!(tasks.is_none());
+}
+
+#[wasm_bindgen_test]
+async fn error_body() {
+    mock_fetch(404, r#"{"error":{"code":404,"message":"Not Found"}}"#);
+
+    let service = Service::with_token("Bearer token").unwrap();
+    match service.get_tasklist("missing").await {
+        Err(TasksError::ResponseError(body)) => assert!(body.contains("Not Found")),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}